@@ -1,8 +1,13 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about)]
 pub struct Cli {
+    /// Use this named vault instead of the default one
+    #[clap(long, global = true, value_name = "NAME")]
+    pub vault: Option<String>,
+
     #[clap(subcommand)]
     pub command: Command,
 }
@@ -48,6 +53,66 @@ pub enum Command {
         /// Also include archived (dot-prefixed) entries
         #[clap(long)]
         all: bool,
+        /// Mark entries whose password is overdue for rotation (decrypts every entry)
+        #[clap(long)]
+        stale: bool,
+        /// Only show entries with this tag
+        #[clap(long, value_name = "TAG")]
+        tag: Option<String>,
+        /// Show each entry's tags next to its name
+        #[clap(long)]
+        tags: bool,
+        /// Print one entry name per line instead of a tree
+        #[clap(long, conflicts_with_all = ["stale", "tag", "tags"])]
+        flat: bool,
+    },
+
+    /// Rebuild the encrypted search index (and start keeping it up to date)
+    Reindex,
+
+    /// Move or rename an entry or folder
+    Mv {
+        /// Existing entry or folder
+        from: String,
+
+        /// New name
+        to: String,
+    },
+
+    /// Show every tag and how many entries carry it
+    Tags {
+        /// Also include archived (dot-prefixed) entries
+        #[clap(long)]
+        all: bool,
+    },
+
+    /// Generate a random password for a new entry, or replace an existing one's
+    Generate {
+        /// /path/to/passwordfile
+        path: String,
+
+        /// Number of characters
+        #[clap(long, default_value_t = 24)]
+        length: usize,
+
+        /// Only use letters and digits
+        #[clap(long)]
+        no_symbols: bool,
+
+        /// Replace the password of an existing entry, keeping its fields and notes
+        #[clap(long)]
+        in_place: bool,
+    },
+
+    /// List entries whose password must be rotated soon
+    Expiring {
+        /// How far ahead to look, e.g. 14d, 2w, 3m
+        #[clap(long, default_value = "14d")]
+        within: String,
+
+        /// Also include archived (dot-prefixed) entries
+        #[clap(long)]
+        all: bool,
     },
 
     /// Hide a password file from the list command
@@ -66,6 +131,386 @@ pub enum Command {
         path: String,
     },
 
+    /// Rename every entry to one backend's extension
+    Convert {
+        /// Target extension
+        #[clap(long, value_parser = ["age", "rage"])]
+        to: String,
+    },
+
+    /// Search entry names across all vaults
+    Find {
+        /// Case-insensitive substring to look for
+        pattern: String,
+
+        /// Also match usernames, URLs, tags and field names (uses the search index)
+        #[clap(long)]
+        meta: bool,
+
+        /// Also include archived (dot-prefixed) entries
+        #[clap(long)]
+        all: bool,
+    },
+
+    /// Report weak, reused and incomplete entries
+    Audit {
+        /// Print a JSON report instead of a table
+        #[clap(long)]
+        json: bool,
+
+        /// Also include archived (dot-prefixed) entries
+        #[clap(long)]
+        all: bool,
+
+        /// Check passwords against a downloaded Pwned Passwords SHA-1 list
+        /// (the "ordered by hash" file)
+        #[clap(long, value_name = "FILE")]
+        breached: Option<PathBuf>,
+
+        /// Check passwords with the Pwned Passwords range API (only hash
+        /// prefixes are sent)
+        #[clap(
+            long,
+            value_name = "URL",
+            num_args = 0..=1,
+            default_missing_value = crate::utils::hibp::DEFAULT_API,
+            conflicts_with = "breached"
+        )]
+        hibp_api: Option<String>,
+    },
+
+    /// Export the vault as JSON, CSV or an encrypted backup bundle
+    Export {
+        /// Only export entries in this folder
+        subtree: Option<String>,
+
+        /// Output format
+        #[clap(long, value_enum, default_value = "json")]
+        format: ExportFormat,
+
+        /// Shorthand for `--format json`
+        #[clap(long, conflicts_with = "format")]
+        json: bool,
+
+        /// Only export entries whose path matches this glob, e.g. `web/*`
+        #[clap(long, value_name = "PATTERN")]
+        glob: Option<String>,
+
+        /// Leave notes out of JSON and CSV exports
+        #[clap(long)]
+        no_notes: bool,
+
+        /// Write here instead of stdout
+        #[clap(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Print plaintext even when stdout is a terminal
+        #[clap(long)]
+        force: bool,
+
+        /// Encrypt the bundle to this age recipient instead of the vault's (repeatable)
+        #[clap(long, value_name = "RECIPIENT")]
+        recipient: Vec<String>,
+
+        /// Encrypt the bundle with a passphrase instead of recipients
+        #[clap(long, conflicts_with = "recipient")]
+        passphrase: bool,
+
+        /// Also include archived (dot-prefixed) entries; bundles always do
+        #[clap(long)]
+        all: bool,
+    },
+
+    /// Import entries from another password manager
+    Import {
+        #[clap(subcommand)]
+        source: ImportSource,
+    },
+
+    /// Store entries under random file IDs, or restore their names
+    Names {
+        #[clap(subcommand)]
+        action: NamesAction,
+    },
+
+    /// Check the vault's files for damage, stray plaintext and stale recipients
+    Fsck {
+        /// Re-encrypt entries whose recipients are out of date
+        #[clap(long)]
+        fix: bool,
+    },
+
+    /// Show who can decrypt an entry, or every entry in a folder
+    Access {
+        /// Entry or folder
+        path: String,
+    },
+
+    /// Check that every file is signed by a trusted device
+    Verify {
+        /// Trust the device with this signing key (shown by `passman verify`)
+        #[clap(long, value_name = "KEY")]
+        trust: Option<String>,
+
+        /// Sign the vault's current contents as this device first
+        #[clap(long)]
+        sign: bool,
+    },
+
+    /// Enroll, list and revoke the devices that can read the vault
+    Device {
+        #[clap(subcommand)]
+        action: DeviceAction,
+    },
+
+    /// Back up the identity as Shamir shares, or rebuild it from them
+    Key {
+        #[clap(subcommand)]
+        action: KeyAction,
+    },
+
+    /// Keep the unlocked identity in a background agent, so a
+    /// passphrase-protected identity is only unlocked once
+    Agent {
+        /// Forget the identity after this many minutes without use
+        #[clap(long, value_name = "MINUTES", default_value_t = 15)]
+        timeout: u64,
+
+        #[clap(subcommand)]
+        action: Option<AgentAction>,
+    },
+
+    /// Check that keys, backend, git, completions and editor are set up
+    Doctor {
+        /// Print a JSON report instead of a list
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Manage named vaults
+    Vault {
+        #[clap(subcommand)]
+        action: VaultAction,
+    },
+
     /// Do initial setup
     Init,
 }
+
+#[derive(Subcommand, Debug)]
+pub enum ImportSource {
+    /// Import a pass (password-store) tree through the local `gpg`
+    Pass {
+        /// Store to import (defaults to $PASSWORD_STORE_DIR or ~/.password-store)
+        #[clap(long, value_name = "DIR")]
+        store: Option<PathBuf>,
+
+        /// Lines of `<gpg-id> <age-recipient>` used to translate `.gpg-id` files
+        #[clap(long, value_name = "FILE")]
+        recipient_map: Option<PathBuf>,
+
+        /// Decrypt and check everything, but write nothing
+        #[clap(long)]
+        dry_run: bool,
+    },
+
+    /// Import a password manager's CSV export
+    Csv {
+        /// Which tool produced the export
+        #[clap(long, value_enum)]
+        format: CsvFormat,
+
+        /// The exported CSV file
+        file: PathBuf,
+
+        /// Folder to create the entries under
+        #[clap(long, default_value = "imported")]
+        prefix: String,
+
+        /// What to do when an entry name is already taken
+        #[clap(long, value_enum, default_value = "skip")]
+        on_conflict: OnConflict,
+
+        /// Parse and check everything, but write nothing
+        #[clap(long)]
+        dry_run: bool,
+    },
+
+    /// Restore a bundle made by `passman export --format bundle`
+    Bundle {
+        /// The bundle file
+        file: PathBuf,
+
+        /// Identity to open recipient-encrypted bundles (defaults to the vault's)
+        #[clap(long, value_name = "FILE")]
+        identity: Option<PathBuf>,
+
+        /// What to do when an entry name is already taken
+        #[clap(long, value_enum, default_value = "skip")]
+        on_conflict: OnConflict,
+
+        /// Also write the bundle's `.recipients` files, which decide who
+        /// can read the entries below them
+        #[clap(long)]
+        restore_recipients: bool,
+
+        /// Decrypt and check everything, but write nothing
+        #[clap(long)]
+        dry_run: bool,
+    },
+
+    /// Import a KeePass database (KDBX 3.1 or 4.x)
+    Keepass {
+        /// The `.kdbx` file
+        file: PathBuf,
+
+        /// Key file, if the database uses one
+        #[clap(long, value_name = "FILE")]
+        key_file: Option<PathBuf>,
+
+        /// Folder to create the entries under
+        #[clap(long, default_value = "imported")]
+        prefix: String,
+
+        /// What to do when an entry name is already taken
+        #[clap(long, value_enum, default_value = "skip")]
+        on_conflict: OnConflict,
+
+        /// Decrypt and check everything, but write nothing
+        #[clap(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum ExportFormat {
+    /// Entry paths mapped to their fields (plaintext)
+    #[default]
+    Json,
+    /// One row per entry (plaintext)
+    Csv,
+    /// A single age-encrypted file for `passman import bundle`
+    Bundle,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum CsvFormat {
+    Bitwarden,
+    #[value(name = "1password")]
+    Onepassword,
+    Chrome,
+    Keepassxc,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum OnConflict {
+    /// Leave the existing entry alone and report the row
+    Skip,
+    /// Write to `name-2`, `name-3`, ...
+    Rename,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VaultAction {
+    /// List configured vaults
+    List,
+
+    /// Register a new named vault
+    Add {
+        /// Name used with `--vault`
+        name: String,
+
+        /// Directory holding the vault, keys, etc.
+        #[clap(long)]
+        dir: PathBuf,
+
+        /// Private identity file (defaults to `<dir>/private.<backend>`)
+        #[clap(long)]
+        identity: Option<PathBuf>,
+
+        /// Recipients file, relative to `<dir>` (defaults to `public.key`)
+        #[clap(long)]
+        recipients: Option<String>,
+
+        /// Git remote URL for the vault repository
+        #[clap(long)]
+        remote: Option<String>,
+    },
+
+    /// Make a vault the default
+    Default {
+        /// Name of a configured vault
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DeviceAction {
+    /// Print an enrollment code for this device, to approve on another one
+    Add {
+        /// Name to enroll under (defaults to the host name)
+        #[clap(long)]
+        name: Option<String>,
+    },
+
+    /// Let the device behind an enrollment code read the vault
+    Approve {
+        /// Code printed by `passman device add` on the new device
+        code: String,
+    },
+
+    /// Show the devices that can read the vault
+    List,
+
+    /// Stop encrypting to a device
+    Revoke {
+        /// Device name or recipient
+        device: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum KeyAction {
+    /// Split the identity into shares, printed as text and QR codes
+    Split {
+        /// Number of shares to make
+        #[clap(long, default_value_t = 5)]
+        shares: u8,
+
+        /// Number of shares needed to rebuild the identity
+        #[clap(long, default_value_t = 3)]
+        threshold: u8,
+    },
+
+    /// Rebuild the identity from shares
+    Combine {
+        /// Files holding shares (reads pasted shares when omitted)
+        files: Vec<PathBuf>,
+
+        /// Write the identity here instead of the configured path
+        #[clap(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AgentAction {
+    /// Wipe the identity from the agent and stop it
+    Lock,
+
+    /// Show whether an agent is running and how long until it locks
+    Status,
+
+    /// Run the agent in the foreground (started by `passman agent`)
+    #[clap(hide = true)]
+    Serve,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NamesAction {
+    /// Rename entries to random IDs and keep their names in an encrypted manifest
+    Hide,
+
+    /// Give entries their names back and delete the manifest
+    Reveal,
+}
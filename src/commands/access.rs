@@ -0,0 +1,337 @@
+use crate::config::{Config, NamedRecipient, RECIPIENTS_FILENAME, parse_named_recipients};
+use crate::utils::{age_header, entries};
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Who one entry is encrypted to, compared with who it should be
+#[derive(Debug, Default)]
+pub struct Access {
+    pub name: String,
+    /// Recipients file that decides who should read the entry
+    pub source: String,
+    /// Current recipients the file has a stanza for
+    pub readers: Vec<String>,
+    /// Current recipients the file has no stanza for
+    pub missing: Vec<String>,
+    /// Stanzas for no current recipient
+    pub extra: Vec<String>,
+    /// Why the header or the recipients file couldn't be read
+    pub error: Option<String>,
+}
+
+impl Access {
+    pub fn is_current(&self) -> bool {
+        self.error.is_none() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Show who can decrypt an entry, or every entry in a folder.
+///
+/// Usage:
+///     passman access <path|folder>
+///
+/// Reads the recipient stanzas in each file's age header (nothing is
+/// decrypted) and compares them with the recipients file that governs the
+/// entry: the nearest `.recipients`, else the public key file. Recipients
+/// are shown by the names those files give them (`# device:` lines, SSH
+/// key comments). SSH stanzas identify their key; X25519 stanzas don't, so
+/// those are only counted and never named. Entries whose stanzas differ
+/// from their recipients are flagged (`passman fsck --fix` re-encrypts
+/// them) and make the command exit non-zero.
+pub fn run(config: &Config, path: &str) -> Result<()> {
+    let report = check(config, path)?;
+    for access in &report {
+        print(access);
+    }
+
+    let stale = report.iter().filter(|a| !a.is_current()).count();
+    if report
+        .iter()
+        .flat_map(|a| a.readers.iter().chain(&a.missing))
+        .any(|r| r.contains("X25519"))
+    {
+        println!(
+            "ℹ️  X25519 stanzas don't say which key they are for; they are counted, not named."
+        );
+    }
+    if stale == 0 {
+        println!("✅ {} entries match their recipients", report.len());
+        return Ok(());
+    }
+    println!(
+        "⚠️  {} of {} entries don't match their recipients; `passman fsck --fix` re-encrypts them",
+        stale,
+        report.len()
+    );
+    anyhow::bail!("{} entries don't match their recipients", stale);
+}
+
+/// Access to the entry `path`, or to every entry below the folder `path`
+pub fn check(config: &Config, path: &str) -> Result<Vec<Access>> {
+    let wanted = path.trim_matches('/');
+    let folder = format!("{}/", wanted);
+    let files: Vec<_> = entries::collect(&config.prefix, true)?
+        .into_iter()
+        .filter(|e| wanted.is_empty() || e.name == wanted || e.name.starts_with(&folder))
+        .collect();
+    if files.is_empty() {
+        anyhow::bail!("No entry or folder `{}` in the vault", path);
+    }
+
+    let known = known_ssh_keys(config);
+    Ok(files
+        .iter()
+        .map(|e| access(config, &known, &e.name, &e.path))
+        .collect())
+}
+
+fn access(config: &Config, known: &[(String, String)], name: &str, path: &Path) -> Access {
+    let source_path = config.recipients_file_for(path);
+    let mut access = Access {
+        name: name.to_string(),
+        source: source_path
+            .strip_prefix(&config.prefix)
+            .or_else(|_| source_path.strip_prefix(&config.base_dir))
+            .unwrap_or(&source_path)
+            .display()
+            .to_string(),
+        ..Default::default()
+    };
+
+    let header = match age_header::read(path) {
+        Ok(header) => header,
+        Err(e) => {
+            access.error = Some(e.root_cause().to_string());
+            return access;
+        }
+    };
+    let current = match fs::read_to_string(&source_path) {
+        Ok(raw) => parse_named_recipients(&raw),
+        Err(e) => {
+            access.error = Some(format!("can't read {}: {}", access.source, e));
+            return access;
+        }
+    };
+
+    let mut stanzas = header.recipient_kinds();
+    let mut x25519 = Vec::new();
+    for recipient in &current {
+        let kind = age_header::recipient_kind(&recipient.recipient)
+            .unwrap_or_else(|| recipient.recipient.clone());
+        if kind == "X25519" {
+            x25519.push(label(recipient, &kind));
+            continue;
+        }
+        match stanzas.iter().position(|k| *k == kind) {
+            Some(i) => {
+                stanzas.remove(i);
+                access.readers.push(label(recipient, &kind));
+            }
+            None => access.missing.push(label(recipient, &kind)),
+        }
+    }
+
+    // which X25519 key a stanza is for can't be told, only how many there are
+    let stanza_count = stanzas.iter().filter(|k| *k == "X25519").count();
+    stanzas.retain(|k| k != "X25519");
+    let matched = stanza_count.min(x25519.len());
+    if matched > 0 {
+        access.readers.insert(0, x25519_keys(matched));
+    }
+    if x25519.len() > stanza_count {
+        access.missing.push(format!(
+            "{} of {} X25519 recipients ({})",
+            x25519.len() - stanza_count,
+            x25519.len(),
+            x25519.join(", ")
+        ));
+    }
+    if stanza_count > x25519.len() {
+        access.extra.push(x25519_keys(stanza_count - x25519.len()));
+    }
+    access.extra.extend(stanzas.iter().map(|kind| {
+        known
+            .iter()
+            .find(|(k, _)| k == kind)
+            .map(|(_, name)| format!("{} ({})", name, key_type(kind)))
+            .unwrap_or_else(|| {
+                if kind.contains(' ') {
+                    format!("{} (unknown key)", kind)
+                } else {
+                    kind.clone()
+                }
+            })
+    }));
+    access
+}
+
+/// "2 X25519 keys", unattributed
+fn x25519_keys(n: usize) -> String {
+    format!("{} X25519 key{}", n, if n == 1 { "" } else { "s" })
+}
+
+/// Names for the SSH keys in any recipients file of the vault, so a stanza
+/// for a key that was removed from one folder can still be named. X25519
+/// stanzas carry nothing to look up.
+fn known_ssh_keys(config: &Config) -> Vec<(String, String)> {
+    let files = WalkDir::new(&config.prefix)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+        .filter_map(Result::ok)
+        .filter(|e| e.file_name() == RECIPIENTS_FILENAME)
+        .map(|e| e.into_path())
+        .chain(std::iter::once(
+            config.base_dir.join(&config.public_key_filename),
+        ));
+    files
+        .filter_map(|f| fs::read_to_string(f).ok())
+        .flat_map(|raw| parse_named_recipients(&raw))
+        .filter_map(|r| {
+            let kind = age_header::recipient_kind(&r.recipient)?;
+            Some((kind, r.name?))
+        })
+        .filter(|(kind, _)| kind.contains(' '))
+        .collect()
+}
+
+/// "alice@laptop (ssh-ed25519)", or the key itself when its file doesn't
+/// name it. X25519 recipients are labelled without their type, since only
+/// the count says anything about them.
+fn label(recipient: &NamedRecipient, kind: &str) -> String {
+    match &recipient.name {
+        Some(name) if kind == "X25519" => name.clone(),
+        Some(name) => format!("{} ({})", name, key_type(kind)),
+        None if kind == "X25519" => short(&recipient.recipient),
+        None => kind.to_string(),
+    }
+}
+
+fn key_type(kind: &str) -> &str {
+    kind.split(' ').next().unwrap_or(kind)
+}
+
+/// `age1ql3z7hjy…ac8p`
+fn short(recipient: &str) -> String {
+    let chars: Vec<char> = recipient.chars().collect();
+    if chars.len() <= 16 {
+        return recipient.to_string();
+    }
+    let head: String = chars[..12].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", head, tail)
+}
+
+fn print(access: &Access) {
+    println!("🔑 {}  (recipients from {})", access.name, access.source);
+    if let Some(error) = &access.error {
+        println!("   ✗ {}", error);
+        return;
+    }
+    if !access.readers.is_empty() {
+        println!("   readable by: {}", access.readers.join(", "));
+    }
+    if !access.missing.is_empty() {
+        println!("   ⚠️  not encrypted to: {}", access.missing.join(", "));
+    }
+    if !access.extra.is_empty() {
+        println!("   ⚠️  still encrypted to: {}", access.extra.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use std::io::Write;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    const ALICE: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHsKLqeplhpW+uObz5dvMgjz1OxfM/XXUB+VHtZ6isGN alice@laptop";
+
+    fn key() -> String {
+        age::x25519::Identity::generate().to_public().to_string()
+    }
+
+    /// Encrypt with the age crate, so the header is the real thing
+    fn encrypt(path: &Path, recipients: &[&str]) -> Result<()> {
+        let recipients: Vec<Box<dyn age::Recipient>> = recipients
+            .iter()
+            .map(|r| -> Box<dyn age::Recipient> {
+                if r.starts_with("ssh-") {
+                    Box::new(age::ssh::Recipient::from_str(r).unwrap())
+                } else {
+                    Box::new(age::x25519::Recipient::from_str(r).unwrap())
+                }
+            })
+            .collect();
+        let encryptor = age::Encryptor::with_recipients(recipients.iter().map(|r| r.as_ref()))?;
+        fs::create_dir_all(path.parent().unwrap())?;
+        let mut writer = encryptor.wrap_output(fs::File::create(path)?)?;
+        writer.write_all(b"pw")?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn make_config(tmp: &TempDir) -> Config {
+        let cfg = test_config(tmp.path());
+        fs::create_dir_all(cfg.prefix.join("ops")).unwrap();
+        cfg
+    }
+
+    #[test]
+    fn readers_are_named_from_the_recipients_file() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let laptop = key();
+        fs::write(tmp.path().join("public.key"), key())?;
+        fs::write(
+            cfg.prefix.join("ops").join(RECIPIENTS_FILENAME),
+            format!("# device: laptop\n{}\n{}\n", laptop, ALICE),
+        )?;
+        encrypt(&cfg.entry_path("ops/prod-db")?, &[&laptop, ALICE])?;
+        encrypt(&cfg.entry_path("web")?, &[&key()])?;
+
+        let report = check(&cfg, "ops/prod-db")?;
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].source, "ops/.recipients");
+        assert_eq!(
+            report[0].readers,
+            vec!["1 X25519 key", "alice@laptop (ssh-ed25519)"]
+        );
+        assert!(report[0].is_current());
+        run(&cfg, "ops")?;
+
+        let all = check(&cfg, "/")?;
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].source, "public.key");
+        assert!(check(&cfg, "op").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn stale_entries_are_flagged() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let (laptop, desk) = (key(), key());
+        // alice is only known from another file now
+        fs::write(tmp.path().join("public.key"), ALICE)?;
+        encrypt(&cfg.entry_path("ops/prod-db")?, &[&laptop, ALICE])?;
+        fs::write(
+            cfg.prefix.join("ops").join(RECIPIENTS_FILENAME),
+            format!("# device: laptop\n{}\n# device: desk\n{}\n", laptop, desk),
+        )?;
+
+        let report = check(&cfg, "ops")?;
+        assert_eq!(report[0].readers, vec!["1 X25519 key"]);
+        assert_eq!(
+            report[0].missing,
+            vec!["1 of 2 X25519 recipients (laptop, desk)"]
+        );
+        assert_eq!(report[0].extra, vec!["alice@laptop (ssh-ed25519)"]);
+        assert!(!report[0].is_current());
+        assert!(run(&cfg, "ops").is_err(), "stale entries exit non-zero");
+        Ok(())
+    }
+}
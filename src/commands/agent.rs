@@ -0,0 +1,155 @@
+use crate::cli::AgentAction;
+use crate::config::Config;
+use crate::crypto::{SecretString, identity};
+use crate::utils::agent::{self, Reply, Request, Server};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use zeroize::Zeroize;
+
+/// What `passman agent` passes to the daemon it starts, on its stdin
+#[derive(Serialize, Deserialize)]
+struct Handoff {
+    identity: PathBuf,
+    key: String,
+}
+
+impl Drop for Handoff {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+/// Keep a passphrase-protected identity unlocked for a while.
+///
+/// Usage:
+///     passman agent [--timeout MINUTES]
+///     passman agent lock
+///     passman agent status
+///
+/// `passman agent` asks for the passphrase once and starts a daemon that
+/// listens on a socket in `$XDG_RUNTIME_DIR/passman`. Other commands send
+/// their decryptions there instead of asking again; only processes of the
+/// same user are answered. The daemon wipes the key and exits after
+/// `--timeout` minutes without a decryption, or on `passman agent lock`.
+/// Without a running agent every command simply unlocks the identity itself.
+pub fn run(config: &Config, timeout: u64, action: Option<AgentAction>) -> Result<()> {
+    let idle = timeout
+        .checked_mul(60)
+        .map(Duration::from_secs)
+        .with_context(|| format!("--timeout {} minutes is too long", timeout))?;
+    match action {
+        None => start(config, timeout),
+        Some(AgentAction::Lock) => lock(),
+        Some(AgentAction::Status) => status(),
+        Some(AgentAction::Serve) => serve(idle),
+    }
+}
+
+fn start(config: &Config, timeout: u64) -> Result<()> {
+    let socket = agent::socket_path()?;
+    if let Some(Reply::Status { pid, .. }) = agent::request(&socket, &Request::Status)? {
+        println!("🔓 passman agent is already running (pid {})", pid);
+        return Ok(());
+    }
+    if !identity::is_protected(&config.secret) {
+        anyhow::bail!(
+            "{} is not passphrase-protected, so there is nothing for an agent to keep unlocked",
+            config.secret.display()
+        );
+    }
+
+    let key = identity::unlock(&config.secret)?;
+    let mut handoff = serde_json::to_vec(&Handoff {
+        identity: std::path::absolute(&config.secret)?,
+        key: key.expose().to_string(),
+    })?;
+
+    let exe = env::current_exe().context("Can't find the passman binary")?;
+    let mut child = Command::new(exe)
+        .args(["agent", "--timeout", &timeout.to_string(), "serve"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // its own process group, so Ctrl-C in this terminal doesn't reach it
+        .process_group(0)
+        .spawn()
+        .context("Failed to start the agent")?;
+    let sent = child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(&handoff);
+    handoff.zeroize();
+    sent.context("Failed to hand the identity to the agent")?;
+
+    for _ in 0..50 {
+        if let Some(Reply::Status { pid, .. }) = agent::request(&socket, &Request::Status)? {
+            println!(
+                "🔓 passman agent running (pid {}); it locks after {} minutes unused",
+                pid, timeout
+            );
+            println!("   Run `passman agent lock` to lock it now.");
+            return Ok(());
+        }
+        if let Some(status) = child.try_wait()? {
+            anyhow::bail!("The agent exited straight away ({})", status);
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    anyhow::bail!("The agent did not start listening on {}", socket.display())
+}
+
+fn lock() -> Result<()> {
+    match agent::request(&agent::socket_path()?, &Request::Lock)? {
+        Some(Reply::Locked) => println!("🔒 Agent locked; the identity is wiped from memory"),
+        Some(Reply::Error { ref message }) => anyhow::bail!("{}", message),
+        Some(_) => anyhow::bail!("Unexpected reply from the agent"),
+        None => println!("No agent is running"),
+    }
+    Ok(())
+}
+
+fn status() -> Result<()> {
+    match agent::request(&agent::socket_path()?, &Request::Status)? {
+        Some(Reply::Status {
+            pid,
+            ref identity,
+            idle_left,
+        }) => {
+            println!("🔓 passman agent running (pid {})", pid);
+            println!("   identity: {}", identity.display());
+            println!("   locks in: {}m {}s", idle_left / 60, idle_left % 60);
+        }
+        Some(Reply::Error { ref message }) => anyhow::bail!("{}", message),
+        Some(_) => anyhow::bail!("Unexpected reply from the agent"),
+        None => println!("No agent is running"),
+    }
+    Ok(())
+}
+
+/// The daemon itself: take the identity from stdin and answer requests
+fn serve(timeout: Duration) -> Result<()> {
+    if io::stdin().is_terminal() {
+        anyhow::bail!("`agent serve` is started by `passman agent`; run that instead");
+    }
+    let mut raw = Vec::new();
+    io::stdin().read_to_end(&mut raw)?;
+    let handoff = serde_json::from_slice::<Handoff>(&raw);
+    raw.zeroize();
+    let handoff = handoff.context("Malformed handoff from `passman agent`")?;
+    identity::remember(&handoff.identity, SecretString::from(handoff.key.as_str()));
+
+    let socket = agent::socket_path()?;
+    let listener = Server::bind(&socket)?;
+    let served = Server::new(handoff.identity.clone(), timeout).serve(&listener);
+    fs::remove_file(&socket).ok();
+    served
+}
@@ -1,4 +1,5 @@
 use crate::config::Config;
+use crate::utils::manifest;
 use anyhow::{Context, Result};
 use std::{fs, path::Path};
 
@@ -11,14 +12,33 @@ use std::{fs, path::Path};
 /// When archiving a file, you can omit the crypto extension; it will be auto-added.
 /// When `--folder` is set, `path` must refer to a dir (no extension logic applied).
 pub fn run(cfg: &Config, path: String, folder: bool) -> Result<()> {
+    // Hidden-names vaults archive by renaming in the manifest only
+    if manifest::is_enabled(&cfg.prefix) {
+        let path = path.trim_matches('/');
+        let (dir, base) = path.rsplit_once('/').unwrap_or(("", path));
+        if base.starts_with('.') {
+            anyhow::bail!(
+                "{} already archived",
+                if folder { "Directory" } else { "File" }
+            );
+        }
+        let hidden = match dir {
+            "" => format!(".{}", base),
+            dir => format!("{}/.{}", dir, base),
+        };
+        manifest::rename(cfg, path, &hidden)?;
+        println!("Archived {}", path);
+        return Ok(());
+    }
+
     // Compute vault root
     let vault_root = cfg.base_dir.join(&cfg.prefix);
     let mut full_path = vault_root.join(&path);
 
-    // If archiving a file and no extension present, tack on the crypto_extension
+    // If archiving a file and no extension present, find the entry
+    // under either extension (falling back to the crypto_extension)
     if !folder && full_path.extension().is_none() {
-        let ext = cfg.crypto_extension.trim_start_matches('.');
-        full_path.set_extension(ext);
+        full_path = cfg.entry_path(&path)?;
     }
 
     // Existence check
@@ -77,6 +97,7 @@ pub fn run(cfg: &Config, path: String, folder: bool) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::SyncPolicy;
     use anyhow::Result;
     use std::fs::{self, File};
     use std::io::Write;
@@ -90,6 +111,11 @@ mod tests {
             secret: tmp.path().to_path_buf(),
             crypto_extension: "rage".into(),
             public_key_filename: "public.key".into(),
+            editor: None,
+            clipboard_timeout: 45,
+            sync: SyncPolicy::Manual,
+            vault: None,
+            remote: None,
         }
     }
 
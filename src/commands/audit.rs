@@ -0,0 +1,450 @@
+use crate::config::Config;
+use crate::crypto;
+use crate::entry::Entry;
+use crate::utils::entries;
+use crate::utils::hibp::{self, Breaches, HashList, RangeApi};
+use anyhow::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+/// Below this many bits of estimated entropy a password counts as weak
+const MIN_ENTROPY_BITS: f64 = 60.0;
+
+/// Shorter than this is reported on its own, whatever the entropy
+const MIN_LENGTH: usize = 8;
+
+/// Passwords and words that show up at the top of every breach list
+const COMMON_WORDS: &[&str] = &[
+    "password", "passwort", "letmein", "welcome", "admin", "login", "master", "secret", "dragon",
+    "monkey", "shadow", "sunshine", "princess", "football", "baseball", "soccer", "hockey",
+    "superman", "batman", "trustno", "iloveyou", "starwars", "whatever", "freedom", "charlie",
+    "jordan", "michael", "jennifer", "hunter", "ginger", "summer", "winter", "spring", "autumn",
+    "flower", "cookie", "cheese", "pepper", "orange", "banana", "computer", "internet", "google",
+    "changeme", "default", "qazwsx", "killer", "access", "mustang", "matrix", "ninja", "azerty",
+];
+
+/// Rows that people run a finger along
+const SEQUENCES: &[&str] = &[
+    "qwertyuiop",
+    "asdfghjkl",
+    "zxcvbnm",
+    "qwertzuiop",
+    "azertyuiop",
+    "1234567890",
+    "abcdefghijklmnopqrstuvwxyz",
+];
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Problem {
+    Undecryptable,
+    Breached,
+    Weak,
+    Short,
+    Reused,
+    NoUsername,
+    NoUrl,
+}
+
+impl Problem {
+    fn label(self) -> &'static str {
+        match self {
+            Problem::Undecryptable => "undecryptable",
+            Problem::Breached => "breached",
+            Problem::Weak => "weak",
+            Problem::Short => "short",
+            Problem::Reused => "reused",
+            Problem::NoUsername => "no username",
+            Problem::NoUrl => "no URL",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Finding {
+    pub entry: String,
+    pub problem: Problem,
+    pub detail: String,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    entries: usize,
+    summary: BTreeMap<Problem, usize>,
+    findings: &'a [Finding],
+}
+
+#[derive(Default)]
+pub struct Options {
+    pub json: bool,
+    pub show_all: bool,
+    /// Local Pwned Passwords SHA-1 list (ordered by hash)
+    pub breached: Option<PathBuf>,
+    /// Base URL of a Pwned Passwords range API
+    pub hibp_api: Option<String>,
+}
+
+/// Check every entry's password health.
+///
+/// Usage:
+///     passman audit [--json] [--all] [--breached FILE | --hibp-api [URL]]
+///
+/// Reports weak passwords (low estimated entropy, dictionary words, keyboard
+/// runs), passwords shared between entries (compared by hash; values are
+/// never printed), very short ones, and entries without a username or URL.
+/// Exits non-zero when anything is found, so it can run from cron or CI.
+///
+/// `--breached` also looks every password up in a downloaded Have I Been
+/// Pwned hash list, entirely offline. `--hibp-api` asks the range API
+/// instead, which only ever sees the first five hex digits of each hash.
+pub fn run(config: &Config, opts: Options) -> Result<()> {
+    let mut source: Option<Box<dyn Breaches>> = match (&opts.breached, &opts.hibp_api) {
+        (Some(path), _) => Some(Box::new(HashList::open(path)?)),
+        (None, Some(url)) => Some(Box::new(RangeApi::new(url))),
+        (None, None) => None,
+    };
+
+    let files = entries::collect(&config.prefix, opts.show_all)?;
+    let mut findings = Vec::new();
+    let mut decrypted = Vec::new();
+    for file in &files {
+        match crypto::decrypt(&config.secret, &file.path) {
+            Ok(plaintext) => decrypted.push((file.name.clone(), Entry::parse(plaintext.expose()))),
+            Err(e) => findings.push(Finding {
+                entry: file.name.clone(),
+                problem: Problem::Undecryptable,
+                detail: format!("{:#}", e),
+            }),
+        }
+    }
+    findings.extend(audit(&decrypted));
+    if let Some(source) = source.as_deref_mut() {
+        findings.extend(breached(&decrypted, source)?);
+    }
+    findings.sort_by(|a, b| (&a.entry, a.problem).cmp(&(&b.entry, b.problem)));
+
+    let mut summary = BTreeMap::new();
+    for f in &findings {
+        *summary.entry(f.problem).or_insert(0) += 1;
+    }
+    if opts.json {
+        let report = Report {
+            entries: files.len(),
+            summary,
+            findings: &findings,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_table(files.len(), &summary, &findings);
+    }
+
+    if !findings.is_empty() {
+        anyhow::bail!("{} problems found", findings.len());
+    }
+    Ok(())
+}
+
+fn print_table(total: usize, summary: &BTreeMap<Problem, usize>, findings: &[Finding]) {
+    if findings.is_empty() {
+        println!("✅ {} entries checked, no problems found", total);
+        return;
+    }
+    let width = findings
+        .iter()
+        .map(|f| f.entry.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("ENTRY".len());
+    println!("{:<width$}  {:<13}  DETAIL", "ENTRY", "PROBLEM");
+    for f in findings {
+        println!(
+            "{:<width$}  {:<13}  {}",
+            f.entry,
+            f.problem.label(),
+            f.detail
+        );
+    }
+    println!();
+    let counts: Vec<String> = summary
+        .iter()
+        .map(|(p, n)| format!("{} {}", n, p.label()))
+        .collect();
+    println!("⚠️  {} entries checked: {}", total, counts.join(", "));
+}
+
+/// Every problem found in already-decrypted entries
+pub fn audit(entries: &[(String, Entry)]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut by_hash: HashMap<[u8; 32], Vec<&str>> = HashMap::new();
+
+    for (name, entry) in entries {
+        let mut found = |problem, detail: String| {
+            findings.push(Finding {
+                entry: name.clone(),
+                problem,
+                detail,
+            })
+        };
+        let password = entry.password.as_str();
+
+        let length = password.chars().count();
+        if length < MIN_LENGTH {
+            found(Problem::Short, format!("{} characters", length));
+        }
+        if let Some(reason) = weakness(password) {
+            found(Problem::Weak, reason);
+        }
+        if ["username", "user", "login", "email"]
+            .iter()
+            .all(|k| entry.field(k).is_none())
+        {
+            found(Problem::NoUsername, "no username field".into());
+        }
+        if ["url", "website"].iter().all(|k| entry.field(k).is_none()) {
+            found(Problem::NoUrl, "no url field".into());
+        }
+
+        if !password.is_empty() {
+            by_hash
+                .entry(Sha256::digest(password).into())
+                .or_default()
+                .push(name);
+        }
+    }
+
+    for names in by_hash.values().filter(|n| n.len() > 1) {
+        for name in names {
+            let others: Vec<&str> = names.iter().filter(|n| *n != name).copied().collect();
+            findings.push(Finding {
+                entry: name.to_string(),
+                problem: Problem::Reused,
+                detail: format!("same password as {}", others.join(", ")),
+            });
+        }
+    }
+    findings
+}
+
+/// Entries whose password shows up in `source`
+pub fn breached(entries: &[(String, Entry)], source: &mut dyn Breaches) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    let mut seen: HashMap<String, Option<u64>> = HashMap::new();
+    for (name, entry) in entries {
+        if entry.password.is_empty() {
+            continue;
+        }
+        let hash = hibp::sha1_hex(&entry.password);
+        let count = match seen.get(&hash) {
+            Some(count) => *count,
+            None => {
+                let count = source.count(&hash)?;
+                seen.insert(hash, count);
+                count
+            }
+        };
+        if let Some(count) = count {
+            findings.push(Finding {
+                entry: name.clone(),
+                problem: Problem::Breached,
+                detail: format!("seen {} times in known breaches", count),
+            });
+        }
+    }
+    Ok(findings)
+}
+
+/// Why a password is weak, or `None` if it looks fine
+fn weakness(password: &str) -> Option<String> {
+    if password.is_empty() {
+        return Some("empty password".into());
+    }
+    let lower = password.to_lowercase();
+    let unleet: String = lower
+        .chars()
+        .map(|c| match c {
+            '0' => 'o',
+            '1' | '!' => 'i',
+            '3' => 'e',
+            '4' | '@' => 'a',
+            '5' | '$' => 's',
+            '7' => 't',
+            c => c,
+        })
+        .collect();
+    if let Some(word) = COMMON_WORDS
+        .iter()
+        .find(|w| lower.contains(*w) || unleet.contains(*w))
+    {
+        return Some(format!("contains the common word `{}`", word));
+    }
+    if let Some(run) = keyboard_run(&lower) {
+        return Some(format!("contains the sequence `{}`", run));
+    }
+    let bits = entropy_bits(password);
+    if bits < MIN_ENTROPY_BITS {
+        return Some(format!("about {:.0} bits of entropy", bits));
+    }
+    None
+}
+
+/// The first run of 4+ characters taken from a keyboard row or the alphabet,
+/// forwards or backwards
+fn keyboard_run(lower: &str) -> Option<String> {
+    let chars: Vec<char> = lower.chars().collect();
+    for window in chars.windows(4) {
+        let run: String = window.iter().collect();
+        let reversed: String = window.iter().rev().collect();
+        if SEQUENCES
+            .iter()
+            .any(|s| s.contains(&run) || s.contains(&reversed))
+        {
+            return Some(run);
+        }
+    }
+    None
+}
+
+/// Length × log2(alphabet size), where the alphabet is the union of the
+/// character classes used. Repeated characters only count once per run, so
+/// `aaaaaaaa` isn't mistaken for something strong.
+fn entropy_bits(password: &str) -> f64 {
+    let (mut lower, mut upper, mut digit, mut symbol, mut other) =
+        (false, false, false, false, false);
+    for c in password.chars() {
+        match c {
+            'a'..='z' => lower = true,
+            'A'..='Z' => upper = true,
+            '0'..='9' => digit = true,
+            c if c.is_ascii() => symbol = true,
+            _ => other = true,
+        }
+    }
+    let pool = [
+        (lower, 26),
+        (upper, 26),
+        (digit, 10),
+        (symbol, 33),
+        (other, 100),
+    ]
+    .iter()
+    .filter(|(used, _)| *used)
+    .map(|(_, size)| size)
+    .sum::<u32>();
+
+    let mut effective = 0;
+    let mut prev = None;
+    for c in password.chars() {
+        if prev != Some(c) {
+            effective += 1;
+        }
+        prev = Some(c);
+    }
+    effective as f64 * (pool.max(1) as f64).log2()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(text: &str) -> Entry {
+        Entry::parse(text)
+    }
+
+    fn problems(findings: &[Finding], name: &str) -> Vec<Problem> {
+        findings
+            .iter()
+            .filter(|f| f.entry == name)
+            .map(|f| f.problem)
+            .collect()
+    }
+
+    #[test]
+    fn flags_weak_short_and_missing_fields() {
+        let entries = vec![
+            (
+                "strong".to_string(),
+                entry("vT9#qL2!mZ8&xR4w\nusername: a\nurl: https://a.test"),
+            ),
+            (
+                "short".to_string(),
+                entry("x7#Q\nusername: a\nurl: https://a.test"),
+            ),
+            ("bare".to_string(), entry("Gk4$pW9@zN2!hC7%")),
+        ];
+        let findings = audit(&entries);
+
+        assert!(problems(&findings, "strong").is_empty());
+        assert_eq!(
+            problems(&findings, "short"),
+            vec![Problem::Short, Problem::Weak]
+        );
+        assert_eq!(
+            problems(&findings, "bare"),
+            vec![Problem::NoUsername, Problem::NoUrl]
+        );
+    }
+
+    #[test]
+    fn reuse_is_found_without_printing_values() {
+        let entries = vec![
+            ("a".to_string(), entry("Same-Secret-4812-xyzq")),
+            ("b".to_string(), entry("Same-Secret-4812-xyzq")),
+            ("c".to_string(), entry("Different-9931-abqz")),
+        ];
+        let findings: Vec<_> = audit(&entries)
+            .into_iter()
+            .filter(|f| f.problem == Problem::Reused)
+            .collect();
+
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].detail, "same password as b");
+        assert!(findings.iter().all(|f| !f.detail.contains("Secret")));
+    }
+
+    #[test]
+    fn patterns_make_passwords_weak() {
+        assert_eq!(
+            weakness("Tr0ub4dor-P4ssw0rd-99"),
+            Some("contains the common word `password`".into())
+        );
+        assert_eq!(
+            weakness("zz-Qwerty-8812-Lm"),
+            Some("contains the sequence `qwer`".into())
+        );
+        assert_eq!(
+            weakness("9876mlkpZX!"),
+            Some("contains the sequence `9876`".into())
+        );
+        assert!(weakness("aaaaaaaaaaaaaaaaaaaa").is_some());
+        assert_eq!(weakness("k8#Vd2!qLs9@Wm4$"), None);
+    }
+
+    #[test]
+    fn breached_passwords_are_looked_up_once() -> Result<()> {
+        struct Known(Vec<String>);
+        impl Breaches for Known {
+            fn count(&mut self, hash: &str) -> Result<Option<u64>> {
+                self.0.push(hash.to_string());
+                Ok((hash == hibp::sha1_hex("hunter2")).then_some(17))
+            }
+        }
+
+        let entries = vec![
+            ("a".to_string(), entry("hunter2")),
+            ("b".to_string(), entry("hunter2")),
+            ("c".to_string(), entry("k8#Vd2!qLs9@Wm4$")),
+            ("d".to_string(), entry("")),
+        ];
+        let mut source = Known(Vec::new());
+        let findings = breached(&entries, &mut source)?;
+
+        assert_eq!(source.0.len(), 2);
+        assert_eq!(problems(&findings, "a"), vec![Problem::Breached]);
+        assert_eq!(problems(&findings, "b"), vec![Problem::Breached]);
+        assert!(problems(&findings, "c").is_empty());
+        assert_eq!(findings[0].detail, "seen 17 times in known breaches");
+        Ok(())
+    }
+}
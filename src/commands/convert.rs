@@ -0,0 +1,142 @@
+use crate::config::{self, Config};
+use crate::crypto;
+use crate::utils::{entries, git_ops};
+use anyhow::{Context, Result};
+use std::fs;
+
+/// Rename every entry (archived ones included) to the `to` extension.
+///
+/// Usage:
+///     passman convert --to age|rage
+///
+/// `age` and `rage` share one file format, so this is only a rename, but each
+/// file is decrypted first so a broken entry is never silently relabelled.
+pub fn run(config: &Config, to: &str) -> Result<()> {
+    let mut converted = 0;
+    let mut failures = Vec::new();
+
+    for entry in entries::collect(&config.prefix, true)? {
+        if entry.path.extension().and_then(|e| e.to_str()) == Some(to) {
+            continue;
+        }
+
+        let target = entry.path.with_extension(to);
+        if target.exists() {
+            failures.push(format!(
+                "{}: {} already exists",
+                entry.name,
+                target.display()
+            ));
+            continue;
+        }
+        if let Err(e) = crypto::decrypt(&config.secret, &entry.path) {
+            failures.push(format!("{}: {}", entry.name, e));
+            continue;
+        }
+
+        fs::rename(&entry.path, &target).with_context(|| {
+            format!(
+                "Failed to rename {} → {}",
+                entry.path.display(),
+                target.display()
+            )
+        })?;
+        converted += 1;
+    }
+
+    println!("✅ Converted {} entries to .{}", converted, to);
+    for failure in &failures {
+        eprintln!("⚠️  Skipped {}", failure);
+    }
+
+    if converted > 0 {
+        git_ops::sync_vault(&config.prefix, config.sync)?;
+    }
+    if config.crypto_extension != to {
+        println!(
+            "ℹ️  New entries still use .{}; set `backend = \"{}\"` in {} to switch.",
+            config.crypto_extension,
+            to,
+            config::config_file_path().display()
+        );
+    }
+
+    if !failures.is_empty() {
+        anyhow::bail!("{} entries could not be converted", failures.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use crate::crypto::{CryptoBackend, SecretString, set_decrypt_factory};
+    use anyhow::Result;
+    use std::fs::File;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn make_config(tmp: &TempDir) -> Config {
+        Config {
+            prefix: tmp.path().to_path_buf(),
+            crypto_extension: "age".into(),
+            ..test_config(tmp.path())
+        }
+    }
+
+    /// Decrypts everything except files whose name contains "broken"
+    struct PickyBackend;
+    impl CryptoBackend for PickyBackend {
+        fn decrypt(&self, _id: &Path, enc: &Path) -> Result<SecretString> {
+            if enc.to_string_lossy().contains("broken") {
+                anyhow::bail!("no identity matched");
+            }
+            Ok("secret".into())
+        }
+        fn encrypt(&self, _r: &[String], _o: &Path, _p: &SecretString) -> Result<()> {
+            unreachable!("convert never re-encrypts")
+        }
+    }
+    fn picky_factory(_: &Path) -> Box<dyn CryptoBackend> {
+        Box::new(PickyBackend)
+    }
+
+    #[test]
+    fn renames_entries_that_decrypt() -> Result<()> {
+        set_decrypt_factory(picky_factory);
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        fs::create_dir_all(tmp.path().join("bank"))?;
+        File::create(tmp.path().join("bank/chase.rage"))?;
+        File::create(tmp.path().join(".old.rage"))?;
+        File::create(tmp.path().join("mail.age"))?;
+
+        run(&cfg, "age")?;
+
+        assert!(tmp.path().join("bank/chase.age").exists());
+        assert!(!tmp.path().join("bank/chase.rage").exists());
+        assert!(tmp.path().join(".old.age").exists());
+        assert!(tmp.path().join("mail.age").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn skips_undecryptable_and_colliding_entries() -> Result<()> {
+        set_decrypt_factory(picky_factory);
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        File::create(tmp.path().join("broken.rage"))?;
+        File::create(tmp.path().join("dup.rage"))?;
+        File::create(tmp.path().join("dup.age"))?;
+        File::create(tmp.path().join("fine.rage"))?;
+
+        let err = run(&cfg, "age").unwrap_err();
+        assert_eq!(err.to_string(), "2 entries could not be converted");
+
+        assert!(tmp.path().join("broken.rage").exists());
+        assert!(tmp.path().join("dup.rage").exists());
+        assert!(tmp.path().join("fine.age").exists());
+        Ok(())
+    }
+}
@@ -1,12 +1,11 @@
 use crate::config::Config;
-use crate::crypto;
-use crate::utils::git_ops;
+use crate::crypto::{self, SecretString};
+use crate::utils::{git_ops, index, secure_fs};
 use anyhow::{Context, Result};
 use std::fs;
 use std::process::Command;
-use tempfile::NamedTempFile;
 
-type EditFn = fn() -> Result<String>;
+type EditFn = fn(&Config) -> Result<SecretString>;
 
 thread_local! {
     // By default, call the real editor
@@ -21,7 +20,7 @@ pub fn set_edit_hook(f: EditFn) {
 }
 
 pub fn run(config: &Config, path: String) -> Result<()> {
-    let output_path = config.entry_path(&path);
+    let output_path = config.entry_path(&path)?;
 
     if output_path.exists() {
         anyhow::bail!("File already exists at {}.", output_path.display());
@@ -32,20 +31,20 @@ pub fn run(config: &Config, path: String) -> Result<()> {
             .with_context(|| format!("Failed to create directory {}", parent.display()))?;
     }
 
-    let public = config.read_public()?;
-    let plaintext = EDIT_HOOK.with(|c| (c.borrow())())?;
-    crypto::encrypt(&public, &output_path, &plaintext)?;
+    let recipients = config.recipients_for(&output_path)?;
+    let plaintext = EDIT_HOOK.with(|c| (c.borrow())(config))?;
+    crypto::encrypt(&recipients, &output_path, &plaintext)?;
+    index::record(config, &path, plaintext.expose());
     println!("Password for '{}' stored successfully.", path);
 
-    git_ops::sync_vault(&config.prefix)
+    git_ops::sync_vault(&config.prefix, config.sync)
 }
 
-fn real_edit() -> Result<String> {
-    let file = NamedTempFile::new().context("Failed to create temporary file")?;
+fn real_edit(config: &Config) -> Result<SecretString> {
+    let file = secure_fs::scratch_file()?;
     let path = file.path();
 
-    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
-    let status = Command::new(editor)
+    let status = Command::new(config.editor())
         .arg(path)
         .status()
         .context("Failed to launch $EDITOR")?;
@@ -54,10 +53,9 @@ fn real_edit() -> Result<String> {
         anyhow::bail!("Editor exited with error");
     }
 
-    let contents = fs::read_to_string(path)
-        .context("Failed to read from temporary file")?
-        .trim_end()
-        .to_string();
+    let raw =
+        SecretString::new(fs::read_to_string(path).context("Failed to read from temporary file")?);
+    let contents = SecretString::from(raw.expose().trim_end());
 
     if contents.is_empty() {
         anyhow::bail!("Aborted: file was empty");
@@ -69,7 +67,7 @@ fn real_edit() -> Result<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::Config;
+    use crate::config::{Config, SyncPolicy};
     use crate::crypto::{CryptoBackend, set_encrypt_factory};
     use anyhow::Result;
     use serial_test::serial;
@@ -86,6 +84,11 @@ mod tests {
             secret: tmp.path().to_path_buf(),
             crypto_extension: "rage".into(),
             public_key_filename: "public.key".into(),
+            editor: None,
+            clipboard_timeout: 45,
+            sync: SyncPolicy::Manual,
+            vault: None,
+            remote: None,
         }
     }
 
@@ -94,7 +97,7 @@ mod tests {
         let tmp = TempDir::new()?;
         let cfg = make_test_config(&tmp);
 
-        let entry = cfg.entry_path("mypw");
+        let entry = cfg.entry_path("mypw")?;
         File::create(&entry)?;
 
         let err = run(&cfg, "mypw".into()).unwrap_err();
@@ -125,20 +128,24 @@ mod tests {
 
     #[test]
     fn edit_success_and_encrypts() -> Result<()> {
-        set_edit_hook(|| Ok("my secret".into()));
+        set_edit_hook(|_| Ok("my secret".into()));
 
         struct Spy;
         impl CryptoBackend for Spy {
             fn encrypt(
                 &self,
-                _recipient: &str,
+                _recipients: &[String],
                 _output_file: &Path,
-                plaintext: &str,
+                plaintext: &SecretString,
             ) -> Result<()> {
-                assert_eq!(plaintext, "my secret");
+                assert_eq!(plaintext.expose(), "my secret");
                 Ok(())
             }
-            fn decrypt(&self, _identity_file: &Path, _encrypted_file: &Path) -> Result<String> {
+            fn decrypt(
+                &self,
+                _identity_file: &Path,
+                _encrypted_file: &Path,
+            ) -> Result<SecretString> {
                 panic!("decrypt should never be called");
             }
         }
@@ -176,7 +183,7 @@ mod tests {
             std::env::set_var("EDITOR", &script);
         }
 
-        let err = real_edit().unwrap_err();
+        let err = real_edit(&make_test_config(&tmp)).unwrap_err();
         assert_eq!(err.to_string(), "Aborted: file was empty");
         Ok(())
     }
@@ -184,12 +191,13 @@ mod tests {
     #[test]
     #[serial]
     fn real_edit_editor_failure() -> Result<()> {
+        let tmp = TempDir::new()?;
         // Use the builtin `false` to simulate an editor that runs but exits non-zero
         unsafe {
             std::env::set_var("EDITOR", "false");
         }
 
-        let err = real_edit().unwrap_err();
+        let err = real_edit(&make_test_config(&tmp)).unwrap_err();
         assert_eq!(err.to_string(), "Editor exited with error");
         Ok(())
     }
@@ -204,8 +212,8 @@ mod tests {
             std::env::set_var("EDITOR", &script);
         }
 
-        let out = real_edit()?;
-        assert_eq!(out, "magic");
+        let out = real_edit(&make_test_config(&tmp))?;
+        assert_eq!(out.expose(), "magic");
         Ok(())
     }
 }
@@ -0,0 +1,635 @@
+use crate::cli::DeviceAction;
+use crate::config::{
+    Config, DEVICE_COMMENT, NamedRecipient as Device, RECIPIENTS_FILENAME, parse_named_recipients,
+    parse_recipients,
+};
+use crate::crypto::{self, ssh};
+use crate::utils::{entries, git_ops, manifest, signing};
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use walkdir::WalkDir;
+
+/// Start of every enrollment code, so a stray paste is recognised
+const CODE_PREFIX: &str = "passman-device-v1:";
+
+/// What `device add` prints on the new machine and `device approve` reads.
+/// Only public keys, so it can travel over chat or email.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Enrollment {
+    device: String,
+    recipient: String,
+    /// The device's signing key, so its syncs verify on the approving device
+    #[serde(default)]
+    signing_key: Option<String>,
+}
+
+impl Enrollment {
+    fn encode(&self) -> Result<String> {
+        let json = serde_json::to_vec(self)?;
+        Ok(format!("{}{}", CODE_PREFIX, URL_SAFE_NO_PAD.encode(json)))
+    }
+
+    fn decode(code: &str) -> Result<Self> {
+        let payload = code.trim().strip_prefix(CODE_PREFIX).with_context(|| {
+            format!("Not an enrollment code (they start with `{}`)", CODE_PREFIX)
+        })?;
+        let json = URL_SAFE_NO_PAD
+            .decode(payload)
+            .context("Enrollment code is damaged; copy it again")?;
+        serde_json::from_slice(&json).context("Enrollment code is damaged; copy it again")
+    }
+}
+
+/// Add, list and remove the devices that can read the vault.
+///
+/// Usage:
+///     passman device add [--name NAME]
+///     passman device approve <code>
+///     passman device list
+///     passman device revoke <device>
+///
+/// `add` runs on the new machine (after `passman init`) and prints a code
+/// holding its recipient, signing key and name. `approve <code>` on a
+/// device that can already read the vault adds the recipient to the
+/// vault's root `.recipients` file, re-encrypts the entries it governs,
+/// trusts the new device's signatures and syncs. The first enrollment
+/// creates that file from the public key file. Folders with their own
+/// `.recipients` keep theirs.
+///
+/// `revoke` removes a device from every recipients file in the vault and
+/// re-encrypts without it, refusing when it is a folder's only recipient.
+/// It can still read anything it already pulled, so rotate those
+/// passwords too.
+pub fn run(config: &Config, action: DeviceAction) -> Result<()> {
+    match action {
+        DeviceAction::Add { name } => add(config, name),
+        DeviceAction::Approve { code } => approve(config, &code),
+        DeviceAction::List => list(config),
+        DeviceAction::Revoke { device } => revoke(config, &device),
+    }
+}
+
+fn add(config: &Config, name: Option<String>) -> Result<()> {
+    if !config.secret.exists() {
+        anyhow::bail!(
+            "No identity at {}; run `passman init` first",
+            config.secret.display()
+        );
+    }
+    let device = name.unwrap_or_else(signing::device_name);
+    check_name(&device)?;
+    let code = Enrollment {
+        device: device.clone(),
+        recipient: own_recipient(config)?,
+        signing_key: Some(signing::public_key(&signing::device_key(config)?)),
+    }
+    .encode()?;
+
+    println!("📋 On a device that can already read the vault, run:\n");
+    println!("    passman device approve {}\n", code);
+    println!(
+        "The code only holds public keys. Once `{}` is approved,",
+        device
+    );
+    println!(
+        "pull the vault with `git -C {} pull`.",
+        config.prefix.display()
+    );
+    Ok(())
+}
+
+fn approve(config: &Config, code: &str) -> Result<()> {
+    let enrollment = Enrollment::decode(code)?;
+    let name = enrollment.device.clone();
+    check_name(&name)?;
+    let recipient = valid_recipient(&enrollment.recipient)?;
+
+    let mut devices = load(config)?;
+    if let Some(known) = devices.iter().find(|d| d.recipient == recipient) {
+        anyhow::bail!("`{}` can already read this vault", known.label());
+    }
+    if devices
+        .iter()
+        .any(|d| d.name.as_deref() == Some(name.as_str()))
+    {
+        anyhow::bail!(
+            "A device named `{}` is already enrolled; run `passman device add --name <other>` on the new device",
+            name
+        );
+    }
+    if let Some(key) = &enrollment.signing_key {
+        signing::trust(
+            config,
+            &signing::TrustedSigner {
+                key: key.clone(),
+                device: name.clone(),
+            },
+        )
+        .context("Enrollment code has an invalid signing key")?;
+    }
+
+    devices.push(Device {
+        name: Some(name.clone()),
+        recipient,
+    });
+    let count = save_and_reencrypt(config, &devices, &[])?;
+    println!("✅ Enrolled `{}`; re-encrypted {} entries", name, count);
+    git_ops::sync_vault_with_message(
+        &config.prefix,
+        config.sync,
+        &format!("Enroll device {}", name),
+    )?;
+    if enrollment.signing_key.is_some() {
+        println!(
+            "🔏 On `{}`, pull the vault and run `passman verify --trust {}`.",
+            name,
+            signing::public_key(&signing::device_key(config)?)
+        );
+    }
+    Ok(())
+}
+
+fn list(config: &Config) -> Result<()> {
+    let devices = load(config)?;
+    let own = own_recipient(config).ok();
+    let width = devices
+        .iter()
+        .map(|d| d.label().chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("DEVICE".len());
+    println!("{:<width$}  RECIPIENT", "DEVICE");
+    for device in &devices {
+        let marker = if own.as_ref() == Some(&device.recipient) {
+            "  (this device)"
+        } else {
+            ""
+        };
+        println!("{:<width$}  {}{}", device.label(), device.recipient, marker);
+    }
+    Ok(())
+}
+
+fn revoke(config: &Config, wanted: &str) -> Result<()> {
+    let mut devices = load(config)?;
+    let wanted_recipient = ssh::recipient(wanted).unwrap_or_else(|| wanted.trim().to_string());
+    let Some(pos) = devices
+        .iter()
+        .position(|d| d.name.as_deref() == Some(wanted) || d.recipient == wanted_recipient)
+    else {
+        anyhow::bail!(
+            "No device `{}` can read this vault (see `passman device list`)",
+            wanted
+        );
+    };
+    if own_recipient(config).ok().as_ref() == Some(&devices[pos].recipient) {
+        anyhow::bail!(
+            "`{}` is this device; revoke it from another one",
+            devices[pos].label()
+        );
+    }
+    if devices.len() == 1 {
+        anyhow::bail!("`{}` is the only device left", devices[pos].label());
+    }
+
+    let device = devices.remove(pos);
+    // folders shared with the device lose it too, unless it is all they have
+    let folders: Vec<(PathBuf, String)> = folder_recipients(config)?
+        .into_iter()
+        .filter_map(|(path, raw)| Some((path, without(&raw, &device.recipient)?)))
+        .collect();
+    let orphaned: Vec<String> = folders
+        .iter()
+        .filter(|(_, raw)| parse_recipients(raw).is_empty())
+        .map(|(path, _)| folder_name(config, path))
+        .collect();
+    if !orphaned.is_empty() {
+        anyhow::bail!(
+            "`{}` is the only recipient of {}; add another one there first",
+            device.label(),
+            orphaned.join(", ")
+        );
+    }
+
+    let count = save_and_reencrypt(config, &devices, &folders)?;
+    println!(
+        "🗑️  Revoked `{}`; re-encrypted {} entries",
+        device.label(),
+        count
+    );
+    for (path, _) in &folders {
+        println!("   also removed from {}", folder_name(config, path));
+    }
+    if let Some(name) = &device.name
+        && signing::untrust(config, name)? > 0
+    {
+        println!("🔏 No longer trusting signatures from `{}`", name);
+    }
+    git_ops::sync_vault_with_message(
+        &config.prefix,
+        config.sync,
+        &format!("Revoke device {}", device.label()),
+    )?;
+    println!(
+        "⚠️  `{}` can still read what it already pulled, older commits included; rotate those passwords.",
+        device.label()
+    );
+    Ok(())
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() || name.trim() != name || name.chars().any(char::is_control) {
+        anyhow::bail!("Invalid device name `{}`", name.escape_debug());
+    }
+    Ok(())
+}
+
+/// `raw` as a recipient age can encrypt to, or an error saying why not
+fn valid_recipient(raw: &str) -> Result<String> {
+    if ssh::is_public_key(raw) {
+        return ssh::recipient(raw)
+            .with_context(|| format!("age can't encrypt to this kind of SSH key: {}", raw));
+    }
+    age::x25519::Recipient::from_str(raw.trim())
+        .map_err(|e| anyhow::anyhow!("Invalid recipient `{}`: {}", raw, e))?;
+    Ok(raw.trim().to_string())
+}
+
+/// This device's recipient: the SSH identity's public key, else the first
+/// one in the public key file `init` wrote
+fn own_recipient(config: &Config) -> Result<String> {
+    if ssh::is_private_key(&config.secret) {
+        return ssh::public_key(&config.secret);
+    }
+    Ok(config.read_recipients()?.remove(0))
+}
+
+fn root_recipients(config: &Config) -> PathBuf {
+    config.prefix.join(RECIPIENTS_FILENAME)
+}
+
+/// The vault's devices: its root `.recipients` file, or before the first
+/// enrollment the public key file, with this device named
+fn load(config: &Config) -> Result<Vec<Device>> {
+    let path = root_recipients(config);
+    if path.is_file() {
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        return Ok(parse_named_recipients(&raw));
+    }
+    let path = config.base_dir.join(&config.public_key_filename);
+    let raw = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read public key: {}", path.display()))?;
+    let own = own_recipient(config).ok();
+    let mut devices = parse_named_recipients(&raw);
+    for device in &mut devices {
+        if device.name.is_none() && own.as_ref() == Some(&device.recipient) {
+            device.name = Some(signing::device_name());
+        }
+    }
+    Ok(devices)
+}
+
+fn render(devices: &[Device]) -> String {
+    let mut out = String::from("# Devices that can read this vault; see `passman device`\n");
+    for device in devices {
+        if let Some(name) = &device.name {
+            out.push_str(&format!("{} {}\n", DEVICE_COMMENT, name));
+        }
+        out.push_str(&format!("{}\n", device.recipient));
+    }
+    out
+}
+
+/// Every `.recipients` file below the vault root, with its contents
+fn folder_recipients(config: &Config) -> Result<Vec<(PathBuf, String)>> {
+    let root = root_recipients(config);
+    WalkDir::new(&config.prefix)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+        .filter_map(Result::ok)
+        .filter(|e| e.file_name() == RECIPIENTS_FILENAME && e.path() != root)
+        .map(|e| {
+            let raw = fs::read_to_string(e.path())
+                .with_context(|| format!("Failed to read {}", e.path().display()))?;
+            Ok((e.into_path(), raw))
+        })
+        .collect()
+}
+
+/// `raw` without the lines for `recipient` (and their `# device:` names),
+/// or `None` if it doesn't list it
+fn without(raw: &str, recipient: &str) -> Option<String> {
+    let mut kept: Vec<&str> = Vec::new();
+    let mut found = false;
+    for line in raw.lines() {
+        if parse_recipients(line).first().map(String::as_str) == Some(recipient) {
+            found = true;
+            if kept
+                .last()
+                .is_some_and(|l| l.trim().starts_with(DEVICE_COMMENT))
+            {
+                kept.pop();
+            }
+            continue;
+        }
+        kept.push(line);
+    }
+    found.then(|| kept.iter().map(|l| format!("{}\n", l)).collect())
+}
+
+/// `team/.recipients`, relative to the vault
+fn folder_name(config: &Config, path: &Path) -> String {
+    path.strip_prefix(&config.prefix)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// Every file encrypted to the vault's recipients: entries (archived ones
+/// too) and a hidden-names manifest. The search index is this device's own.
+fn vault_files(prefix: &Path) -> Result<Vec<PathBuf>> {
+    Ok(entries::collect(prefix, true)?
+        .into_iter()
+        .map(|e| e.path)
+        .chain(manifest::manifest_file(prefix))
+        .collect())
+}
+
+/// Write `devices` as the root `.recipients`, and `folders` over the folder
+/// recipients files they name, then re-encrypt every file whose recipients
+/// that changes. Every such file is decrypted before any is re-encrypted;
+/// if one can't be, the recipients files are put back as they were.
+/// Returns how many were re-encrypted.
+fn save_and_reencrypt(
+    config: &Config,
+    devices: &[Device],
+    folders: &[(PathBuf, String)],
+) -> Result<usize> {
+    let files = vault_files(&config.prefix)?;
+    let before = files
+        .iter()
+        .map(|f| config.recipients_for(f))
+        .collect::<Result<Vec<_>>>()?;
+
+    let root = (root_recipients(config), render(devices));
+    let writes: Vec<(&PathBuf, &String)> = std::iter::once((&root.0, &root.1))
+        .chain(folders.iter().map(|(path, raw)| (path, raw)))
+        .collect();
+    let originals: Vec<(&PathBuf, Option<Vec<u8>>)> = writes
+        .iter()
+        .map(|(path, _)| (*path, fs::read(path).ok()))
+        .collect();
+    let restore = || {
+        for (path, original) in &originals {
+            let _ = match original {
+                Some(raw) => fs::write(path, raw),
+                None => fs::remove_file(path),
+            };
+        }
+    };
+
+    let mut pending = Vec::new();
+    let decrypted = (|| -> Result<()> {
+        for (path, raw) in &writes {
+            fs::write(path, raw).with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        for (file, old) in files.iter().zip(before) {
+            let recipients = config.recipients_for(file)?;
+            if recipients != old {
+                let plaintext = crypto::decrypt(&config.secret, file)?;
+                pending.push((file, recipients, plaintext));
+            }
+        }
+        Ok(())
+    })();
+    if let Err(e) = decrypted {
+        restore();
+        return Err(e.context("Recipients left unchanged"));
+    }
+
+    for (file, recipients, plaintext) in &pending {
+        crypto::encrypt(recipients, file, plaintext).with_context(|| {
+            format!(
+                "Failed to re-encrypt {}; run `passman fsck --fix` to finish",
+                file.display()
+            )
+        })?;
+    }
+    Ok(pending.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use crate::crypto::testing;
+    use ed25519_dalek::SigningKey;
+    use tempfile::TempDir;
+
+    fn key() -> String {
+        age::x25519::Identity::generate().to_public().to_string()
+    }
+
+    fn make_config(tmp: &TempDir, own: &str) -> Config {
+        testing::use_recording();
+        let cfg = test_config(tmp.path());
+        fs::write(tmp.path().join("public.key"), format!("{}\n", own)).unwrap();
+        fs::write(&cfg.secret, "AGE-SECRET-KEY-1\n").unwrap();
+        cfg
+    }
+
+    fn write_entry(cfg: &Config, name: &str) -> Result<PathBuf> {
+        let path = cfg.entry_path(name)?;
+        fs::create_dir_all(path.parent().unwrap())?;
+        crypto::encrypt(&cfg.recipients_for(&path)?, &path, &"pw".into())?;
+        Ok(path)
+    }
+
+    fn encrypted_to(path: &Path) -> String {
+        let raw = fs::read_to_string(path).unwrap();
+        raw.split_once('|').unwrap().0.to_string()
+    }
+
+    fn code(device: &str, recipient: &str) -> Result<String> {
+        Enrollment {
+            device: device.into(),
+            recipient: recipient.into(),
+            signing_key: Some(signing::public_key(&SigningKey::from_bytes(&[7; 32]))),
+        }
+        .encode()
+    }
+
+    #[test]
+    fn approving_a_code_enrolls_the_device() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let (me, laptop, team) = (key(), key(), key());
+        let cfg = make_config(&tmp, &me);
+        let web = write_entry(&cfg, "web")?;
+        fs::create_dir_all(cfg.prefix.join("team"))?;
+        fs::write(cfg.prefix.join("team").join(RECIPIENTS_FILENAME), &team)?;
+        let db = write_entry(&cfg, "team/db")?;
+
+        run(
+            &cfg,
+            DeviceAction::Approve {
+                code: code("laptop", &laptop)?,
+            },
+        )?;
+        assert_eq!(encrypted_to(&web), format!("{},{}", me, laptop));
+        assert_eq!(encrypted_to(&db), team, "folders keep their recipients");
+        assert_eq!(
+            load(&cfg)?,
+            vec![
+                Device {
+                    name: Some(signing::device_name()),
+                    recipient: me.clone(),
+                },
+                Device {
+                    name: Some("laptop".into()),
+                    recipient: laptop.clone(),
+                },
+            ]
+        );
+        assert!(signing::trusted(&cfg)?.iter().any(|t| t.device == "laptop"));
+
+        let again = run(
+            &cfg,
+            DeviceAction::Approve {
+                code: code("laptop", &laptop)?,
+            },
+        );
+        assert!(again.unwrap_err().to_string().contains("already"));
+        Ok(())
+    }
+
+    #[test]
+    fn revoked_devices_are_no_longer_recipients() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let (me, laptop, desk, team) = (key(), key(), key(), key());
+        let cfg = make_config(&tmp, &me);
+        let web = write_entry(&cfg, "web")?;
+        for (name, recipient) in [("laptop", &laptop), ("desk", &desk)] {
+            run(
+                &cfg,
+                DeviceAction::Approve {
+                    code: code(name, recipient)?,
+                },
+            )?;
+        }
+        fs::create_dir_all(cfg.prefix.join("team"))?;
+        fs::write(
+            cfg.prefix.join("team").join(RECIPIENTS_FILENAME),
+            format!("# ops\n{}\n# device: laptop\n{}\n", team, laptop),
+        )?;
+        let db = write_entry(&cfg, "team/db")?;
+        fs::create_dir_all(cfg.prefix.join("solo"))?;
+        fs::write(cfg.prefix.join("solo").join(RECIPIENTS_FILENAME), &desk)?;
+
+        run(
+            &cfg,
+            DeviceAction::Revoke {
+                device: "laptop".into(),
+            },
+        )?;
+        assert_eq!(encrypted_to(&web), format!("{},{}", me, desk));
+        assert!(!signing::trusted(&cfg)?.iter().any(|t| t.device == "laptop"));
+        assert_eq!(encrypted_to(&db), team, "shared folders lose it too");
+        assert_eq!(
+            fs::read_to_string(cfg.prefix.join("team").join(RECIPIENTS_FILENAME))?,
+            format!("# ops\n{}\n", team)
+        );
+        let solo_db = write_entry(&cfg, "solo/db")?;
+        assert_eq!(encrypted_to(&solo_db), desk);
+        let err = run(
+            &cfg,
+            DeviceAction::Revoke {
+                device: "desk".into(),
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("solo/.recipients"), "{}", err);
+        assert_eq!(encrypted_to(&solo_db), desk, "nothing changed");
+
+        let own = run(&cfg, DeviceAction::Revoke { device: me.clone() });
+        assert!(own.unwrap_err().to_string().contains("this device"));
+        Ok(())
+    }
+
+    struct Undecryptable;
+    impl crypto::CryptoBackend for Undecryptable {
+        fn encrypt(&self, _r: &[String], _o: &Path, _p: &crypto::SecretString) -> Result<()> {
+            anyhow::bail!("should not encrypt")
+        }
+        fn decrypt(&self, _i: &Path, e: &Path) -> Result<crypto::SecretString> {
+            anyhow::bail!("no identity matched {}", e.display())
+        }
+    }
+
+    #[test]
+    fn failed_decrypts_leave_recipients_alone() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let (me, laptop, desk, team) = (key(), key(), key(), key());
+        let cfg = make_config(&tmp, &me);
+        let web = write_entry(&cfg, "web")?;
+        run(
+            &cfg,
+            DeviceAction::Approve {
+                code: code("laptop", &laptop)?,
+            },
+        )?;
+        fs::create_dir_all(cfg.prefix.join("team"))?;
+        let team_file = cfg.prefix.join("team").join(RECIPIENTS_FILENAME);
+        fs::write(
+            &team_file,
+            format!("{}\n# device: laptop\n{}\n", team, laptop),
+        )?;
+        write_entry(&cfg, "team/db")?;
+        let root = fs::read_to_string(root_recipients(&cfg))?;
+
+        crypto::set_decrypt_factory(|_: &Path| Box::new(Undecryptable));
+        let err = run(
+            &cfg,
+            DeviceAction::Approve {
+                code: code("desk", &desk)?,
+            },
+        )
+        .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("no identity matched"),
+            "{:#}",
+            err
+        );
+        assert_eq!(fs::read_to_string(root_recipients(&cfg))?, root);
+
+        let err = run(
+            &cfg,
+            DeviceAction::Revoke {
+                device: "laptop".into(),
+            },
+        );
+        assert!(err.is_err());
+        assert_eq!(fs::read_to_string(root_recipients(&cfg))?, root);
+        assert_eq!(
+            fs::read_to_string(&team_file)?,
+            format!("{}\n# device: laptop\n{}\n", team, laptop)
+        );
+        assert_eq!(encrypted_to(&web), format!("{},{}", me, laptop));
+        Ok(())
+    }
+
+    #[test]
+    fn codes_round_trip_and_reject_garbage() {
+        let code = code("desk", &key()).unwrap();
+        let decoded = Enrollment::decode(&format!("  {}\n", code)).unwrap();
+        assert_eq!(decoded.device, "desk");
+        assert!(Enrollment::decode("age1abc").is_err());
+        assert!(Enrollment::decode(&code[..code.len() - 3]).is_err());
+    }
+}
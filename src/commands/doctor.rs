@@ -0,0 +1,386 @@
+use crate::config::{Config, SyncPolicy};
+use crate::crypto::{identity, ssh};
+use anyhow::Result;
+use git2::Repository;
+use serde::Serialize;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use which::which;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Status {
+    fn icon(self) -> &'static str {
+        match self {
+            Status::Pass => "✅",
+            Status::Warn => "⚠️ ",
+            Status::Fail => "❌",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Pass,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Check that this machine is set up to use the vault.
+///
+/// Usage:
+///     passman doctor [--json]
+///
+/// Unlike `show --config`, which only prints what was resolved, every item
+/// here is checked and gets pass, warn or fail plus a suggested fix: the
+/// identity file and its permissions, whether the public key belongs to the
+/// identity, the backend binary, the vault's git repository and remote,
+/// shell completions and `$EDITOR`. Exits non-zero if anything fails.
+pub fn run(config: &Config, json: bool) -> Result<()> {
+    let checks = checks(config);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+    } else {
+        for c in &checks {
+            println!("{} {:<12} {}", c.status.icon(), c.name, c.detail);
+            if let Some(fix) = &c.fix {
+                println!("   {:<12} ↳ {}", "", fix);
+            }
+        }
+    }
+
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    if failed > 0 {
+        anyhow::bail!("{} checks failed", failed);
+    }
+    Ok(())
+}
+
+/// Every check, in the order they are printed
+pub fn checks(config: &Config) -> Vec<Check> {
+    vec![
+        identity(&config.secret),
+        public_key(config),
+        backend(&config.crypto_extension),
+        git_repo(config),
+        completions(),
+        editor(&config.editor()),
+    ]
+}
+
+fn identity(secret: &Path) -> Check {
+    const NAME: &str = "identity";
+    let meta = match fs::metadata(secret) {
+        Ok(meta) => meta,
+        Err(_) => {
+            return Check::fail(
+                NAME,
+                format!("{} does not exist", secret.display()),
+                "Run `passman init` to create one, or set `secret` in the config file",
+            );
+        }
+    };
+    let mode = meta.permissions().mode() & 0o777;
+    let detail = format!("{} has mode {:04o}", secret.display(), mode);
+    let fix = format!("chmod 600 {}", secret.display());
+    // same rule as every other command: others reading it is fatal
+    if mode & 0o007 != 0 {
+        return Check::fail(NAME, detail, fix);
+    }
+    if mode & 0o070 != 0 {
+        return Check::warn(NAME, detail, fix);
+    }
+    let protected = if identity::is_protected(secret) {
+        ", passphrase-protected"
+    } else {
+        ""
+    };
+    Check::pass(
+        NAME,
+        format!("{} (mode {:04o}{})", secret.display(), mode, protected),
+    )
+}
+
+fn public_key(config: &Config) -> Check {
+    const NAME: &str = "public key";
+    let public = config.base_dir.join(&config.public_key_filename);
+    let recipients = match config.read_recipients() {
+        Ok(r) => r,
+        Err(e) => {
+            return Check::fail(
+                NAME,
+                format!("{:#}", e),
+                format!(
+                    "{}-keygen -y {} > {}",
+                    config.crypto_extension.trim_start_matches('.'),
+                    config.secret.display(),
+                    public.display()
+                ),
+            );
+        }
+    };
+    if identity::is_protected(&config.secret) {
+        // comparing would mean asking for the passphrase
+        return Check::pass(
+            NAME,
+            format!(
+                "{} (not compared: the identity is passphrase-protected)",
+                public.display()
+            ),
+        );
+    }
+    let own = if ssh::is_private_key(&config.secret) {
+        ssh::public_key(&config.secret).ok()
+    } else {
+        let Ok(raw) = fs::read_to_string(&config.secret) else {
+            return Check::warn(
+                NAME,
+                "can't read the identity to compare",
+                "Fix the identity check first",
+            );
+        };
+        raw.lines()
+            .map(str::trim)
+            .find(|l| l.starts_with("AGE-SECRET-KEY-"))
+            .and_then(|l| age::x25519::Identity::from_str(l).ok())
+            .map(|id| id.to_public().to_string())
+    };
+    let Some(own) = own else {
+        return Check::warn(
+            NAME,
+            "identity is neither an age key nor an SSH key, so it can't be compared",
+            "Nothing to do if you can decrypt entries",
+        );
+    };
+    if recipients.contains(&own) {
+        Check::pass(NAME, format!("{} matches the identity", public.display()))
+    } else {
+        Check::fail(
+            NAME,
+            format!(
+                "{} does not contain the identity's key {}",
+                public.display(),
+                own
+            ),
+            format!("Add {} to {}", own, public.display()),
+        )
+    }
+}
+
+fn backend(extension: &str) -> Check {
+    const NAME: &str = "backend";
+    let bin = match extension.trim_start_matches('.') {
+        "age" => "age",
+        _ => "rage",
+    };
+    let hint = match bin {
+        "age" => "Install age: https://github.com/FiloSottile/age",
+        _ => "Install rage: https://github.com/str4d/rage",
+    };
+    let Ok(path) = which(bin) else {
+        return Check::fail(NAME, format!("`{}` is not on PATH", bin), hint);
+    };
+    match Command::new(&path).arg("--version").output() {
+        Ok(out) if out.status.success() => {
+            let version = String::from_utf8_lossy(&out.stdout).trim().to_string();
+            Check::pass(NAME, format!("{} ({})", version, path.display()))
+        }
+        _ => Check::fail(NAME, format!("`{} --version` failed", path.display()), hint),
+    }
+}
+
+fn git_repo(config: &Config) -> Check {
+    const NAME: &str = "git";
+    let Ok(repo) = Repository::open(&config.prefix) else {
+        let detail = format!("{} is not a git repository", config.prefix.display());
+        let fix = "Run `passman init` to set up the vault repository";
+        // with `sync = "manual"` git is optional
+        return match config.sync {
+            SyncPolicy::Manual => Check::warn(NAME, detail, fix),
+            _ => Check::fail(NAME, detail, fix),
+        };
+    };
+    let url = repo
+        .find_remote("origin")
+        .ok()
+        .and_then(|r| r.url().map(str::to_string));
+    match (url, &config.remote) {
+        (None, Some(wanted)) => Check::warn(
+            NAME,
+            "no `origin` remote",
+            format!(
+                "git -C {} remote add origin {}",
+                config.prefix.display(),
+                wanted
+            ),
+        ),
+        (None, None) => Check::warn(
+            NAME,
+            "no `origin` remote, so nothing is backed up",
+            format!("git -C {} remote add origin <url>", config.prefix.display()),
+        ),
+        (Some(url), Some(wanted)) if &url != wanted => Check::warn(
+            NAME,
+            format!("origin is {}, but the config says {}", url, wanted),
+            format!(
+                "git -C {} remote set-url origin {}",
+                config.prefix.display(),
+                wanted
+            ),
+        ),
+        (Some(url), _) => Check::pass(NAME, format!("origin {}", url)),
+    }
+}
+
+fn completions() -> Check {
+    const NAME: &str = "completions";
+    let shell = std::env::var("SHELL").unwrap_or_default();
+    let shell = shell.rsplit('/').next().unwrap_or_default();
+    if shell != "bash" {
+        return Check::warn(
+            NAME,
+            format!("only bash completions are generated (shell is `{}`)", shell),
+            "Use bash, or source the bash script from your shell",
+        );
+    }
+    let file = completion_file();
+    if file.is_file() {
+        Check::pass(NAME, file.display().to_string())
+    } else {
+        Check::warn(
+            NAME,
+            format!("{} is missing", file.display()),
+            "Run `passman init` to install completions",
+        )
+    }
+}
+
+/// Where `completions::install` writes the bash script
+fn completion_file() -> PathBuf {
+    let bin = std::env::current_exe()
+        .ok()
+        .and_then(|p| p.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "passman".into());
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("bash/completions")
+        .join(format!("{}.bash", bin))
+}
+
+fn editor(editor: &str) -> Check {
+    const NAME: &str = "editor";
+    let program = editor.split_whitespace().next().unwrap_or_default();
+    match which(program) {
+        Ok(path) => Check::pass(NAME, format!("{} ({})", editor, path.display())),
+        Err(_) => Check::fail(
+            NAME,
+            format!("`{}` is not on PATH", program),
+            "Set $EDITOR, or `editor` in the config file, to an installed editor",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn identity_must_be_private() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = test_config(tmp.path());
+        assert_eq!(identity(&cfg.secret).status, Status::Fail);
+
+        fs::write(&cfg.secret, "key")?;
+        fs::set_permissions(&cfg.secret, fs::Permissions::from_mode(0o644))?;
+        let check = identity(&cfg.secret);
+        assert_eq!(check.status, Status::Fail);
+        assert_eq!(
+            check.fix,
+            Some(format!("chmod 600 {}", cfg.secret.display()))
+        );
+
+        fs::set_permissions(&cfg.secret, fs::Permissions::from_mode(0o640))?;
+        assert_eq!(identity(&cfg.secret).status, Status::Warn);
+        fs::set_permissions(&cfg.secret, fs::Permissions::from_mode(0o600))?;
+        assert_eq!(identity(&cfg.secret).status, Status::Pass);
+        Ok(())
+    }
+
+    #[test]
+    fn public_key_must_match_identity() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = test_config(tmp.path());
+        let id = age::x25519::Identity::generate();
+        let other = age::x25519::Identity::generate();
+        use age::secrecy::ExposeSecret;
+        fs::write(&cfg.secret, id.to_string().expose_secret())?;
+
+        fs::write(tmp.path().join("public.key"), other.to_public().to_string())?;
+        assert_eq!(public_key(&cfg).status, Status::Fail);
+
+        fs::write(tmp.path().join("public.key"), id.to_public().to_string())?;
+        assert_eq!(public_key(&cfg).status, Status::Pass);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_git_repo_fails() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let mut cfg = test_config(tmp.path());
+        assert_eq!(git_repo(&cfg).status, Status::Warn, "manual sync");
+        cfg.sync = SyncPolicy::Auto;
+        assert_eq!(git_repo(&cfg).status, Status::Fail);
+
+        let repo = Repository::init(&cfg.prefix)?;
+        assert_eq!(git_repo(&cfg).status, Status::Warn);
+        repo.remote("origin", "git@example.com:me/vault.git")?;
+        assert_eq!(git_repo(&cfg).status, Status::Pass);
+
+        cfg.remote = Some("git@example.com:me/other.git".into());
+        assert_eq!(git_repo(&cfg).status, Status::Warn);
+        Ok(())
+    }
+}
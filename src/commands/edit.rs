@@ -1,16 +1,16 @@
 use crate::config::Config;
-use crate::crypto;
-use crate::utils::git_ops;
+use crate::crypto::{self, SecretString};
+use crate::entry::Entry;
+use crate::utils::{expiry, git_ops, index, secure_fs};
 use anyhow::{Context, Result};
 use std::cell::RefCell;
 use std::path::Path;
-use std::{env, fs, process::Command};
-use tempfile::NamedTempFile;
+use std::{fs, process::Command};
 
 /// Edit an existing password entry in your $EDITOR and re‐encrypt it.
 pub fn run(config: &Config, path: String) -> Result<()> {
     // Locate the encrypted file
-    let file_path = config.entry_path(&path);
+    let file_path = config.entry_path(&path)?;
     if !file_path.exists() {
         anyhow::bail!("No such password: {}", file_path.display());
     }
@@ -19,17 +19,16 @@ pub fn run(config: &Config, path: String) -> Result<()> {
     let plaintext =
         crypto::decrypt(&config.secret, &file_path).context("Failed to decrypt existing entry")?;
 
-    // Write decrypted contents to a temp file (through a hook for testability)
-    let tmp = NamedTempFile::new().context("Failed to create temporary file")?;
+    // Write decrypted contents to a private temp file (through a hook for testability)
+    let tmp = secure_fs::scratch_file()?;
     let p = tmp.path();
     WRITE_HOOK.with(|h| {
-        (h.borrow())(p, &plaintext)
+        (h.borrow())(p, plaintext.expose())
             .with_context(|| format!("Failed to write to temporary file {:?}", p))
     })?;
 
     // Launch editor
-    let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
-    let status = Command::new(editor)
+    let status = Command::new(config.editor())
         .arg(tmp.path())
         .status()
         .context("Failed to launch $EDITOR")?;
@@ -38,21 +37,31 @@ pub fn run(config: &Config, path: String) -> Result<()> {
     }
 
     // Read updated contents
-    let updated = fs::read_to_string(tmp.path())
-        .context("Failed to read from temporary file")?
-        .trim_end()
-        .to_string();
+    let raw = SecretString::new(
+        fs::read_to_string(tmp.path()).context("Failed to read from temporary file")?,
+    );
+    let mut updated = SecretString::from(raw.expose().trim_end());
     if updated.is_empty() {
         anyhow::bail!("Aborted: no content (file was empty)");
     }
+    // a new password restarts the rotation clock; re-encryption doesn't
+    let mut entry = Entry::parse(updated.expose());
+    if entry.password != Entry::parse(plaintext.expose()).password
+        && entry.field(expiry::ROTATE_EVERY).is_some()
+    {
+        expiry::record_change(&mut entry, expiry::today());
+        updated = entry.to_string().into();
+    }
 
     // Re-encrypt with updated contents
-    let public = config.read_public()?;
-    crypto::encrypt(&public, &file_path, &updated).context("Failed to re-encrypt updated entry")?;
+    let recipients = config.recipients_for(&file_path)?;
+    crypto::encrypt(&recipients, &file_path, &updated)
+        .context("Failed to re-encrypt updated entry")?;
+    index::record(config, &path, updated.expose());
     println!("Password for '{}' updated successfully.", path);
 
     // Sync vault
-    git_ops::sync_vault(&config.prefix)?;
+    git_ops::sync_vault(&config.prefix, config.sync)?;
 
     Ok(())
 }
@@ -71,7 +80,7 @@ pub fn set_write_hook(f: fn(&Path, &str) -> std::io::Result<()>) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::Config;
+    use crate::config::{Config, SyncPolicy};
     use crate::crypto::{CryptoBackend, set_decrypt_factory, set_encrypt_factory};
     use anyhow::Result;
     use serial_test::serial;
@@ -91,16 +100,21 @@ mod tests {
             secret: tmp.path().to_path_buf(),
             crypto_extension: "rage".into(),
             public_key_filename: "public.key".into(),
+            editor: None,
+            clipboard_timeout: 45,
+            sync: SyncPolicy::Manual,
+            vault: None,
+            remote: None,
         }
     }
 
     // Backend that always fails decrypt with "boom"
     struct BoomBackend;
     impl CryptoBackend for BoomBackend {
-        fn decrypt(&self, _id: &Path, _enc: &Path) -> Result<String> {
+        fn decrypt(&self, _id: &Path, _enc: &Path) -> Result<SecretString> {
             Err(anyhow::anyhow!("boom"))
         }
-        fn encrypt(&self, _r: &str, _o: &Path, _p: &str) -> Result<()> {
+        fn encrypt(&self, _r: &[String], _o: &Path, _p: &SecretString) -> Result<()> {
             unreachable!()
         }
     }
@@ -111,10 +125,10 @@ mod tests {
     // Backend that returns a fixed plaintext
     struct OkBackend(&'static str);
     impl CryptoBackend for OkBackend {
-        fn decrypt(&self, _id: &Path, _enc: &Path) -> Result<String> {
-            Ok(self.0.to_string())
+        fn decrypt(&self, _id: &Path, _enc: &Path) -> Result<SecretString> {
+            Ok(self.0.into())
         }
-        fn encrypt(&self, _r: &str, _o: &Path, _p: &str) -> Result<()> {
+        fn encrypt(&self, _r: &[String], _o: &Path, _p: &SecretString) -> Result<()> {
             Ok(())
         }
     }
@@ -143,7 +157,7 @@ mod tests {
         let cfg = make_test_config(&tmp);
 
         let err = run(&cfg, "noentry".into()).unwrap_err();
-        let expected = format!("No such password: {}", cfg.entry_path("noentry").display());
+        let expected = format!("No such password: {}", cfg.entry_path("noentry")?.display());
         assert_eq!(err.to_string(), expected);
         Ok(())
     }
@@ -153,7 +167,7 @@ mod tests {
         let tmp = TempDir::new()?;
         let cfg = make_test_config(&tmp);
 
-        let entry = cfg.entry_path("entry");
+        let entry = cfg.entry_path("entry")?;
         File::create(&entry)?;
 
         set_decrypt_factory(boom_factory);
@@ -169,7 +183,7 @@ mod tests {
         let tmp = TempDir::new()?;
         let cfg = make_test_config(&tmp);
 
-        let entry = cfg.entry_path("entry");
+        let entry = cfg.entry_path("entry")?;
         File::create(&entry)?;
 
         set_decrypt_factory(ok_factory_empty);
@@ -189,13 +203,13 @@ mod tests {
     #[serial]
     fn editor_exit_error() -> Result<()> {
         let tmp = TempDir::new()?;
-        // create public key so read_public() succeeds
+        // create public key so recipients_for() succeeds
         let pub_key = tmp.path().join("public.key");
         File::create(&pub_key)?;
 
         // set up config and a dummy encrypted file
         let cfg = make_test_config(&tmp);
-        let entry = cfg.entry_path("entry");
+        let entry = cfg.entry_path("entry")?;
         File::create(&entry)?;
 
         // stub decrypt to return some non-empty plaintext
@@ -216,21 +230,21 @@ mod tests {
     fn success_and_reencrypts() -> Result<()> {
         let tmp = TempDir::new()?;
         let pub_key = tmp.path().join("public.key");
-        File::create(&pub_key)?;
+        File::create(&pub_key)?.write_all(b"dummy")?;
 
         let cfg = make_test_config(&tmp);
-        let entry = cfg.entry_path("entry");
+        let entry = cfg.entry_path("entry")?;
         File::create(&entry)?;
 
         set_decrypt_factory(ok_factory_orig);
 
         struct Spy;
         impl CryptoBackend for Spy {
-            fn encrypt(&self, _r: &str, _o: &Path, p: &str) -> Result<()> {
-                assert_eq!(p, "updated");
+            fn encrypt(&self, _r: &[String], _o: &Path, p: &SecretString) -> Result<()> {
+                assert_eq!(p.expose(), "updated");
                 Ok(())
             }
-            fn decrypt(&self, _i: &Path, _e: &Path) -> Result<String> {
+            fn decrypt(&self, _i: &Path, _e: &Path) -> Result<SecretString> {
                 unreachable!()
             }
         }
@@ -245,6 +259,38 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[serial]
+    fn new_passwords_record_when_they_changed() -> Result<()> {
+        let tmp = TempDir::new()?;
+        File::create(tmp.path().join("public.key"))?.write_all(b"dummy")?;
+        let cfg = make_test_config(&tmp);
+        File::create(cfg.entry_path("entry")?)?;
+
+        set_decrypt_factory(|_: &Path| Box::new(OkBackend("old\nrotate-every: 90d")));
+        struct Spy;
+        impl CryptoBackend for Spy {
+            fn encrypt(&self, _r: &[String], _o: &Path, p: &SecretString) -> Result<()> {
+                let entry = Entry::parse(p.expose());
+                assert_eq!(entry.password, "new");
+                let today = expiry::today().format("%Y-%m-%d").to_string();
+                assert_eq!(entry.field(expiry::CHANGED), Some(today.as_str()));
+                Ok(())
+            }
+            fn decrypt(&self, _i: &Path, _e: &Path) -> Result<SecretString> {
+                unreachable!()
+            }
+        }
+        set_encrypt_factory(|_: &Path| Box::new(Spy));
+
+        let script = make_editor_script(&tmp, r#"printf 'new\nrotate-every: 90d\n' > "$1""#);
+        unsafe {
+            env::set_var("EDITOR", &script);
+        }
+        run(&cfg, "entry".into())?;
+        Ok(())
+    }
+
     #[test]
     #[serial]
     fn write_failure_errors() -> Result<()> {
@@ -253,7 +299,7 @@ mod tests {
         File::create(&pub_key)?;
 
         let cfg = make_test_config(&tmp);
-        let entry = cfg.entry_path("e1");
+        let entry = cfg.entry_path("e1")?;
         File::create(&entry)?;
 
         set_decrypt_factory(ok_factory_empty);
@@ -0,0 +1,144 @@
+use crate::config::Config;
+use crate::crypto;
+use crate::entry::Entry;
+use crate::utils::entries;
+use crate::utils::expiry::{self, Due};
+use anyhow::Result;
+use chrono::NaiveDate;
+use std::path::PathBuf;
+
+/// An entry whose password is due, or couldn't be checked
+pub struct Expiring {
+    pub name: String,
+    pub path: PathBuf,
+    pub due: Result<Due>,
+}
+
+/// List entries whose password must be changed soon.
+///
+/// Usage:
+///     passman expiring [--within 14d] [--all]
+///
+/// An entry is due on its `expires:` date, or `rotate-every:` after its
+/// password last changed: the `changed:` field that `edit` and `generate
+/// --in-place` keep, else its file's last git commit (or modification time
+/// outside git).
+/// Overdue entries are always listed.
+pub fn run(config: &Config, within: &str, show_all: bool) -> Result<()> {
+    let today = expiry::today();
+    let limit = expiry::add_period(today, within)?;
+
+    let mut due = scan(config, show_all, today)?;
+    due.retain(|e| e.due.as_ref().map_or(true, |d| d.date <= limit));
+    if due.is_empty() {
+        println!("✅ Nothing due within {}", within);
+        return Ok(());
+    }
+    due.sort_by_key(|e| e.due.as_ref().map(|d| d.date).ok());
+
+    let width = due
+        .iter()
+        .map(|e| e.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("ENTRY".len());
+    println!("{:<10}  {:<width$}  STATUS", "DUE", "ENTRY");
+    for e in &due {
+        match &e.due {
+            Ok(d) => println!(
+                "{}  {:<width$}  {} ({})",
+                d.date,
+                e.name,
+                describe(d.date, today),
+                d.rule
+            ),
+            Err(err) => println!("{:<10}  {:<width$}  ⚠️  {:#}", "?", e.name, err),
+        }
+    }
+    Ok(())
+}
+
+/// Every entry with an expiry rule, with the date it's due. Entries that
+/// fail to decrypt are reported rather than silently skipped.
+pub fn scan(config: &Config, show_all: bool, today: NaiveDate) -> Result<Vec<Expiring>> {
+    let files = entries::collect(&config.prefix, show_all)?;
+    let paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+    let changed = expiry::last_changed(&config.prefix, &paths);
+
+    let mut found = Vec::new();
+    for file in files {
+        let due = crypto::decrypt(&config.secret, &file.path).and_then(|plaintext| {
+            let last = changed.get(&file.path).copied().unwrap_or(today);
+            expiry::due(&Entry::parse(plaintext.expose()), last)
+        });
+        let due = match due {
+            Ok(None) => continue,
+            Ok(Some(d)) => Ok(d),
+            Err(e) => Err(e),
+        };
+        found.push(Expiring {
+            name: file.name,
+            path: file.path,
+            due,
+        });
+    }
+    Ok(found)
+}
+
+/// "overdue by 3 days", "due today", "due in 5 days"
+pub fn describe(date: NaiveDate, today: NaiveDate) -> String {
+    let days = (date - today).num_days();
+    match days {
+        0 => "due today".into(),
+        1 => "due tomorrow".into(),
+        -1 => "overdue by 1 day".into(),
+        d if d < 0 => format!("overdue by {} days", -d),
+        d => format!("due in {} days", d),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use crate::crypto::testing;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn finds_due_entries() -> Result<()> {
+        testing::use_plain();
+        let tmp = TempDir::new()?;
+        let cfg = test_config(tmp.path());
+        fs::write(cfg.entry_path("old")?, "pw\nexpires: 2000-01-01")?;
+        fs::write(cfg.entry_path("fresh")?, "pw\nrotate-every: 90d")?;
+        fs::write(cfg.entry_path("forever")?, "pw\nusername: me")?;
+        fs::write(cfg.entry_path("broken")?, "pw\nexpires: someday")?;
+
+        let today = expiry::today();
+        let found = scan(&cfg, false, today)?;
+        let names: Vec<&str> = found.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["broken", "fresh", "old"]);
+
+        assert!(found[0].due.is_err());
+        let fresh = found[1].due.as_ref().unwrap();
+        assert_eq!(fresh.date, expiry::add_period(today, "90d")?);
+        let old = found[2].due.as_ref().unwrap();
+        assert_eq!(old.date, NaiveDate::from_ymd_opt(2000, 1, 1).unwrap());
+        assert_eq!(old.rule, "expires: 2000-01-01");
+
+        run(&cfg, "14d", false)?;
+        Ok(())
+    }
+
+    #[test]
+    fn describes_relative_dates() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2025, 3, d).unwrap();
+        assert_eq!(describe(day(10), today), "due today");
+        assert_eq!(describe(day(11), today), "due tomorrow");
+        assert_eq!(describe(day(15), today), "due in 5 days");
+        assert_eq!(describe(day(9), today), "overdue by 1 day");
+        assert_eq!(describe(day(1), today), "overdue by 9 days");
+    }
+}
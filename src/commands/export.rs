@@ -0,0 +1,487 @@
+use crate::cli::ExportFormat;
+use crate::config::{Config, RECIPIENTS_FILENAME};
+use crate::crypto::{self, SecretString};
+use crate::entry::Entry;
+use crate::utils::bundle::{self, Bundle, Seal};
+use crate::utils::entries;
+use anyhow::{Context, Result};
+use dialoguer::Password;
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+type PassphraseFn = fn() -> Result<String>;
+
+thread_local! {
+    static PASSPHRASE_HOOK: RefCell<PassphraseFn> = RefCell::new(prompt_passphrase);
+}
+
+/// Test-only hook to answer the bundle passphrase prompt
+#[cfg(test)]
+pub fn set_passphrase_hook(f: PassphraseFn) {
+    PASSPHRASE_HOOK.with(|h| *h.borrow_mut() = f);
+}
+
+fn prompt_passphrase() -> Result<String> {
+    Password::new()
+        .with_prompt("Bundle passphrase")
+        .with_confirmation("Repeat passphrase", "Passphrases don't match")
+        .interact()
+        .context("Failed to read passphrase")
+}
+
+/// What `passman export` was asked to do
+#[derive(Default)]
+pub struct Options {
+    pub format: ExportFormat,
+    pub output: Option<PathBuf>,
+
+    /// Only entries at or below this folder
+    pub subtree: Option<String>,
+
+    /// Only entries whose path matches this glob (`*` stays within a folder)
+    pub glob: Option<String>,
+
+    pub no_notes: bool,
+    pub recipients: Vec<String>,
+    pub passphrase: bool,
+    pub show_all: bool,
+
+    /// Allow plaintext on a terminal
+    pub force: bool,
+}
+
+/// Export the vault.
+///
+/// Usage:
+///     passman export [--format json|csv|bundle | --json] [SUBTREE] [--glob PATTERN]
+///         [--no-notes] [--output FILE] [--all] [--force]
+///         [--recipient AGE1...]... [--passphrase]
+///
+/// `json` and `csv` are plaintext, meant for scripts and for moving to
+/// another tool; they refuse to print to a terminal unless `--force` is
+/// given. `bundle` is a single age file holding the entries (archived ones
+/// included) and the folder `.recipients` files, encrypted to the given
+/// recipients, a passphrase, or by default the vault's own recipients.
+/// Restore it with `passman import bundle`.
+pub fn run(config: &Config, opts: Options) -> Result<()> {
+    let out = opts.output.as_deref();
+    let to_terminal = out.is_none() && io::stdout().is_terminal();
+    let filter = Filter::new(opts.subtree.as_deref(), opts.glob.as_deref())?;
+
+    match opts.format {
+        ExportFormat::Json | ExportFormat::Csv => {
+            check_terminal(to_terminal, opts.force)?;
+            let mut decrypted = decrypt_all(config, &filter, opts.show_all)?;
+            if opts.no_notes {
+                for (_, plaintext) in &mut decrypted {
+                    let mut entry = Entry::parse(plaintext.expose());
+                    entry.notes.clear();
+                    *plaintext = entry.to_string().into();
+                }
+            }
+
+            let mut w = open_output(out)?;
+            if let ExportFormat::Json = opts.format {
+                serde_json::to_writer_pretty(&mut w, &to_json(&decrypted))?;
+                writeln!(w)?;
+            } else {
+                write_csv(w, &decrypted)?;
+            }
+        }
+        ExportFormat::Bundle => {
+            if to_terminal {
+                anyhow::bail!("Refusing to write a bundle to the terminal; use --output");
+            }
+            let seal = if opts.passphrase {
+                Seal::Passphrase(PASSPHRASE_HOOK.with(|h| (h.borrow())())?.into())
+            } else if opts.recipients.is_empty() {
+                Seal::Recipients(config.read_recipients()?)
+            } else {
+                Seal::Recipients(opts.recipients)
+            };
+
+            let bundle = Bundle {
+                version: bundle::VERSION,
+                entries: decrypt_all(config, &filter, true)?.into_iter().collect(),
+                recipients: recipient_files(&config.prefix)?,
+            };
+            bundle::seal(&bundle, &seal, open_output(out)?)?;
+            if let Some(path) = out {
+                eprintln!(
+                    "📦 {} entries written to {}",
+                    bundle.entries.len(),
+                    path.display()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Plaintext on a terminal ends up in scrollback
+fn check_terminal(to_terminal: bool, force: bool) -> Result<()> {
+    if to_terminal && !force {
+        anyhow::bail!(
+            "Refusing to print decrypted entries to the terminal; \
+             pipe the output, use --output, or pass --force"
+        );
+    }
+    Ok(())
+}
+
+/// Which entry names an export includes
+struct Filter {
+    subtree: Option<String>,
+    glob: Option<glob::Pattern>,
+}
+
+impl Filter {
+    fn new(subtree: Option<&str>, glob: Option<&str>) -> Result<Self> {
+        let subtree = subtree
+            .map(|s| s.trim_matches('/').to_string())
+            .filter(|s| !s.is_empty());
+        let glob = glob
+            .map(|g| glob::Pattern::new(g).with_context(|| format!("Invalid glob `{}`", g)))
+            .transpose()?;
+        Ok(Filter { subtree, glob })
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let in_subtree = self.subtree.as_deref().is_none_or(|s| {
+            name == s
+                || name
+                    .strip_prefix(s)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        });
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        in_subtree
+            && self
+                .glob
+                .as_ref()
+                .is_none_or(|g| g.matches_with(name, options))
+    }
+}
+
+/// `(name, plaintext)` for every matching entry, stopping at the first
+/// failure so an export is never silently incomplete
+fn decrypt_all(
+    config: &Config,
+    filter: &Filter,
+    show_all: bool,
+) -> Result<Vec<(String, SecretString)>> {
+    entries::collect(&config.prefix, show_all)?
+        .into_iter()
+        .filter(|e| filter.matches(&e.name))
+        .map(|e| {
+            let plaintext = crypto::decrypt(&config.secret, &e.path)
+                .with_context(|| format!("Failed to decrypt {}", e.name))?;
+            Ok((e.name, plaintext))
+        })
+        .collect()
+}
+
+/// `{ "<path>": { "password": ..., "<field>": ..., "notes": ... } }`
+fn to_json(decrypted: &[(String, SecretString)]) -> Value {
+    let mut doc = Map::new();
+    for (name, plaintext) in decrypted {
+        let entry = Entry::parse(plaintext.expose());
+        let mut obj = Map::new();
+        for (k, v) in &entry.fields {
+            obj.entry(k.to_lowercase()).or_insert(v.clone().into());
+        }
+        obj.insert("password".into(), entry.password.clone().into());
+        if !entry.notes.is_empty() {
+            obj.insert("notes".into(), entry.notes.clone().into());
+        }
+        doc.insert(name.clone(), Value::Object(obj));
+    }
+    Value::Object(doc)
+}
+
+const CSV_COLUMNS: [&str; 7] = [
+    "path", "password", "username", "url", "totp", "fields", "notes",
+];
+
+/// One row per entry; fields without a column of their own go into `fields`
+/// as `key: value` lines
+fn write_csv(out: impl Write, decrypted: &[(String, SecretString)]) -> Result<()> {
+    let mut w = ::csv::Writer::from_writer(out);
+    w.write_record(CSV_COLUMNS)?;
+    for (name, plaintext) in decrypted {
+        let entry = Entry::parse(plaintext.expose());
+        let field = |k: &str| entry.field(k).unwrap_or("").to_string();
+        let others: Vec<String> = entry
+            .fields
+            .iter()
+            .filter(|(k, _)| !CSV_COLUMNS.contains(&k.to_lowercase().as_str()))
+            .map(|(k, v)| format!("{}: {}", k, v))
+            .collect();
+        w.write_record([
+            name.clone(),
+            entry.password.clone(),
+            field("username"),
+            field("url"),
+            field("totp"),
+            others.join("\n"),
+            entry.notes.clone(),
+        ])?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Every `.recipients` file under the vault, keyed by its folder
+fn recipient_files(prefix: &Path) -> Result<BTreeMap<String, String>> {
+    let mut files = BTreeMap::new();
+    let walker = WalkDir::new(prefix)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git");
+    for entry in walker.filter_map(Result::ok) {
+        if entry.file_name() != RECIPIENTS_FILENAME || !entry.file_type().is_file() {
+            continue;
+        }
+        let folder = entry
+            .path()
+            .parent()
+            .and_then(|p| p.strip_prefix(prefix).ok())
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        files.insert(folder, fs::read_to_string(entry.path())?);
+    }
+    Ok(files)
+}
+
+/// stdout, or a new file only the owner can read
+fn open_output(output: Option<&Path>) -> Result<Box<dyn Write>> {
+    match output {
+        None => Ok(Box::new(io::stdout().lock())),
+        Some(path) => {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            Ok(Box::new(file))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use crate::crypto::{CryptoBackend, set_decrypt_factory, testing};
+    use age::secrecy::ExposeSecret;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    fn make_config(tmp: &TempDir) -> Config {
+        let cfg = test_config(tmp.path());
+        let prefix = &cfg.prefix;
+        fs::create_dir_all(prefix.join("web")).unwrap();
+        fs::write(
+            prefix.join("web/github.rage"),
+            "ghp\nusername: octo\nPIN: 12",
+        )
+        .unwrap();
+        fs::write(prefix.join("mail.age"), "hunter2\n\nsecond mailbox").unwrap();
+        fs::write(prefix.join(".old.rage"), "archived").unwrap();
+        fs::write(prefix.join("web/.recipients"), "age1web\n").unwrap();
+        cfg
+    }
+
+    #[test]
+    fn json_maps_paths_to_fields() -> Result<()> {
+        testing::use_plain();
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let out = tmp.path().join("out.json");
+
+        run(
+            &cfg,
+            Options {
+                output: Some(out.clone()),
+                ..Default::default()
+            },
+        )?;
+
+        let json: Value = serde_json::from_str(&fs::read_to_string(&out)?)?;
+        assert_eq!(json["web/github"]["password"], "ghp");
+        assert_eq!(json["web/github"]["username"], "octo");
+        assert_eq!(json["web/github"]["pin"], "12");
+        assert_eq!(json["mail"]["notes"], "second mailbox");
+        assert!(json.get(".old").is_none());
+        assert_eq!(fs::metadata(&out)?.permissions().mode() & 0o777, 0o600);
+        Ok(())
+    }
+
+    #[test]
+    fn csv_has_one_row_per_entry() -> Result<()> {
+        testing::use_plain();
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let out = tmp.path().join("out.csv");
+
+        run(
+            &cfg,
+            Options {
+                format: ExportFormat::Csv,
+                output: Some(out.clone()),
+                ..Default::default()
+            },
+        )?;
+
+        let text = fs::read_to_string(&out)?;
+        let mut lines = text.lines();
+        assert_eq!(
+            lines.next(),
+            Some("path,password,username,url,totp,fields,notes")
+        );
+        assert_eq!(lines.next(), Some("mail,hunter2,,,,,second mailbox"));
+        assert_eq!(lines.next(), Some("web/github,ghp,octo,,,PIN: 12,"));
+        Ok(())
+    }
+
+    #[test]
+    fn bundle_holds_everything() -> Result<()> {
+        testing::use_plain();
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let identity = age::x25519::Identity::generate();
+        let key_file = tmp.path().join("backup.key");
+        fs::write(&key_file, identity.to_string().expose_secret())?;
+        let out = tmp.path().join("vault.bundle");
+
+        let recipient = identity.to_public().to_string();
+        run(
+            &cfg,
+            Options {
+                format: ExportFormat::Bundle,
+                output: Some(out.clone()),
+                recipients: vec![recipient],
+                ..Default::default()
+            },
+        )?;
+
+        let opened = bundle::open(fs::File::open(&out)?, &key_file, || unreachable!())?;
+        assert_eq!(
+            opened.entries.keys().collect::<Vec<_>>(),
+            vec![".old", "mail", "web/github"]
+        );
+        assert_eq!(opened.recipients["web"], "age1web\n");
+        Ok(())
+    }
+
+    #[test]
+    fn bundle_can_use_a_passphrase() -> Result<()> {
+        testing::use_plain();
+        set_passphrase_hook(|| Ok("correct horse".into()));
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let out = tmp.path().join("vault.bundle");
+
+        run(
+            &cfg,
+            Options {
+                format: ExportFormat::Bundle,
+                output: Some(out.clone()),
+                passphrase: true,
+                ..Default::default()
+            },
+        )?;
+
+        let opened = bundle::open(fs::File::open(&out)?, Path::new("/nonexistent"), || {
+            Ok("correct horse".into())
+        })?;
+        assert_eq!(opened.entries["mail"].expose(), "hunter2\n\nsecond mailbox");
+        Ok(())
+    }
+
+    #[test]
+    fn undecryptable_entries_abort() -> Result<()> {
+        struct Broken;
+        impl CryptoBackend for Broken {
+            fn encrypt(&self, _r: &[String], _o: &Path, _p: &SecretString) -> Result<()> {
+                unreachable!()
+            }
+            fn decrypt(&self, _i: &Path, _e: &Path) -> Result<SecretString> {
+                anyhow::bail!("no identity matched")
+            }
+        }
+        set_decrypt_factory(|_: &Path| Box::new(Broken));
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+
+        let output = Some(tmp.path().join("out.json"));
+        let err = run(
+            &cfg,
+            Options {
+                output,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Failed to decrypt mail");
+        Ok(())
+    }
+
+    #[test]
+    fn json_can_be_narrowed_and_drop_notes() -> Result<()> {
+        testing::use_plain();
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        fs::write(
+            cfg.prefix.join("web/gitlab.rage"),
+            "glpat
+
+recovery codes",
+        )?;
+        fs::create_dir_all(cfg.prefix.join("web/deep"))?;
+        fs::write(cfg.prefix.join("web/deep/git.rage"), "nested")?;
+        let out = tmp.path().join("out.json");
+
+        run(
+            &cfg,
+            Options {
+                output: Some(out.clone()),
+                subtree: Some("web/".into()),
+                glob: Some("web/git*".into()),
+                no_notes: true,
+                ..Default::default()
+            },
+        )?;
+
+        let json: Value = serde_json::from_str(&fs::read_to_string(&out)?)?;
+        let keys: Vec<_> = json.as_object().unwrap().keys().collect();
+        assert_eq!(keys, vec!["web/github", "web/gitlab"]);
+        assert!(json["web/gitlab"].get("notes").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn subtree_matches_whole_folders() -> Result<()> {
+        let filter = Filter::new(Some("web"), None)?;
+        assert!(filter.matches("web/github"));
+        assert!(!filter.matches("webmail"));
+        Ok(())
+    }
+
+    #[test]
+    fn terminal_needs_force() {
+        let err = check_terminal(true, false).unwrap_err();
+        assert!(err.to_string().starts_with("Refusing to print"));
+        assert!(check_terminal(true, true).is_ok());
+        assert!(check_terminal(false, false).is_ok());
+    }
+}
@@ -0,0 +1,155 @@
+use crate::config::{self, Config};
+use crate::utils::{entries, index};
+use anyhow::{Context, Result};
+
+/// Search entry names in every configured vault.
+///
+/// Matches are printed one per line as `<vault>:<path>`, without the crypto
+/// extension, so they can be passed straight back as `--vault <vault> show <path>`.
+/// With `--meta`, usernames, URLs, tags and field names match too; each
+/// vault's search index is decrypted once instead of every entry.
+pub fn run(pattern: &str, meta: bool, show_all: bool) -> Result<()> {
+    let vaults = config::load_all_vaults()?;
+    let matches = if meta {
+        search_meta(&vaults, pattern, show_all)?
+    } else {
+        search(&vaults, pattern, show_all)?
+    };
+
+    if matches.is_empty() {
+        anyhow::bail!("No entries matching `{}`", pattern);
+    }
+    for m in matches {
+        println!("{}", m);
+    }
+    Ok(())
+}
+
+/// Case-insensitive substring search over entry paths in each vault
+fn search(vaults: &[(String, Config)], pattern: &str, show_all: bool) -> Result<Vec<String>> {
+    let needle = pattern.to_lowercase();
+    let mut matches = Vec::new();
+
+    for (name, cfg) in vaults {
+        let found = entries::collect(&cfg.prefix, show_all)
+            .with_context(|| format!("Failed to list vault {}", name))?;
+        for entry in found {
+            if entry.name.to_lowercase().contains(&needle) {
+                matches.push(format!("{}:{}", name, entry.name));
+            }
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Like `search`, also matching each entry's indexed metadata
+fn search_meta(vaults: &[(String, Config)], pattern: &str, show_all: bool) -> Result<Vec<String>> {
+    let needle = pattern.to_lowercase();
+    let mut matches = Vec::new();
+
+    for (name, cfg) in vaults {
+        let metadata = index::metadata(cfg)
+            .with_context(|| format!("Failed to read the search index of vault {}", name))?;
+        for (entry, meta) in metadata {
+            if !show_all && index::is_hidden(&entry) {
+                continue;
+            }
+            if entry.to_lowercase().contains(&needle) || meta.matches(&needle) {
+                matches.push(format!("{}:{}", name, entry));
+            }
+        }
+    }
+
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use std::fs::{self, File};
+    use tempfile::TempDir;
+
+    fn make_config(tmp: &TempDir, name: &str) -> Config {
+        Config {
+            vault: Some(name.into()),
+            ..test_config(&tmp.path().join(name))
+        }
+    }
+
+    #[test]
+    fn finds_matches_across_vaults_with_prefix() {
+        let tmp = TempDir::new().unwrap();
+        let personal = make_config(&tmp, "personal");
+        let team = make_config(&tmp, "team");
+        fs::create_dir_all(personal.prefix.join("bank")).unwrap();
+        File::create(personal.prefix.join("bank/Chase.rage")).unwrap();
+        File::create(team.prefix.join("chase-api.rage")).unwrap();
+        File::create(team.prefix.join("github.rage")).unwrap();
+        File::create(team.prefix.join("chase-old.age")).unwrap();
+
+        let vaults = vec![("personal".into(), personal), ("team".into(), team)];
+        let found = search(&vaults, "chase", false).unwrap();
+        assert_eq!(
+            found,
+            vec!["personal:bank/Chase", "team:chase-api", "team:chase-old"]
+        );
+    }
+
+    #[test]
+    fn hidden_entries_need_all() {
+        let tmp = TempDir::new().unwrap();
+        let cfg = make_config(&tmp, "personal");
+        File::create(cfg.prefix.join(".old-mail.rage")).unwrap();
+        fs::create_dir_all(cfg.prefix.join(".git")).unwrap();
+        File::create(cfg.prefix.join(".git/mail.rage")).unwrap();
+
+        let vaults = vec![("personal".into(), cfg)];
+        assert!(search(&vaults, "mail", false).unwrap().is_empty());
+        assert_eq!(
+            search(&vaults, "mail", true).unwrap(),
+            vec!["personal:.old-mail"]
+        );
+    }
+
+    #[test]
+    fn unreadable_manifests_are_reported_not_fatal() -> Result<()> {
+        crate::crypto::testing::use_plain();
+        let tmp = TempDir::new()?;
+        let team = make_config(&tmp, "team");
+        fs::write(team.prefix.join(".passman-manifest.rage"), "not json")?;
+        config::register(&team);
+
+        let vaults = vec![("team".into(), team)];
+        let err = search(&vaults, "db", false).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to list vault team");
+        Ok(())
+    }
+
+    #[test]
+    fn meta_search_matches_usernames_urls_and_tags() -> Result<()> {
+        crate::crypto::testing::use_plain();
+
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp, "team");
+        fs::write(
+            cfg.prefix.join("db.rage"),
+            "pw\nusername: alice\ntags: prod",
+        )?;
+        fs::write(
+            cfg.prefix.join("web.rage"),
+            "pw\nurl: https://alice.example",
+        )?;
+        fs::write(cfg.prefix.join("mail.rage"), "alice-is-the-password")?;
+
+        let vaults = vec![("team".into(), cfg)];
+        assert_eq!(
+            search_meta(&vaults, "ALICE", false)?,
+            vec!["team:db", "team:web"]
+        );
+        assert_eq!(search_meta(&vaults, "prod", false)?, vec!["team:db"]);
+        assert!(search(&vaults, "alice", false)?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,388 @@
+use crate::config::{Config, RECIPIENTS_FILENAME};
+use crate::crypto;
+use crate::utils::{age_header, entries, index, signing};
+use anyhow::{Context, Result};
+use git2::{Repository, StatusOptions};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Files that belong in a vault without being entries
+const VAULT_FILES: &[&str] = &[RECIPIENTS_FILENAME, ".gitignore", ".gitattributes"];
+
+/// Extensions editors and merge tools leave behind
+const BACKUP_EXTENSIONS: &[&str] = &["swp", "swo", "swx", "bak", "orig", "rej", "tmp"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Problem {
+    Empty,
+    InvalidHeader,
+    Undecryptable,
+    Recipients,
+    Plaintext,
+    Backup,
+    Uncommitted,
+}
+
+impl Problem {
+    fn label(self) -> &'static str {
+        match self {
+            Problem::Empty => "empty",
+            Problem::InvalidHeader => "invalid header",
+            Problem::Undecryptable => "undecryptable",
+            Problem::Recipients => "recipients",
+            Problem::Plaintext => "not encrypted",
+            Problem::Backup => "editor backup",
+            Problem::Uncommitted => "uncommitted",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    /// Entry name, or the file's path in the vault when it isn't an entry
+    pub file: String,
+    pub path: PathBuf,
+    pub problem: Problem,
+    pub detail: String,
+}
+
+/// Check the vault's files for damage and leftovers.
+///
+/// Usage:
+///     passman fsck [--fix]
+///
+/// Every file under the vault must be an age file whose header parses,
+/// that this device can decrypt, and whose recipient stanzas match the
+/// recipients it would be encrypted to today. Plaintext files, editor
+/// backups and empty entries are reported, as is a dirty git working tree.
+///
+/// age only reveals how many X25519 recipients a file has, not which ones,
+/// so for those keys the check compares counts; SSH recipients are matched
+/// by key. `--fix` re-encrypts entries whose recipients are out of date.
+/// Exits non-zero when problems remain.
+pub fn run(config: &Config, fix: bool) -> Result<()> {
+    let mut findings = check(config)?;
+    let checked = files(&config.prefix).len();
+
+    if fix {
+        let (stale, rest): (Vec<_>, Vec<_>) = findings
+            .into_iter()
+            .partition(|f| f.problem == Problem::Recipients);
+        for f in &stale {
+            reencrypt(config, &f.path)
+                .with_context(|| format!("Failed to re-encrypt {}", f.file))?;
+            println!("🔑 Re-encrypted {}", f.file);
+        }
+        if !stale.is_empty() {
+            crate::utils::git_ops::sync_vault_with_message(
+                &config.prefix,
+                config.sync,
+                "Re-encrypt entries for their current recipients",
+            )?;
+        }
+        findings = rest;
+    }
+
+    print_table(checked, &findings);
+    if !findings.is_empty() {
+        anyhow::bail!("{} problems found", findings.len());
+    }
+    Ok(())
+}
+
+/// Every problem in the vault at `config.prefix`, sorted by file
+pub fn check(config: &Config) -> Result<Vec<Finding>> {
+    // hidden-names vaults store entries under IDs; report them by name
+    let names: HashMap<PathBuf, String> = entries::collect(&config.prefix, true)?
+        .into_iter()
+        .map(|e| (e.path, e.name))
+        .collect();
+
+    let mut findings = Vec::new();
+    for path in files(&config.prefix) {
+        let rel = path
+            .strip_prefix(&config.prefix)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .to_string();
+        let file = names.get(&path).cloned().unwrap_or_else(|| rel.clone());
+        if let Some((problem, detail)) = check_file(config, &path, &rel) {
+            findings.push(Finding {
+                file,
+                path,
+                problem,
+                detail,
+            });
+        }
+    }
+    if let Some(detail) = git_changes(&config.prefix) {
+        findings.push(Finding {
+            file: "(git)".into(),
+            path: config.prefix.clone(),
+            problem: Problem::Uncommitted,
+            detail,
+        });
+    }
+    findings.sort_by(|a, b| (&a.file, a.problem).cmp(&(&b.file, b.problem)));
+    Ok(findings)
+}
+
+/// Every file in the vault outside `.git` and the device signatures
+fn files(prefix: &Path) -> Vec<PathBuf> {
+    WalkDir::new(prefix)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git" && e.file_name() != signing::SIGNATURES_DIR)
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .collect()
+}
+
+fn check_file(config: &Config, path: &Path, rel: &str) -> Option<(Problem, String)> {
+    let name = path.file_name()?.to_string_lossy();
+    if VAULT_FILES.contains(&name.as_ref()) {
+        return None;
+    }
+    if is_backup(&name) {
+        return Some((Problem::Backup, "left behind by an editor".into()));
+    }
+    if crate::config::strip_entry_extension(&name).is_none() {
+        return Some((Problem::Plaintext, "not an .age or .rage file".into()));
+    }
+
+    match fs::metadata(path) {
+        Ok(m) if m.len() == 0 => return Some((Problem::Empty, "zero bytes".into())),
+        Ok(_) => {}
+        Err(e) => return Some((Problem::InvalidHeader, e.to_string())),
+    }
+    let header = match age_header::read(path) {
+        Ok(header) => header,
+        Err(e) => {
+            let cause = e.root_cause().to_string();
+            return Some((Problem::InvalidHeader, cause));
+        }
+    };
+    if let Err(e) = crypto::decrypt(&config.secret, path) {
+        let first_line = format!("{:#}", e)
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        return Some((Problem::Undecryptable, first_line));
+    }
+
+    // the search index is encrypted to this device only
+    if rel.starts_with(index::INDEX_NAME) {
+        return None;
+    }
+    let expected = match config.recipients_for(path) {
+        Ok(recipients) => recipients,
+        Err(e) => return Some((Problem::Recipients, format!("{:#}", e))),
+    };
+    let mut want: Vec<String> = expected
+        .iter()
+        .map(|r| age_header::recipient_kind(r).unwrap_or_else(|| r.clone()))
+        .collect();
+    want.sort();
+    let have = header.recipient_kinds();
+    if have != want {
+        return Some((
+            Problem::Recipients,
+            format!(
+                "encrypted for {}, expected {}",
+                describe(&have),
+                describe(&want)
+            ),
+        ));
+    }
+    None
+}
+
+fn is_backup(name: &str) -> bool {
+    let ext = name.rsplit_once('.').map(|(_, e)| e).unwrap_or_default();
+    name.ends_with('~')
+        || name.starts_with(".#")
+        || (name.starts_with('#') && name.ends_with('#'))
+        || BACKUP_EXTENSIONS.contains(&ext)
+}
+
+/// "2 X25519, 1 ssh-ed25519" for a list of recipient kinds
+fn describe(kinds: &[String]) -> String {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for kind in kinds {
+        let key_type = kind.split(' ').next().unwrap_or(kind);
+        *counts.entry(key_type).or_default() += 1;
+    }
+    if counts.is_empty() {
+        return "no recipients".into();
+    }
+    counts
+        .iter()
+        .map(|(k, n)| format!("{} {}", n, k))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Summary of uncommitted changes, if the vault is a dirty git repository
+fn git_changes(prefix: &Path) -> Option<String> {
+    let repo = Repository::open(prefix).ok()?;
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true).include_ignored(false);
+    let statuses = repo.statuses(Some(&mut opts)).ok()?;
+    match statuses.len() {
+        0 => None,
+        1 => Some("1 file changed since the last commit".into()),
+        n => Some(format!("{} files changed since the last commit", n)),
+    }
+}
+
+fn reencrypt(config: &Config, path: &Path) -> Result<()> {
+    let recipients = config.recipients_for(path)?;
+    let plaintext = crypto::decrypt(&config.secret, path)?;
+    crypto::encrypt(&recipients, path, &plaintext)
+}
+
+fn print_table(total: usize, findings: &[Finding]) {
+    if findings.is_empty() {
+        println!("✅ {} files checked, no problems found", total);
+        return;
+    }
+    let width = findings
+        .iter()
+        .map(|f| f.file.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("FILE".len());
+    println!("{:<width$}  {:<14}  DETAIL", "FILE", "PROBLEM");
+    for f in findings {
+        println!(
+            "{:<width$}  {:<14}  {}",
+            f.file,
+            f.problem.label(),
+            f.detail
+        );
+    }
+    println!();
+    println!("⚠️  {} files checked, {} problems", total, findings.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use crate::crypto::{CryptoBackend, SecretString, set_decrypt_factory, set_encrypt_factory};
+    use std::io::Write;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    /// Writes real age files (so headers parse) and "decrypts" anything
+    /// that has a header
+    struct HeaderBackend;
+    impl CryptoBackend for HeaderBackend {
+        fn encrypt(&self, r: &[String], o: &Path, p: &SecretString) -> Result<()> {
+            let recipients: Vec<age::x25519::Recipient> = r
+                .iter()
+                .map(|r| age::x25519::Recipient::from_str(r).unwrap())
+                .collect();
+            let encryptor = age::Encryptor::with_recipients(
+                recipients.iter().map(|r| r as &dyn age::Recipient),
+            )?;
+            let mut writer = encryptor.wrap_output(fs::File::create(o)?)?;
+            writer.write_all(p.expose().as_bytes())?;
+            writer.finish()?;
+            Ok(())
+        }
+        fn decrypt(&self, _i: &Path, e: &Path) -> Result<SecretString> {
+            age_header::read(e)?;
+            Ok("pw".into())
+        }
+    }
+
+    fn key() -> String {
+        age::x25519::Identity::generate().to_public().to_string()
+    }
+
+    fn make_config(tmp: &TempDir) -> Config {
+        set_encrypt_factory(|_: &Path| Box::new(HeaderBackend));
+        set_decrypt_factory(|_: &Path| Box::new(HeaderBackend));
+        let cfg = test_config(tmp.path());
+        fs::write(tmp.path().join("public.key"), format!("{}\n", key())).unwrap();
+        cfg
+    }
+
+    fn problems(config: &Config) -> Vec<(String, Problem)> {
+        check(config)
+            .unwrap()
+            .into_iter()
+            .map(|f| (f.file, f.problem))
+            .collect()
+    }
+
+    #[test]
+    fn reports_damaged_and_stray_files() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let good = cfg.entry_path("good")?;
+        crypto::encrypt(&cfg.recipients_for(&good)?, &good, &"pw".into())?;
+        fs::write(cfg.prefix.join("empty.rage"), "")?;
+        fs::write(cfg.prefix.join("broken.age"), "hunter2")?;
+        fs::write(cfg.prefix.join("notes.txt"), "hunter2")?;
+        fs::write(cfg.prefix.join("good.rage~"), "x")?;
+        fs::write(
+            cfg.prefix.join(RECIPIENTS_FILENAME),
+            fs::read(tmp.path().join("public.key"))?,
+        )?;
+
+        assert_eq!(
+            problems(&cfg),
+            vec![
+                ("broken".into(), Problem::InvalidHeader),
+                ("empty".into(), Problem::Empty),
+                ("good.rage~".into(), Problem::Backup),
+                ("notes.txt".into(), Problem::Plaintext),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fix_reencrypts_for_new_recipients() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        fs::create_dir_all(cfg.prefix.join("team"))?;
+        let entry = cfg.entry_path("team/db")?;
+        crypto::encrypt(&cfg.recipients_for(&entry)?, &entry, &"pw".into())?;
+        fs::write(
+            cfg.prefix.join("team").join(RECIPIENTS_FILENAME),
+            format!("{}\n{}\n", key(), key()),
+        )?;
+
+        let findings = check(&cfg)?;
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].problem, Problem::Recipients);
+        assert_eq!(
+            findings[0].detail,
+            "encrypted for 1 X25519, expected 2 X25519"
+        );
+
+        run(&cfg, true)?;
+        assert!(check(&cfg)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn dirty_git_tree_is_reported() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        Repository::init(&cfg.prefix)?;
+        let entry = cfg.entry_path("a")?;
+        crypto::encrypt(&cfg.recipients_for(&entry)?, &entry, &"pw".into())?;
+
+        assert_eq!(problems(&cfg), vec![("(git)".into(), Problem::Uncommitted)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use crate::config::Config;
+use crate::crypto::{self, SecretString};
+use crate::entry::Entry;
+use crate::utils::{expiry, git_ops, index};
+use anyhow::{Context, Result};
+use rand::Rng;
+use rand::rngs::OsRng;
+use std::fs;
+
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!#$%&()*+,-./:;<=>?@[]^_{|}~";
+
+/// Generate a random password and store it.
+///
+/// Usage:
+///     passman generate <path> [--length 24] [--no-symbols] [--in-place]
+///
+/// Without `--in-place` a new entry is created holding just the password.
+/// With it, only the first line of an existing entry is replaced; fields
+/// and notes are kept and the expiry clock starts again (see
+/// `passman expiring`).
+pub fn run(
+    config: &Config,
+    path: String,
+    length: usize,
+    no_symbols: bool,
+    in_place: bool,
+) -> Result<()> {
+    let file_path = config.entry_path(&path)?;
+    let password = password(length, !no_symbols)?;
+
+    let plaintext = if in_place {
+        if !file_path.exists() {
+            anyhow::bail!("No such password: {}", file_path.display());
+        }
+        let old = crypto::decrypt(&config.secret, &file_path)
+            .context("Failed to decrypt existing entry")?;
+        let mut entry = Entry::parse(old.expose());
+        entry.password = password.clone();
+        expiry::reset(&mut entry, expiry::today())?;
+        entry.to_string()
+    } else {
+        if file_path.exists() {
+            anyhow::bail!(
+                "{} already exists; use --in-place to replace its password",
+                path
+            );
+        }
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        password.clone()
+    };
+
+    let recipients = config.recipients_for(&file_path)?;
+    let plaintext = SecretString::from(plaintext);
+    crypto::encrypt(&recipients, &file_path, &plaintext)?;
+    index::record(config, &path, plaintext.expose());
+    println!("Generated a new password for '{}':", path);
+    println!("{}", password);
+
+    git_ops::sync_vault_with_message(
+        &config.prefix,
+        config.sync,
+        &format!("Generate password for {}", path),
+    )
+}
+
+/// A random password of `length` characters with at least one lower-case
+/// letter, upper-case letter, digit and (unless disabled) symbol
+pub fn password(length: usize, symbols: bool) -> Result<String> {
+    let mut classes = vec![LOWER, UPPER, DIGITS];
+    if symbols {
+        classes.push(SYMBOLS);
+    }
+    if length < classes.len() {
+        anyhow::bail!("Passwords must be at least {} characters", classes.len());
+    }
+    let alphabet: Vec<char> = classes.concat().chars().collect();
+
+    loop {
+        let candidate: String = (0..length)
+            .map(|_| alphabet[OsRng.gen_range(0..alphabet.len())])
+            .collect();
+        if classes
+            .iter()
+            .all(|class| candidate.chars().any(|c| class.contains(c)))
+        {
+            return Ok(candidate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::testing;
+    use tempfile::TempDir;
+
+    #[test]
+    fn passwords_use_every_class() -> Result<()> {
+        for _ in 0..50 {
+            let pw = password(8, true)?;
+            assert_eq!(pw.chars().count(), 8);
+            assert!(pw.chars().any(|c| SYMBOLS.contains(c)));
+        }
+        assert!(!password(30, false)?.chars().any(|c| SYMBOLS.contains(c)));
+        assert!(password(3, true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn creates_new_entries_only() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::plain_vault(tmp.path());
+
+        run(&cfg, "web/site".into(), 20, false, false)?;
+        assert_eq!(fs::read_to_string(cfg.entry_path("web/site")?)?.len(), 20);
+
+        let err = run(&cfg, "web/site".into(), 20, false, false).unwrap_err();
+        assert!(err.to_string().contains("use --in-place"));
+        Ok(())
+    }
+
+    #[test]
+    fn in_place_keeps_fields_and_resets_expiry() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::plain_vault(tmp.path());
+        fs::write(
+            cfg.entry_path("vpn")?,
+            "old\nusername: me\nexpires: 2000-01-01\nrotate-every: 30d\n\nnotes",
+        )?;
+
+        run(&cfg, "vpn".into(), 16, true, true)?;
+
+        let entry = Entry::parse(&fs::read_to_string(cfg.entry_path("vpn")?)?);
+        assert_ne!(entry.password, "old");
+        assert_eq!(entry.password.len(), 16);
+        assert_eq!(entry.field("username"), Some("me"));
+        assert_eq!(entry.notes, "notes");
+        let next = expiry::add_period(expiry::today(), "30d")?;
+        assert_eq!(
+            entry.field("expires"),
+            Some(next.format("%Y-%m-%d").to_string().as_str())
+        );
+        Ok(())
+    }
+}
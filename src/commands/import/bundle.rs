@@ -0,0 +1,221 @@
+use crate::cli::OnConflict;
+use crate::commands::import::{ImportReport, checked_name, pick_name, write_entry};
+use crate::config::{Config, RECIPIENTS_FILENAME, parse_recipients};
+use crate::utils::bundle;
+use anyhow::{Context, Result};
+use dialoguer::Password;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+type PassphraseFn = fn() -> Result<String>;
+
+thread_local! {
+    static PASSPHRASE_HOOK: RefCell<PassphraseFn> = RefCell::new(prompt_passphrase);
+}
+
+/// Test-only hook to answer the bundle passphrase prompt
+#[cfg(test)]
+pub fn set_passphrase_hook(f: PassphraseFn) {
+    PASSPHRASE_HOOK.with(|h| *h.borrow_mut() = f);
+}
+
+fn prompt_passphrase() -> Result<String> {
+    Password::new()
+        .with_prompt("Bundle passphrase")
+        .interact()
+        .context("Failed to read passphrase")
+}
+
+/// Restore a bundle made by `passman export --format bundle`.
+///
+/// Usage:
+///     passman import bundle <file> [--identity FILE] [--on-conflict skip|rename]
+///         [--restore-recipients] [--dry-run]
+///
+/// Passphrase bundles prompt for the passphrase; others are opened with
+/// `--identity`, defaulting to the vault's own secret key. Names that would
+/// land outside the vault are refused. The bundle's `.recipients` files
+/// are listed, and only written with `--restore-recipients` (existing ones
+/// are kept); then every entry is encrypted for the recipients of its new
+/// location.
+pub fn run(
+    config: &Config,
+    file: &Path,
+    identity: Option<PathBuf>,
+    on_conflict: OnConflict,
+    restore_recipients: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let input =
+        fs::File::open(file).with_context(|| format!("Failed to open {}", file.display()))?;
+    let identity = identity.unwrap_or_else(|| config.secret.clone());
+    let bundle = bundle::open(input, &identity, || {
+        PASSPHRASE_HOOK.with(|h| (h.borrow())())
+    })?;
+
+    let mut report = ImportReport::default();
+    for (folder, contents) in &bundle.recipients {
+        let folder = if folder.is_empty() {
+            String::new()
+        } else {
+            match checked_name(folder) {
+                Ok(folder) => folder,
+                Err(e) => {
+                    report.fail(format!("{}/{}", folder, RECIPIENTS_FILENAME), e);
+                    continue;
+                }
+            }
+        };
+        let shown = Path::new(&folder).join(RECIPIENTS_FILENAME);
+        if !restore_recipients {
+            report.notes.push(format!(
+                "not restored: {} ({}); check it and pass --restore-recipients",
+                shown.display(),
+                parse_recipients(contents).join(", ")
+            ));
+            continue;
+        }
+        let target = config.prefix.join(&folder).join(RECIPIENTS_FILENAME);
+        if target.exists() {
+            report.notes.push(format!(
+                "{} already exists; left unchanged",
+                target.display()
+            ));
+            continue;
+        }
+        if !dry_run {
+            fs::create_dir_all(target.parent().unwrap_or(&config.prefix))?;
+            fs::write(&target, contents)
+                .with_context(|| format!("Failed to write {}", target.display()))?;
+        }
+        report.notes.push(format!("restored {}", shown.display()));
+    }
+
+    let mut taken = HashSet::new();
+    for (raw, plaintext) in &bundle.entries {
+        let base = match checked_name(raw) {
+            Ok(base) => base,
+            Err(e) => {
+                report.fail(raw.clone(), e);
+                continue;
+            }
+        };
+        let Some(name) = pick_name(config, &base, on_conflict, &taken)? else {
+            report.fail(base.clone(), format!("{} already exists", base));
+            continue;
+        };
+        if name != base {
+            report.notes.push(format!("{} renamed to {}", base, name));
+        }
+        if !dry_run && let Err(e) = write_entry(config, &name, plaintext.expose()) {
+            report.fail(name, format!("{:#}", e));
+            continue;
+        }
+        taken.insert(name.clone());
+        report.imported.push(name);
+    }
+
+    report.finish(config, "bundle", dry_run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::testing;
+    use crate::utils::bundle::{Bundle, Seal};
+    use std::collections::BTreeMap;
+    use tempfile::TempDir;
+
+    fn write_bundle(tmp: &TempDir) -> Result<PathBuf> {
+        let bundle = Bundle {
+            version: bundle::VERSION,
+            entries: BTreeMap::from([
+                ("mail".into(), "hunter2".into()),
+                ("team/db".into(), "s3cret".into()),
+            ]),
+            recipients: BTreeMap::from([("team".into(), "age1me\nage1you\n".into())]),
+        };
+        let path = tmp.path().join("backup.age");
+        let file = fs::File::create(&path)?;
+        bundle::seal(&bundle, &Seal::Passphrase("pw".into()), file)?;
+        Ok(path)
+    }
+
+    #[test]
+    fn restores_entries_and_recipients() -> Result<()> {
+        set_passphrase_hook(|| Ok("pw".into()));
+        let tmp = TempDir::new()?;
+        let cfg = testing::recording_vault(tmp.path());
+        let file = write_bundle(&tmp)?;
+
+        run(&cfg, &file, None, OnConflict::Skip, true, false)?;
+
+        assert_eq!(
+            fs::read_to_string(cfg.entry_path("mail")?)?,
+            "age1me|hunter2"
+        );
+        assert_eq!(
+            fs::read_to_string(cfg.entry_path("team/db")?)?,
+            "age1me,age1you|s3cret"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn recipients_need_the_flag_and_names_stay_inside() -> Result<()> {
+        set_passphrase_hook(|| Ok("pw".into()));
+        let tmp = TempDir::new()?;
+        let cfg = testing::recording_vault(tmp.path());
+        let bundle = Bundle {
+            version: bundle::VERSION,
+            entries: BTreeMap::from([
+                ("team/db".into(), "s3cret".into()),
+                ("../outside".into(), "x".into()),
+                ("/tmp/abs".into(), "x".into()),
+            ]),
+            recipients: BTreeMap::from([
+                ("team".into(), "age1evil\n".into()),
+                ("../up".into(), "age1evil\n".into()),
+            ]),
+        };
+        let file = tmp.path().join("backup.age");
+        bundle::seal(
+            &bundle,
+            &Seal::Passphrase("pw".into()),
+            fs::File::create(&file)?,
+        )?;
+
+        let err = run(&cfg, &file, None, OnConflict::Skip, false, false).unwrap_err();
+        assert_eq!(err.to_string(), "3 items failed to import");
+        assert!(!cfg.prefix.join("team").join(RECIPIENTS_FILENAME).exists());
+        assert_eq!(
+            fs::read_to_string(cfg.entry_path("team/db")?)?,
+            "age1me|s3cret"
+        );
+        assert!(!tmp.path().join("outside.rage").exists());
+        assert!(!tmp.path().join("up").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn existing_entries_are_kept() -> Result<()> {
+        set_passphrase_hook(|| Ok("pw".into()));
+        let tmp = TempDir::new()?;
+        let cfg = testing::recording_vault(tmp.path());
+        let file = write_bundle(&tmp)?;
+        fs::write(cfg.entry_path("mail")?, "mine")?;
+
+        let err = run(&cfg, &file, None, OnConflict::Skip, false, false).unwrap_err();
+        assert_eq!(err.to_string(), "1 items failed to import");
+        assert_eq!(fs::read_to_string(cfg.entry_path("mail")?)?, "mine");
+
+        run(&cfg, &file, None, OnConflict::Rename, false, true)?;
+        assert!(
+            !cfg.entry_path("mail-2")?.exists(),
+            "dry run writes nothing"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+use crate::cli::{CsvFormat, OnConflict};
+use crate::commands::import::{ImportReport, entry_name, pick_name, write_entry};
+use crate::config::Config;
+use crate::entry::Entry;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Which CSV column holds each part of an entry, by lowercased header name
+struct Columns {
+    name: &'static [&'static str],
+    folder: &'static [&'static str],
+    username: &'static [&'static str],
+    password: &'static [&'static str],
+    url: &'static [&'static str],
+    notes: &'static [&'static str],
+    totp: &'static [&'static str],
+    /// Bitwarden packs custom fields into one `key: value` per line column
+    custom: &'static [&'static str],
+}
+
+fn columns(format: CsvFormat) -> Columns {
+    match format {
+        CsvFormat::Bitwarden => Columns {
+            name: &["name"],
+            folder: &["folder"],
+            username: &["login_username"],
+            password: &["login_password"],
+            url: &["login_uri"],
+            notes: &["notes"],
+            totp: &["login_totp"],
+            custom: &["fields"],
+        },
+        CsvFormat::Onepassword => Columns {
+            name: &["title"],
+            folder: &["vault", "folder"],
+            username: &["username"],
+            password: &["password"],
+            url: &["url", "website"],
+            notes: &["notes", "notesplain"],
+            totp: &["otpauth", "one-time password"],
+            custom: &[],
+        },
+        CsvFormat::Chrome => Columns {
+            name: &["name"],
+            folder: &[],
+            username: &["username"],
+            password: &["password"],
+            url: &["url"],
+            notes: &["note"],
+            totp: &[],
+            custom: &[],
+        },
+        CsvFormat::Keepassxc => Columns {
+            name: &["title"],
+            folder: &["group"],
+            username: &["username"],
+            password: &["password"],
+            url: &["url"],
+            notes: &["notes"],
+            totp: &["totp"],
+            custom: &[],
+        },
+    }
+}
+
+/// Import a password manager's CSV export.
+///
+/// Usage:
+///     passman import csv --format bitwarden|1password|chrome|keepassxc <file>
+///         [--prefix imported] [--on-conflict skip|rename] [--dry-run]
+///
+/// Each row becomes one structured entry under `prefix/<folder>/<name>`. The
+/// CSV is read straight into memory and every entry is piped to the backend,
+/// so the only files written are the encrypted outputs.
+pub fn run(
+    config: &Config,
+    format: CsvFormat,
+    file: &Path,
+    prefix: &str,
+    on_conflict: OnConflict,
+    dry_run: bool,
+) -> Result<()> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(file)
+        .with_context(|| format!("Failed to open {}", file.display()))?;
+
+    let headers: Vec<String> = reader
+        .headers()
+        .context("Failed to read CSV header")?
+        .iter()
+        .map(|h| h.trim().trim_start_matches('\u{feff}').to_lowercase())
+        .collect();
+    let cols = columns(format);
+    let find = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|n| headers.iter().position(|h| h == n))
+    };
+    let name_col = find(cols.name).with_context(|| {
+        format!(
+            "{} doesn't look like a {:?} export (no `{}` column)",
+            file.display(),
+            format,
+            cols.name[0]
+        )
+    })?;
+    let password_col = find(cols.password);
+    let (folder_col, username_col, url_col) =
+        (find(cols.folder), find(cols.username), find(cols.url));
+    let (notes_col, totp_col, custom_col) = (find(cols.notes), find(cols.totp), find(cols.custom));
+
+    let mut report = ImportReport::default();
+    let mut taken = HashSet::new();
+
+    for (i, record) in reader.records().enumerate() {
+        // +2: one for the header, one because humans count from 1
+        let row = format!("row {}", i + 2);
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => {
+                report.fail(row, e);
+                continue;
+            }
+        };
+        let get = |col: Option<usize>| col.and_then(|c| record.get(c)).unwrap_or("").trim();
+
+        let mut entry = Entry {
+            password: get(password_col).to_string(),
+            notes: get(notes_col).to_string(),
+            fields: Vec::new(),
+        };
+        entry.set_field("username", get(username_col));
+        entry.set_field("url", get(url_col));
+        entry.set_field("totp", get(totp_col));
+        for line in get(custom_col).lines() {
+            if let Some((k, v)) = line.split_once(':') {
+                entry.set_field(k, v);
+            }
+        }
+
+        let folder = match format {
+            // KeePassXC writes the full group path, starting at the root group
+            CsvFormat::Keepassxc => get(folder_col).split_once('/').map_or("", |(_, rest)| rest),
+            _ => get(folder_col),
+        };
+        let base = entry_name(&[prefix, folder], get(Some(name_col)));
+
+        let name = match pick_name(config, &base, on_conflict, &taken) {
+            Ok(Some(name)) => name,
+            Ok(None) => {
+                report.fail(row, format!("{} already exists", base));
+                continue;
+            }
+            Err(e) => {
+                report.fail(row, format!("{:#}", e));
+                continue;
+            }
+        };
+        if name != base {
+            report.notes.push(format!("{} renamed to {}", base, name));
+        }
+
+        if !dry_run && let Err(e) = write_entry(config, &name, &entry.to_string()) {
+            report.fail(row, format!("{}: {:#}", name, e));
+            continue;
+        }
+        taken.insert(name.clone());
+        report.imported.push(name);
+    }
+
+    if !dry_run && !report.imported.is_empty() {
+        println!(
+            "🧹 {} still holds every password in plaintext; delete it once you're happy.",
+            file.display()
+        );
+    }
+    report.finish(config, &format!("{:?} CSV", format), dry_run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::testing;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn import(
+        tmp: &TempDir,
+        cfg: &Config,
+        format: CsvFormat,
+        csv: &str,
+        on_conflict: OnConflict,
+    ) -> Result<()> {
+        let file = tmp.path().join("export.csv");
+        fs::write(&file, csv)?;
+        run(cfg, format, &file, "imported", on_conflict, false)
+    }
+
+    fn read(cfg: &Config, name: &str) -> Entry {
+        Entry::parse(&fs::read_to_string(cfg.entry_path(name).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn bitwarden_rows_map_onto_fields() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::plain_vault(tmp.path());
+        let csv = "folder,favorite,type,name,notes,fields,reprompt,login_uri,login_username,login_password,login_totp\n\
+                   Banking,,login,Chase,\"call 555\nask for Bob\",\"PIN: 1234\",0,https://chase.com,alice,hunter2,JBSWY3DP\n";
+
+        import(&tmp, &cfg, CsvFormat::Bitwarden, csv, OnConflict::Skip)?;
+
+        let e = read(&cfg, "imported/Banking/Chase");
+        assert_eq!(e.password, "hunter2");
+        assert_eq!(e.field("username"), Some("alice"));
+        assert_eq!(e.field("url"), Some("https://chase.com"));
+        assert_eq!(e.field("totp"), Some("JBSWY3DP"));
+        assert_eq!(e.field("pin"), Some("1234"));
+        assert_eq!(e.notes, "call 555\nask for Bob");
+        Ok(())
+    }
+
+    #[test]
+    fn keepassxc_groups_drop_the_root() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::plain_vault(tmp.path());
+        let csv = "\"Group\",\"Title\",\"Username\",\"Password\",\"URL\",\"Notes\",\"TOTP\"\n\
+                   \"Root/Work/Cloud\",\"AWS\",\"ops\",\"pw\",\"\",\"\",\"\"\n";
+
+        import(&tmp, &cfg, CsvFormat::Keepassxc, csv, OnConflict::Skip)?;
+        assert_eq!(read(&cfg, "imported/Work/Cloud/AWS").password, "pw");
+        Ok(())
+    }
+
+    #[test]
+    fn collisions_skip_or_rename() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::plain_vault(tmp.path());
+        let csv = "name,url,username,password,note\n\
+                   github.com,https://github.com,a,one,\n\
+                   github.com,https://github.com,b,two,\n";
+
+        let err = import(&tmp, &cfg, CsvFormat::Chrome, csv, OnConflict::Skip).unwrap_err();
+        assert_eq!(err.to_string(), "1 items failed to import");
+        assert_eq!(read(&cfg, "imported/github.com").password, "one");
+
+        import(&tmp, &cfg, CsvFormat::Chrome, csv, OnConflict::Rename)?;
+        assert_eq!(read(&cfg, "imported/github.com-2").password, "one");
+        assert_eq!(read(&cfg, "imported/github.com-3").password, "two");
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_format_is_rejected() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::plain_vault(tmp.path());
+        let csv = "name,url,username,password,note\nx,,,,\n";
+
+        let err = import(&tmp, &cfg, CsvFormat::Keepassxc, csv, OnConflict::Skip).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("doesn't look like a Keepassxc export"),
+            "got: {err}"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,1058 @@
+//! A reader for KeePass databases (KDBX 3.1 and 4.x).
+//!
+//! Only what an import needs: decrypt the payload, walk the group tree and
+//! hand back each entry's strings and attachments. History, the recycle bin,
+//! icons and custom data are dropped.
+
+use aes::Aes256;
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{
+    BlockDecryptMut, BlockEncrypt, KeyInit, KeyIvInit, StreamCipher, block_padding::Pkcs7,
+};
+use anyhow::{Context, Result, anyhow};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chacha20::ChaCha20;
+use flate2::read::GzDecoder;
+use hmac::{Hmac, Mac};
+use quick_xml::XmlVersion;
+use quick_xml::events::{BytesStart, Event};
+use salsa20::Salsa20;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::io::Read;
+
+const SIGNATURE: [u32; 2] = [0x9AA2_D903, 0xB54B_FB67];
+
+const CIPHER_AES256: [u8; 16] = uuid(0x31C1F2E6_BF71_4350_BE58_05216AFC5AFF);
+const CIPHER_CHACHA20: [u8; 16] = uuid(0xD6038A2B_8B6F_4CB5_A524_339A31DBB59A);
+const CIPHER_TWOFISH: [u8; 16] = uuid(0xAD68F29F_576F_4BB9_A36A_D47AF965346C);
+
+const KDF_AES: [u8; 16] = uuid(0xC9D9F39A_628A_4460_BF74_0D08C18A4FEA);
+const KDF_AES_KDBX4: [u8; 16] = uuid(0x7C02BB82_79A7_4AC0_927D_114A00648238);
+const KDF_ARGON2D: [u8; 16] = uuid(0xEF636DDF_8C29_444B_91F7_A9A403E30A0C);
+const KDF_ARGON2ID: [u8; 16] = uuid(0x9E298B19_56DB_4773_B23D_FC3EC6F0A1E6);
+
+/// Fixed nonce for the KDBX 3 Salsa20 inner stream
+const SALSA20_IV: [u8; 8] = [0xE8, 0x30, 0x09, 0x4B, 0x97, 0x20, 0x5D, 0x2A];
+
+const fn uuid(n: u128) -> [u8; 16] {
+    n.to_be_bytes()
+}
+
+#[derive(Debug, Default)]
+pub struct Group {
+    pub name: String,
+    pub groups: Vec<Group>,
+    pub entries: Vec<Record>,
+    uuid: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Record {
+    /// `Title`, `UserName`, `Password`, `URL`, `Notes` and any custom fields,
+    /// in file order
+    pub strings: Vec<(String, String)>,
+
+    /// `(file name, contents)`
+    pub attachments: Vec<(String, Vec<u8>)>,
+
+    /// `(file name, binary pool id)` until the pool has been read
+    refs: Vec<(String, usize)>,
+}
+
+impl Record {
+    pub fn get(&self, key: &str) -> &str {
+        self.strings
+            .iter()
+            .find(|(k, _)| k == key)
+            .map_or("", |(_, v)| v.as_str())
+    }
+}
+
+/// Decrypt a database and return its root group.
+///
+/// `key_file` is the raw contents of the key file, if the database uses one.
+pub fn open(data: &[u8], password: &str, key_file: Option<&[u8]>) -> Result<Group> {
+    let mut r = Cursor { buf: data, pos: 0 };
+    if [r.u32()?, r.u32()?] != SIGNATURE {
+        anyhow::bail!("Not a KeePass 2 database");
+    }
+    let major = (r.u32()? >> 16) as u16;
+    if major != 3 && major != 4 {
+        anyhow::bail!("Unsupported KDBX version {} (expected 3.1 or 4.x)", major);
+    }
+
+    let header = Header::read(&mut r, major)?;
+    let header_bytes = &data[..r.pos];
+    let composite = composite_key(password, key_file)?;
+    let transformed = header.kdf.transform(&composite)?;
+    let master_key = sha256(&[&header.master_seed, &transformed]);
+
+    let (xml, binaries, stream) = if major == 3 {
+        let plain = decrypt(&header, &master_key, r.rest())?;
+        let start = header.stream_start.as_slice();
+        if plain.len() < start.len() || &plain[..start.len()] != start {
+            anyhow::bail!("Wrong master password or key file");
+        }
+        let payload = unhash_blocks(&plain[start.len()..])?;
+        let payload = decompress(&header, payload)?;
+        let stream = InnerStream::new(header.inner_stream_id, &header.inner_stream_key)?;
+        (payload, Vec::new(), stream)
+    } else {
+        let stored_hash = r.take(32)?;
+        if sha256(&[header_bytes]).as_slice() != stored_hash {
+            anyhow::bail!("KDBX header is corrupted");
+        }
+        let hmac_base = Sha512::new()
+            .chain_update(&header.master_seed)
+            .chain_update(transformed)
+            .chain_update([1])
+            .finalize();
+        let stored_hmac = r.take(32)?;
+        block_hmac(&hmac_base, u64::MAX)
+            .chain_update(header_bytes)
+            .verify_slice(stored_hmac)
+            .map_err(|_| anyhow!("Wrong master password or key file"))?;
+
+        let ciphertext = unhmac_blocks(&mut r, &hmac_base)?;
+        let payload = decompress(&header, decrypt(&header, &master_key, &ciphertext)?)?;
+        let mut inner = Cursor {
+            buf: &payload,
+            pos: 0,
+        };
+        let (stream, binaries) = read_inner_header(&mut inner)?;
+        (inner.rest().to_vec(), binaries, stream)
+    };
+
+    let xml = String::from_utf8(xml).context("Database XML is not valid UTF-8")?;
+    parse_xml(&xml, stream, binaries)
+}
+
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|end| *end <= self.buf.len());
+        let end = end.context("KDBX file is truncated")?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+    fn rest(&self) -> &'a [u8] {
+        &self.buf[self.pos..]
+    }
+}
+
+enum Kdf {
+    Aes {
+        seed: Vec<u8>,
+        rounds: u64,
+    },
+    Argon2 {
+        algorithm: argon2::Algorithm,
+        salt: Vec<u8>,
+        iterations: u32,
+        memory_kib: u32,
+        parallelism: u32,
+        version: u32,
+    },
+}
+
+impl Kdf {
+    fn transform(&self, composite: &[u8; 32]) -> Result<[u8; 32]> {
+        match self {
+            Kdf::Aes { seed, rounds } => {
+                let cipher = Aes256::new_from_slice(seed)
+                    .map_err(|_| anyhow!("AES-KDF seed must be 32 bytes"))?;
+                let mut key = *composite;
+                for _ in 0..*rounds {
+                    for block in key.chunks_exact_mut(16) {
+                        cipher.encrypt_block(GenericArray::from_mut_slice(block));
+                    }
+                }
+                Ok(sha256(&[&key]))
+            }
+            Kdf::Argon2 {
+                algorithm,
+                salt,
+                iterations,
+                memory_kib,
+                parallelism,
+                version,
+            } => {
+                let version = argon2::Version::try_from(*version)
+                    .map_err(|e| anyhow!("Unsupported Argon2 version: {}", e))?;
+                let params = argon2::Params::new(*memory_kib, *iterations, *parallelism, Some(32))
+                    .map_err(|e| anyhow!("Bad Argon2 parameters: {}", e))?;
+                let mut out = [0u8; 32];
+                argon2::Argon2::new(*algorithm, version, params)
+                    .hash_password_into(composite, salt, &mut out)
+                    .map_err(|e| anyhow!("Argon2 failed: {}", e))?;
+                Ok(out)
+            }
+        }
+    }
+}
+
+struct Header {
+    cipher: Vec<u8>,
+    compressed: bool,
+    master_seed: Vec<u8>,
+    iv: Vec<u8>,
+    kdf: Kdf,
+    // KDBX 3 keeps these in the outer header; KDBX 4 moved them inside
+    stream_start: Vec<u8>,
+    inner_stream_id: u32,
+    inner_stream_key: Vec<u8>,
+}
+
+impl Header {
+    fn read(r: &mut Cursor, major: u16) -> Result<Self> {
+        let mut fields = HashMap::new();
+        loop {
+            let id = r.u8()?;
+            let len = if major == 3 {
+                r.u16()? as usize
+            } else {
+                r.u32()? as usize
+            };
+            let data = r.take(len)?;
+            if id == 0 {
+                break;
+            }
+            fields.insert(id, data);
+        }
+        let field = |id: u8, what: &str| {
+            fields
+                .get(&id)
+                .map(|d| d.to_vec())
+                .with_context(|| format!("KDBX header has no {}", what))
+        };
+
+        let kdf = if major == 3 {
+            let rounds = field(6, "transform rounds")?;
+            Kdf::Aes {
+                seed: field(5, "transform seed")?,
+                rounds: u64::from_le_bytes(rounds.as_slice().try_into()?),
+            }
+        } else {
+            read_kdf(&variant_dictionary(&field(11, "KDF parameters")?)?)?
+        };
+        let inner_stream_id = match fields.get(&10) {
+            Some(d) => u32::from_le_bytes((*d).try_into()?),
+            None => 0,
+        };
+        let compressed = match fields.get(&3) {
+            Some(d) => u32::from_le_bytes((*d).try_into()?) == 1,
+            None => false,
+        };
+
+        Ok(Header {
+            cipher: field(2, "cipher")?,
+            compressed,
+            master_seed: field(4, "master seed")?,
+            iv: field(7, "encryption IV")?,
+            kdf,
+            stream_start: fields.get(&9).map(|d| d.to_vec()).unwrap_or_default(),
+            inner_stream_id,
+            inner_stream_key: fields.get(&8).map(|d| d.to_vec()).unwrap_or_default(),
+        })
+    }
+}
+
+/// KDBX 4's typed key/value map; values are kept as raw bytes
+fn variant_dictionary(data: &[u8]) -> Result<HashMap<String, Vec<u8>>> {
+    let mut r = Cursor { buf: data, pos: 0 };
+    if r.u16()? >> 8 != 1 {
+        anyhow::bail!("Unsupported KDF parameter format");
+    }
+    let mut map = HashMap::new();
+    loop {
+        if r.u8()? == 0 {
+            return Ok(map);
+        }
+        let key_len = r.u32()? as usize;
+        let key = String::from_utf8_lossy(r.take(key_len)?).to_string();
+        let value_len = r.u32()? as usize;
+        map.insert(key, r.take(value_len)?.to_vec());
+    }
+}
+
+fn read_kdf(params: &HashMap<String, Vec<u8>>) -> Result<Kdf> {
+    let bytes = |key: &str| {
+        params
+            .get(key)
+            .cloned()
+            .with_context(|| format!("KDF parameters have no `{}`", key))
+    };
+    let int = |key: &str| -> Result<u64> {
+        let raw = bytes(key)?;
+        Ok(match raw.len() {
+            4 => u32::from_le_bytes(raw.as_slice().try_into()?) as u64,
+            8 => u64::from_le_bytes(raw.as_slice().try_into()?),
+            _ => anyhow::bail!("KDF parameter `{}` is not an integer", key),
+        })
+    };
+
+    let id = bytes("$UUID")?;
+    let argon = |algorithm| -> Result<Kdf> {
+        Ok(Kdf::Argon2 {
+            algorithm,
+            salt: bytes("S")?,
+            iterations: int("I")?.try_into()?,
+            memory_kib: (int("M")? / 1024).try_into()?,
+            parallelism: int("P")?.try_into()?,
+            version: int("V")?.try_into()?,
+        })
+    };
+    match <[u8; 16]>::try_from(id.as_slice()) {
+        Ok(KDF_AES) | Ok(KDF_AES_KDBX4) => Ok(Kdf::Aes {
+            seed: bytes("S")?,
+            rounds: int("R")?,
+        }),
+        Ok(KDF_ARGON2D) => argon(argon2::Algorithm::Argon2d),
+        Ok(KDF_ARGON2ID) => argon(argon2::Algorithm::Argon2id),
+        _ => anyhow::bail!("Unsupported key derivation function"),
+    }
+}
+
+/// SHA-256 over the password hash and the key file's key
+fn composite_key(password: &str, key_file: Option<&[u8]>) -> Result<[u8; 32]> {
+    let mut parts = vec![sha256(&[password.as_bytes()])];
+    if let Some(raw) = key_file {
+        parts.push(key_file_key(raw)?);
+    }
+    let parts: Vec<&[u8]> = parts.iter().map(|p| p.as_slice()).collect();
+    Ok(sha256(&parts))
+}
+
+/// KeePass accepts XML key files (v1 base64, v2 hex), 32 raw bytes, 64 hex
+/// characters, or any other file, which is hashed
+fn key_file_key(raw: &[u8]) -> Result<[u8; 32]> {
+    if let Ok(text) = std::str::from_utf8(raw) {
+        if text.contains("<KeyFile") {
+            let data = text
+                .split_once("<Data")
+                .and_then(|(_, rest)| rest.split_once('>'))
+                .and_then(|(_, rest)| rest.split_once("</Data>"))
+                .map(|(data, _)| data.split_whitespace().collect::<String>())
+                .context("Key file has no <Data> element")?;
+            let key = if text.contains("<Version>2.0") {
+                hex(&data)
+            } else {
+                BASE64.decode(&data).ok()
+            };
+            return key
+                .and_then(|k| k.try_into().ok())
+                .context("Key file <Data> is not a 32-byte key");
+        }
+        if let Some(key) = hex(text.trim()).and_then(|k| k.try_into().ok()) {
+            return Ok(key);
+        }
+    }
+    match raw.try_into() {
+        Ok(key) => Ok(key),
+        Err(_) => Ok(sha256(&[raw])),
+    }
+}
+
+fn hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
+fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut h = Sha256::new();
+    for p in parts {
+        h.update(p);
+    }
+    h.finalize().into()
+}
+
+fn decrypt(header: &Header, key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>> {
+    match <[u8; 16]>::try_from(header.cipher.as_slice()) {
+        Ok(CIPHER_AES256) => cbc::Decryptor::<Aes256>::new_from_slices(key, &header.iv)
+            .map_err(|_| anyhow!("Bad AES IV length"))?
+            .decrypt_padded_vec_mut::<Pkcs7>(data)
+            .map_err(|_| anyhow!("Wrong master password or key file")),
+        Ok(CIPHER_CHACHA20) => {
+            let mut out = data.to_vec();
+            ChaCha20::new_from_slices(key, &header.iv)
+                .map_err(|_| anyhow!("Bad ChaCha20 IV length"))?
+                .apply_keystream(&mut out);
+            Ok(out)
+        }
+        Ok(CIPHER_TWOFISH) => {
+            anyhow::bail!(
+                "Twofish databases aren't supported; switch the cipher to AES or ChaCha20 in KeePass first"
+            )
+        }
+        _ => anyhow::bail!("Unknown database cipher"),
+    }
+}
+
+fn decompress(header: &Header, data: Vec<u8>) -> Result<Vec<u8>> {
+    if !header.compressed {
+        return Ok(data);
+    }
+    let mut out = Vec::new();
+    GzDecoder::new(data.as_slice())
+        .read_to_end(&mut out)
+        .context("Failed to decompress database")?;
+    Ok(out)
+}
+
+/// KDBX 3 payload: `index, sha256, size, data` blocks ending with an empty one
+fn unhash_blocks(data: &[u8]) -> Result<Vec<u8>> {
+    let mut r = Cursor { buf: data, pos: 0 };
+    let mut out = Vec::new();
+    loop {
+        r.u32()?;
+        let hash = r.take(32)?;
+        let size = r.u32()? as usize;
+        if size == 0 {
+            return Ok(out);
+        }
+        let block = r.take(size)?;
+        if sha256(&[block]).as_slice() != hash {
+            anyhow::bail!("Database block is corrupted");
+        }
+        out.extend_from_slice(block);
+    }
+}
+
+fn block_hmac(base: &[u8], index: u64) -> Hmac<Sha256> {
+    let key = Sha512::new()
+        .chain_update(index.to_le_bytes())
+        .chain_update(base)
+        .finalize();
+    <Hmac<Sha256> as Mac>::new_from_slice(&key).expect("HMAC accepts any key length")
+}
+
+/// KDBX 4 payload: `hmac, size, data` blocks ending with an empty one
+fn unhmac_blocks(r: &mut Cursor, hmac_base: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for index in 0u64.. {
+        let tag = r.take(32)?;
+        let size = r.u32()?;
+        let block = r.take(size as usize)?;
+        block_hmac(hmac_base, index)
+            .chain_update(index.to_le_bytes())
+            .chain_update(size.to_le_bytes())
+            .chain_update(block)
+            .verify_slice(tag)
+            .map_err(|_| anyhow!("Database block {} is corrupted", index))?;
+        if size == 0 {
+            break;
+        }
+        out.extend_from_slice(block);
+    }
+    Ok(out)
+}
+
+fn read_inner_header(r: &mut Cursor) -> Result<(InnerStream, Vec<Vec<u8>>)> {
+    let (mut id, mut key, mut binaries) = (0, Vec::new(), Vec::new());
+    loop {
+        let field = r.u8()?;
+        let len = r.u32()? as usize;
+        let data = r.take(len)?;
+        match field {
+            0 => break,
+            1 => id = u32::from_le_bytes(data.try_into()?),
+            2 => key = data.to_vec(),
+            // first byte is a flags field ("protect in memory")
+            3 => binaries.push(data.get(1..).unwrap_or_default().to_vec()),
+            _ => {}
+        }
+    }
+    Ok((InnerStream::new(id, &key)?, binaries))
+}
+
+/// Keystream that unmasks `Protected="True"` values, consumed in document order
+enum InnerStream {
+    None,
+    Salsa20(Salsa20),
+    ChaCha20(ChaCha20),
+}
+
+impl InnerStream {
+    fn new(id: u32, key: &[u8]) -> Result<Self> {
+        Ok(match id {
+            0 => InnerStream::None,
+            2 => InnerStream::Salsa20(
+                Salsa20::new_from_slices(&sha256(&[key]), &SALSA20_IV)
+                    .map_err(|_| anyhow!("Bad Salsa20 key"))?,
+            ),
+            3 => {
+                let h = Sha512::digest(key);
+                InnerStream::ChaCha20(
+                    ChaCha20::new_from_slices(&h[..32], &h[32..44])
+                        .map_err(|_| anyhow!("Bad ChaCha20 key"))?,
+                )
+            }
+            other => anyhow::bail!("Unsupported inner stream cipher {}", other),
+        })
+    }
+
+    fn apply(&mut self, data: &mut [u8]) {
+        match self {
+            InnerStream::None => {}
+            InnerStream::Salsa20(c) => c.apply_keystream(data),
+            InnerStream::ChaCha20(c) => c.apply_keystream(data),
+        }
+    }
+
+    fn unprotect(&mut self, value: &str) -> Result<Vec<u8>> {
+        let mut data = BASE64
+            .decode(value.split_whitespace().collect::<String>())
+            .context("Protected value is not base64")?;
+        self.apply(&mut data);
+        Ok(data)
+    }
+}
+
+/// Attributes of the element whose text is being collected
+#[derive(Default)]
+struct Attrs {
+    protected: bool,
+    compressed: bool,
+    id: Option<usize>,
+    reference: Option<usize>,
+}
+
+impl Attrs {
+    fn read(e: &BytesStart) -> Result<Self> {
+        let get = |name: &str| -> Result<Option<String>> {
+            Ok(match e.try_get_attribute(name)? {
+                Some(a) => Some(a.normalized_value(XmlVersion::Implicit1_0)?.to_string()),
+                None => None,
+            })
+        };
+        Ok(Attrs {
+            protected: get("Protected")?.is_some_and(|v| v.eq_ignore_ascii_case("true")),
+            compressed: get("Compressed")?.is_some_and(|v| v.eq_ignore_ascii_case("true")),
+            id: get("ID")?.and_then(|v| v.parse().ok()),
+            reference: get("Ref")?.and_then(|v| v.parse().ok()),
+        })
+    }
+}
+
+fn parse_xml(xml: &str, mut stream: InnerStream, pool: Vec<Vec<u8>>) -> Result<Group> {
+    let mut pool: HashMap<usize, Vec<u8>> = pool.into_iter().enumerate().collect();
+    let mut reader = quick_xml::Reader::from_str(xml);
+
+    let mut path: Vec<String> = Vec::new();
+    let mut attrs = Attrs::default();
+    let mut text = String::new();
+    let mut groups: Vec<Group> = Vec::new();
+    let mut entries: Vec<Record> = Vec::new();
+    let (mut key, mut value) = (String::new(), String::new());
+    let mut reference = None;
+    let mut recycle_bin = String::new();
+    let mut root = None;
+
+    loop {
+        let event = reader.read_event().context("Database XML is malformed")?;
+        let (start, end) = match &event {
+            Event::Start(e) => (Some(e), None),
+            Event::Empty(e) => (Some(e), Some(e.name().as_ref().to_string())),
+            Event::End(e) => (None, Some(e.name().as_ref().to_string())),
+            Event::Text(t) => {
+                text.push_str(&t.xml10_content());
+                (None, None)
+            }
+            Event::CData(t) => {
+                text.push_str(t);
+                (None, None)
+            }
+            Event::GeneralRef(r) => {
+                let c = match r.resolve_char_ref()? {
+                    Some(c) => c,
+                    None => match r.as_ref() {
+                        "lt" => '<',
+                        "gt" => '>',
+                        "amp" => '&',
+                        "quot" => '"',
+                        "apos" => '\'',
+                        other => anyhow::bail!("Unknown XML entity &{};", other),
+                    },
+                };
+                text.push(c);
+                (None, None)
+            }
+            Event::Eof => break,
+            _ => (None, None),
+        };
+
+        if let Some(e) = start {
+            let name = e.name().as_ref().to_string();
+            attrs = Attrs::read(e)?;
+            text.clear();
+            match name.as_str() {
+                "Group" => groups.push(Group::default()),
+                "Entry" => entries.push(Record::default()),
+                _ => {}
+            }
+            path.push(name);
+        }
+
+        let Some(name) = end else { continue };
+        path.pop();
+        let parent = path.last().map(String::as_str).unwrap_or("");
+        match (name.as_str(), parent) {
+            ("Key", "String" | "Binary") => key = std::mem::take(&mut text),
+            ("Value", "String") => {
+                value = if attrs.protected {
+                    String::from_utf8_lossy(&stream.unprotect(&text)?).to_string()
+                } else {
+                    std::mem::take(&mut text)
+                };
+            }
+            ("Value", "Binary") => reference = attrs.reference,
+            ("String", "Entry") => {
+                if let Some(entry) = entries.last_mut() {
+                    entry
+                        .strings
+                        .push((std::mem::take(&mut key), std::mem::take(&mut value)));
+                }
+            }
+            ("Binary", "Entry") => {
+                if let (Some(entry), Some(r)) = (entries.last_mut(), reference.take()) {
+                    entry.refs.push((std::mem::take(&mut key), r));
+                }
+            }
+            // KDBX 3 keeps attachments in Meta/Binaries
+            ("Binary", "Binaries") => {
+                let mut data = if attrs.protected {
+                    stream.unprotect(&text)?
+                } else {
+                    BASE64
+                        .decode(text.split_whitespace().collect::<String>())
+                        .context("Attachment is not base64")?
+                };
+                if attrs.compressed {
+                    let mut out = Vec::new();
+                    GzDecoder::new(data.as_slice())
+                        .read_to_end(&mut out)
+                        .context("Failed to decompress attachment")?;
+                    data = out;
+                }
+                if let Some(id) = attrs.id {
+                    pool.insert(id, data);
+                }
+            }
+            ("RecycleBinUUID", "Meta") => recycle_bin = text.trim().to_string(),
+            ("Name", "Group") => {
+                if let Some(g) = groups.last_mut() {
+                    g.name = text.trim().to_string();
+                }
+            }
+            ("UUID", "Group") => {
+                if let Some(g) = groups.last_mut() {
+                    g.uuid = text.trim().to_string();
+                }
+            }
+            ("Entry", _) => {
+                let entry = entries.pop().unwrap_or_default();
+                // entries still open means this one was inside <History>
+                if entries.is_empty()
+                    && let Some(g) = groups.last_mut()
+                {
+                    g.entries.push(entry);
+                }
+            }
+            ("Group", _) => {
+                let group = groups.pop().unwrap_or_default();
+                let in_bin = !recycle_bin.is_empty() && group.uuid == recycle_bin;
+                match groups.last_mut() {
+                    Some(parent) if !in_bin => parent.groups.push(group),
+                    Some(_) => {}
+                    None => root = Some(group),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut root = root.context("Database has no root group")?;
+    resolve_attachments(&mut root, &pool)?;
+    Ok(root)
+}
+
+fn resolve_attachments(group: &mut Group, pool: &HashMap<usize, Vec<u8>>) -> Result<()> {
+    for entry in &mut group.entries {
+        for (name, id) in std::mem::take(&mut entry.refs) {
+            let data = pool
+                .get(&id)
+                .with_context(|| format!("Attachment {} refers to missing binary {}", name, id))?;
+            entry.attachments.push((name, data.clone()));
+        }
+    }
+    for child in &mut group.groups {
+        resolve_attachments(child, pool)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    //! A minimal KDBX writer, so tests can build databases without KeePass
+
+    use super::*;
+    use aes::cipher::BlockEncryptMut;
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>
+<KeePassFile>
+  <Meta>
+    <RecycleBinEnabled>True</RecycleBinEnabled>
+    <RecycleBinUUID>YmluYmluYmluYmluYmluYg==</RecycleBinUUID>
+    {binaries}
+  </Meta>
+  <Root>
+    <Group>
+      <UUID>cm9vdHJvb3Ryb290cm9vdA==</UUID>
+      <Name>Passwords</Name>
+      <Entry>
+        <String><Key>Title</Key><Value>Mail &amp; Calendar</Value></String>
+        <String><Key>UserName</Key><Value>alice</Value></String>
+        <String><Key>Password</Key><Value Protected="True">{hunter2}</Value></String>
+        <String><Key>PIN</Key><Value Protected="True">{4321}</Value></String>
+        <Binary><Key>note.txt</Key><Value Ref="0"/></Binary>
+        <History>
+          <Entry>
+            <String><Key>Title</Key><Value>Mail</Value></String>
+            <String><Key>Password</Key><Value Protected="True">{old}</Value></String>
+          </Entry>
+        </History>
+      </Entry>
+      <Group>
+        <UUID>d29ya3dvcmt3b3Jrd29yaw==</UUID>
+        <Name>Work</Name>
+        <Entry>
+          <String><Key>Title</Key><Value>VPN</Value></String>
+          <String><Key>Password</Key><Value Protected="True">{s3cret}</Value></String>
+          <String><Key>Notes</Key><Value>line one
+line two</Value></String>
+        </Entry>
+      </Group>
+      <Group>
+        <UUID>YmluYmluYmluYmluYmluYg==</UUID>
+        <Name>Recycle Bin</Name>
+        <Entry>
+          <String><Key>Title</Key><Value>Deleted</Value></String>
+        </Entry>
+      </Group>
+    </Group>
+  </Root>
+</KeePassFile>"#;
+
+    pub struct Options<'a> {
+        pub major: u16,
+        pub password: &'a str,
+        pub key_file: Option<&'a [u8]>,
+        pub cipher: [u8; 16],
+        pub argon2: bool,
+    }
+
+    impl Default for Options<'_> {
+        fn default() -> Self {
+            Options {
+                major: 4,
+                password: "master",
+                key_file: None,
+                cipher: CIPHER_AES256,
+                argon2: false,
+            }
+        }
+    }
+
+    /// The sample database above, with `{...}` values protected in order and
+    /// `note.txt` as its only attachment
+    pub fn sample(opts: &Options) -> Vec<u8> {
+        let attachment = b"hello attachment";
+        let stream_key = [7u8; 64];
+        let mut stream =
+            InnerStream::new(if opts.major == 3 { 2 } else { 3 }, &stream_key).unwrap();
+
+        let binaries = if opts.major == 3 {
+            format!(
+                r#"<Binaries><Binary ID="0" Compressed="False">{}</Binary></Binaries>"#,
+                BASE64.encode(attachment)
+            )
+        } else {
+            String::new()
+        };
+        let mut xml = XML.replace("{binaries}", &binaries);
+        while let Some(open) = xml.find('{') {
+            let close = open + xml[open..].find('}').unwrap();
+            let mut secret = xml.as_bytes()[open + 1..close].to_vec();
+            stream.apply(&mut secret);
+            xml.replace_range(open..=close, &BASE64.encode(secret));
+        }
+
+        let master_seed = [1u8; 32];
+        let iv: Vec<u8> = if opts.cipher == CIPHER_CHACHA20 {
+            vec![2; 12]
+        } else {
+            vec![2; 16]
+        };
+        let kdf = if opts.argon2 {
+            Kdf::Argon2 {
+                algorithm: argon2::Algorithm::Argon2d,
+                salt: vec![3; 32],
+                iterations: 1,
+                memory_kib: 64,
+                parallelism: 1,
+                version: 0x13,
+            }
+        } else {
+            Kdf::Aes {
+                seed: vec![3; 32],
+                rounds: 10,
+            }
+        };
+        let composite = composite_key(opts.password, opts.key_file).unwrap();
+        let transformed = kdf.transform(&composite).unwrap();
+        let master_key = sha256(&[&master_seed, &transformed]);
+
+        let mut header = Vec::new();
+        for s in SIGNATURE {
+            header.extend(s.to_le_bytes());
+        }
+        header.extend(((opts.major as u32) << 16 | 1).to_le_bytes());
+        let mut field = |id: u8, data: &[u8]| {
+            header.push(id);
+            if opts.major == 3 {
+                header.extend((data.len() as u16).to_le_bytes());
+            } else {
+                header.extend((data.len() as u32).to_le_bytes());
+            }
+            header.extend(data);
+        };
+        field(2, &opts.cipher);
+        field(3, &1u32.to_le_bytes());
+        field(4, &master_seed);
+        field(7, &iv);
+        let stream_start = [9u8; 32];
+        match (&kdf, opts.major) {
+            (Kdf::Aes { seed, rounds }, 3) => {
+                field(5, seed);
+                field(6, &rounds.to_le_bytes());
+                field(8, &stream_key);
+                field(9, &stream_start);
+                field(10, &2u32.to_le_bytes());
+            }
+            _ => field(11, &kdf_parameters(&kdf)),
+        }
+        field(0, b"\r\n\r\n");
+
+        let gzip = |data: &[u8]| {
+            let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+            gz.write_all(data).unwrap();
+            gz.finish().unwrap()
+        };
+        let encrypt = |data: &[u8]| -> Vec<u8> {
+            if opts.cipher == CIPHER_CHACHA20 {
+                let mut out = data.to_vec();
+                ChaCha20::new_from_slices(&master_key, &iv)
+                    .unwrap()
+                    .apply_keystream(&mut out);
+                out
+            } else {
+                cbc::Encryptor::<Aes256>::new_from_slices(&master_key, &iv)
+                    .unwrap()
+                    .encrypt_padded_vec_mut::<Pkcs7>(data)
+            }
+        };
+
+        let mut out = header.clone();
+        if opts.major == 3 {
+            let body = gzip(xml.as_bytes());
+            let mut plain = stream_start.to_vec();
+            plain.extend(0u32.to_le_bytes());
+            plain.extend(sha256(&[&body]));
+            plain.extend((body.len() as u32).to_le_bytes());
+            plain.extend(&body);
+            plain.extend(1u32.to_le_bytes());
+            plain.extend([0u8; 32]);
+            plain.extend(0u32.to_le_bytes());
+            out.extend(encrypt(&plain));
+        } else {
+            let mut inner = Vec::new();
+            for (id, data) in [
+                (1u8, 3u32.to_le_bytes().to_vec()),
+                (2, stream_key.to_vec()),
+                (3, [&[1u8][..], attachment].concat()),
+                (0, Vec::new()),
+            ] {
+                inner.push(id);
+                inner.extend((data.len() as u32).to_le_bytes());
+                inner.extend(data);
+            }
+            inner.extend(xml.as_bytes());
+            let ciphertext = encrypt(&gzip(&inner));
+
+            let hmac_base = Sha512::new()
+                .chain_update(master_seed)
+                .chain_update(transformed)
+                .chain_update([1])
+                .finalize();
+            out.extend(sha256(&[&header]));
+            out.extend(
+                block_hmac(&hmac_base, u64::MAX)
+                    .chain_update(&header)
+                    .finalize()
+                    .into_bytes(),
+            );
+            for (index, block) in [ciphertext.as_slice(), &[]].into_iter().enumerate() {
+                let index = index as u64;
+                let size = block.len() as u32;
+                let tag = block_hmac(&hmac_base, index)
+                    .chain_update(index.to_le_bytes())
+                    .chain_update(size.to_le_bytes())
+                    .chain_update(block)
+                    .finalize()
+                    .into_bytes();
+                out.extend(tag);
+                out.extend(size.to_le_bytes());
+                out.extend(block);
+            }
+        }
+        out
+    }
+
+    fn kdf_parameters(kdf: &Kdf) -> Vec<u8> {
+        let mut out = vec![0x00, 0x01];
+        let mut item = |kind: u8, key: &str, value: &[u8]| {
+            out.push(kind);
+            out.extend((key.len() as u32).to_le_bytes());
+            out.extend(key.as_bytes());
+            out.extend((value.len() as u32).to_le_bytes());
+            out.extend(value);
+        };
+        match kdf {
+            Kdf::Aes { seed, rounds } => {
+                item(0x42, "$UUID", &KDF_AES);
+                item(0x05, "R", &rounds.to_le_bytes());
+                item(0x42, "S", seed);
+            }
+            Kdf::Argon2 {
+                salt,
+                iterations,
+                memory_kib,
+                parallelism,
+                version,
+                ..
+            } => {
+                item(0x42, "$UUID", &KDF_ARGON2D);
+                item(0x42, "S", salt);
+                item(0x05, "I", &(*iterations as u64).to_le_bytes());
+                item(0x05, "M", &(*memory_kib as u64 * 1024).to_le_bytes());
+                item(0x04, "P", &parallelism.to_le_bytes());
+                item(0x04, "V", &version.to_le_bytes());
+            }
+        }
+        out.push(0);
+        out
+    }
+
+    fn check_sample(root: &Group) {
+        assert_eq!(root.name, "Passwords");
+        assert_eq!(root.entries.len(), 1, "history entries are dropped");
+        let mail = &root.entries[0];
+        assert_eq!(mail.get("Title"), "Mail & Calendar");
+        assert_eq!(mail.get("Password"), "hunter2");
+        assert_eq!(mail.get("PIN"), "4321");
+        assert_eq!(
+            mail.attachments,
+            vec![("note.txt".to_string(), b"hello attachment".to_vec())]
+        );
+
+        let names: Vec<_> = root.groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, vec!["Work"], "recycle bin is dropped");
+        let vpn = &root.groups[0].entries[0];
+        assert_eq!(vpn.get("Password"), "s3cret");
+        assert_eq!(vpn.get("Notes"), "line one\nline two");
+    }
+
+    #[test]
+    fn reads_kdbx4_aes() -> Result<()> {
+        let db = sample(&Options::default());
+        check_sample(&open(&db, "master", None)?);
+        Ok(())
+    }
+
+    #[test]
+    fn reads_kdbx4_chacha20_argon2() -> Result<()> {
+        let db = sample(&Options {
+            cipher: CIPHER_CHACHA20,
+            argon2: true,
+            ..Default::default()
+        });
+        check_sample(&open(&db, "master", None)?);
+        Ok(())
+    }
+
+    #[test]
+    fn reads_kdbx3() -> Result<()> {
+        let db = sample(&Options {
+            major: 3,
+            ..Default::default()
+        });
+        check_sample(&open(&db, "master", None)?);
+        Ok(())
+    }
+
+    #[test]
+    fn key_file_is_required_when_used() -> Result<()> {
+        let key_file = b"<?xml version=\"1.0\"?>\n<KeyFile><Meta><Version>2.0</Version></Meta>\
+            <Key><Data Hash=\"0\">0102030405060708 090A0B0C0D0E0F10\n\
+            1112131415161718 191A1B1C1D1E1F20</Data></Key></KeyFile>";
+        let db = sample(&Options {
+            key_file: Some(key_file),
+            ..Default::default()
+        });
+
+        check_sample(&open(&db, "master", Some(key_file))?);
+        let err = open(&db, "master", None).unwrap_err();
+        assert_eq!(err.to_string(), "Wrong master password or key file");
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_password_is_reported() {
+        for major in [3, 4] {
+            let db = sample(&Options {
+                major,
+                ..Default::default()
+            });
+            let err = open(&db, "nope", None).unwrap_err();
+            assert_eq!(err.to_string(), "Wrong master password or key file");
+        }
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let err = open(b"PK\x03\x04 not a database", "", None).unwrap_err();
+        assert_eq!(err.to_string(), "Not a KeePass 2 database");
+    }
+}
@@ -0,0 +1,323 @@
+use crate::cli::OnConflict;
+use crate::commands::import::kdbx::{self, Group, Record};
+use crate::commands::import::{ImportReport, entry_name, pick_name, write_entry};
+use crate::config::Config;
+use crate::entry::Entry;
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use dialoguer::Password;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+type PasswordFn = fn(&str) -> Result<String>;
+
+thread_local! {
+    static PASSWORD_HOOK: RefCell<PasswordFn> = RefCell::new(prompt_password);
+}
+
+/// Test-only hook to answer the master password prompt
+#[cfg(test)]
+pub fn set_password_hook(f: PasswordFn) {
+    PASSWORD_HOOK.with(|h| *h.borrow_mut() = f);
+}
+
+fn prompt_password(prompt: &str) -> Result<String> {
+    Password::new()
+        .with_prompt(prompt)
+        .allow_empty_password(true)
+        .interact()
+        .context("Failed to read master password")
+}
+
+/// Import a KeePass database (KDBX 3.1 or 4.x).
+///
+/// Usage:
+///     passman import keepass <file.kdbx> [--key-file FILE] [--prefix imported]
+///         [--on-conflict skip|rename] [--dry-run]
+///
+/// Groups become folders below the prefix (the root group itself is dropped,
+/// as is the recycle bin). Custom string fields become `key: value` fields
+/// and each attachment is stored as its own entry under `<entry>/`; binary
+/// attachments are base64-encoded and get a `.b64` suffix.
+pub fn run(
+    config: &Config,
+    file: &Path,
+    key_file: Option<PathBuf>,
+    prefix: &str,
+    on_conflict: OnConflict,
+    dry_run: bool,
+) -> Result<()> {
+    let data = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let key = match &key_file {
+        Some(path) => Some(
+            fs::read(path)
+                .with_context(|| format!("Failed to read key file {}", path.display()))?,
+        ),
+        None => None,
+    };
+    let password =
+        PASSWORD_HOOK.with(|h| (h.borrow())(&format!("Master password for {}", file.display())))?;
+    let root = kdbx::open(&data, &password, key.as_deref())?;
+
+    let mut import = Import {
+        config,
+        on_conflict,
+        dry_run,
+        report: ImportReport::default(),
+        taken: HashSet::new(),
+    };
+    import.group(&root, &mut vec![prefix.to_string()]);
+    import.report.finish(config, "KeePass", dry_run)
+}
+
+struct Import<'a> {
+    config: &'a Config,
+    on_conflict: OnConflict,
+    dry_run: bool,
+    report: ImportReport,
+    taken: HashSet<String>,
+}
+
+impl Import<'_> {
+    fn group(&mut self, group: &Group, folders: &mut Vec<String>) {
+        for record in &group.entries {
+            let folders: Vec<&str> = folders.iter().map(String::as_str).collect();
+            self.record(record, &folders);
+        }
+        for child in &group.groups {
+            folders.push(child.name.clone());
+            self.group(child, folders);
+            folders.pop();
+        }
+    }
+
+    fn record(&mut self, record: &Record, folders: &[&str]) {
+        let base = entry_name(folders, record.get("Title"));
+        let name = match self.claim(&base) {
+            Ok(Some(name)) => name,
+            Ok(None) => {
+                self.report
+                    .fail(base.clone(), format!("{} already exists", base));
+                return;
+            }
+            Err(e) => {
+                self.report.fail(base, format!("{:#}", e));
+                return;
+            }
+        };
+
+        let mut entry = to_entry(record);
+        if record.get("Password").trim_end().contains('\n') {
+            self.report.notes.push(format!(
+                "{} has a multi-line password; lines after the first are in its notes",
+                name
+            ));
+        }
+        for (key, _) in multi_line_fields(record) {
+            self.report.notes.push(format!(
+                "{}'s `{}` field has several lines; it is in the notes",
+                name, key
+            ));
+        }
+        let mut files = Vec::new();
+        for (file, data) in &record.attachments {
+            let (suffix, contents) = match std::str::from_utf8(data) {
+                Ok(text) => ("", text.to_string()),
+                Err(_) => (".b64", BASE64.encode(data)),
+            };
+            let file_name = entry_name(&[], &format!("{}{}", file, suffix));
+            files.push((format!("{}/{}", name, file_name), contents, file_name));
+        }
+        let listed: Vec<&str> = files.iter().map(|(_, _, f)| f.as_str()).collect();
+        entry.set_field("attachments", &listed.join(", "));
+
+        if let Err(e) = self.write(&name, &entry.to_string()) {
+            self.report.fail(name, format!("{:#}", e));
+            return;
+        }
+        self.report.imported.push(name);
+
+        for (path, contents, _) in files {
+            match self.claim(&path) {
+                Ok(Some(path)) => match self.write(&path, &contents) {
+                    Ok(()) => self.report.imported.push(path),
+                    Err(e) => self.report.fail(path, format!("{:#}", e)),
+                },
+                Ok(None) => self
+                    .report
+                    .fail(path.clone(), format!("{} already exists", path)),
+                Err(e) => self.report.fail(path, format!("{:#}", e)),
+            }
+        }
+    }
+
+    fn claim(&mut self, base: &str) -> Result<Option<String>> {
+        let Some(name) = pick_name(self.config, base, self.on_conflict, &self.taken)? else {
+            return Ok(None);
+        };
+        if name != base {
+            self.report
+                .notes
+                .push(format!("{} renamed to {}", base, name));
+        }
+        self.taken.insert(name.clone());
+        Ok(Some(name))
+    }
+
+    fn write(&self, name: &str, plaintext: &str) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        write_entry(self.config, name, plaintext)
+    }
+}
+
+/// Custom fields whose value spans several lines
+fn multi_line_fields(record: &Record) -> impl Iterator<Item = &(String, String)> {
+    record.strings.iter().filter(|(key, value)| {
+        !matches!(
+            key.as_str(),
+            "Title" | "Password" | "Notes" | "UserName" | "URL" | "otp" | "TOTP Seed"
+        ) && value.trim().contains('\n')
+    })
+}
+
+/// Line 1 holds the password, so the rest of a multi-line KeePass password
+/// goes at the top of the notes. Fields are one line each, so multi-line
+/// custom fields go at the bottom of the notes as `key:` and the value.
+fn to_entry(record: &Record) -> Entry {
+    let mut lines = record.get("Password").lines();
+    let password = lines.next().unwrap_or("").to_string();
+    let rest = lines.collect::<Vec<_>>().join("\n");
+    let notes = record.get("Notes").trim();
+    let notes = match (rest.trim().is_empty(), notes.is_empty()) {
+        (true, _) => notes.to_string(),
+        (false, true) => rest,
+        (false, false) => format!("{}\n{}", rest, notes),
+    };
+    let mut entry = Entry {
+        password,
+        notes,
+        fields: Vec::new(),
+    };
+    for (key, value) in &record.strings {
+        match key.as_str() {
+            "Title" | "Password" | "Notes" => {}
+            "UserName" => entry.set_field("username", value),
+            "URL" => entry.set_field("url", value),
+            // KeePassXC's and the KeeOtp plugin's TOTP fields
+            "otp" | "TOTP Seed" => entry.set_field("totp", value),
+            _ if value.trim().contains('\n') => {}
+            _ => entry.set_field(key, value),
+        }
+    }
+    for (key, value) in multi_line_fields(record) {
+        if !entry.notes.is_empty() {
+            entry.notes.push('\n');
+        }
+        entry.notes.push_str(&format!("{}:\n{}", key, value.trim()));
+    }
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::import::kdbx::tests::{Options, sample};
+    use crate::crypto::testing;
+    use tempfile::TempDir;
+
+    fn import(tmp: &TempDir, cfg: &Config, on_conflict: OnConflict) -> Result<()> {
+        set_password_hook(|_| Ok("master".into()));
+        let file = tmp.path().join("db.kdbx");
+        fs::write(&file, sample(&Options::default()))?;
+        run(cfg, &file, None, "kp", on_conflict, false)
+    }
+
+    fn read(cfg: &Config, name: &str) -> String {
+        fs::read_to_string(cfg.entry_path(name).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn groups_become_folders() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::plain_vault(tmp.path());
+
+        import(&tmp, &cfg, OnConflict::Skip)?;
+
+        let mail = Entry::parse(&read(&cfg, "kp/Mail & Calendar"));
+        assert_eq!(mail.password, "hunter2");
+        assert_eq!(mail.field("username"), Some("alice"));
+        assert_eq!(mail.field("pin"), Some("4321"));
+        assert_eq!(mail.field("attachments"), Some("note.txt"));
+        assert_eq!(
+            read(&cfg, "kp/Mail & Calendar/note.txt"),
+            "hello attachment"
+        );
+
+        let vpn = Entry::parse(&read(&cfg, "kp/Work/VPN"));
+        assert_eq!(vpn.password, "s3cret");
+        assert_eq!(vpn.notes, "line one\nline two");
+        assert!(!cfg.entry_path("kp/Recycle Bin/Deleted")?.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn existing_entries_are_skipped_or_renamed() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::plain_vault(tmp.path());
+        fs::create_dir_all(cfg.prefix.join("kp/Work"))?;
+        fs::write(cfg.entry_path("kp/Work/VPN")?, "mine")?;
+
+        let err = import(&tmp, &cfg, OnConflict::Skip).unwrap_err();
+        assert_eq!(err.to_string(), "1 items failed to import");
+        assert_eq!(read(&cfg, "kp/Work/VPN"), "mine");
+
+        import(&tmp, &cfg, OnConflict::Rename)?;
+        assert!(read(&cfg, "kp/Work/VPN-2").starts_with("s3cret"));
+        Ok(())
+    }
+
+    #[test]
+    fn totp_fields_are_recognized() {
+        let mut record = Record::default();
+        record.strings = vec![
+            ("Title".into(), "Key".into()),
+            ("otp".into(), "otpauth://totp/x".into()),
+            ("Security Answer".into(), "blue".into()),
+        ];
+        let entry = to_entry(&record);
+        assert_eq!(entry.field("totp"), Some("otpauth://totp/x"));
+        assert_eq!(entry.field("security-answer"), Some("blue"));
+    }
+
+    #[test]
+    fn multi_line_passwords_keep_their_other_lines() {
+        let mut record = Record::default();
+        record.strings = vec![
+            ("Password".into(), "first\nsecond\nthird".into()),
+            ("Notes".into(), "a note".into()),
+        ];
+        let entry = to_entry(&record);
+        assert_eq!(entry.password, "first");
+        assert_eq!(entry.notes, "second\nthird\na note");
+    }
+
+    #[test]
+    fn multi_line_custom_fields_go_in_the_notes() {
+        let mut record = Record::default();
+        record.strings = vec![
+            ("Notes".into(), "a note".into()),
+            ("Recovery Codes".into(), "1111\n2222\n".into()),
+            ("PIN".into(), "1234".into()),
+        ];
+        let entry = to_entry(&record);
+        assert_eq!(entry.notes, "a note\nRecovery Codes:\n1111\n2222");
+        assert_eq!(entry.field("recovery-codes"), None);
+        assert_eq!(entry.field("pin"), Some("1234"));
+        assert_eq!(Entry::parse(&entry.to_string()).notes, entry.notes);
+        assert_eq!(multi_line_fields(&record).count(), 1);
+    }
+}
@@ -0,0 +1,183 @@
+pub mod bundle;
+pub mod csv;
+mod kdbx;
+pub mod keepass;
+pub mod pass;
+
+use crate::cli::OnConflict;
+use crate::config::Config;
+use crate::crypto;
+use crate::utils::git_ops;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+
+/// What an import did (or would do), printed once at the end
+#[derive(Default)]
+pub struct ImportReport {
+    /// Entry names written to the vault
+    pub imported: Vec<String>,
+
+    /// Things worth knowing that aren't failures
+    pub notes: Vec<String>,
+
+    /// `(source item, reason)` for everything that was skipped
+    pub failures: Vec<(String, String)>,
+}
+
+impl ImportReport {
+    pub fn fail(&mut self, item: impl Into<String>, reason: impl ToString) {
+        self.failures.push((item.into(), reason.to_string()));
+    }
+
+    /// Print the summary, make a single commit for everything imported, and
+    /// turn any failures into an error so scripts see a non-zero exit.
+    pub fn finish(self, config: &Config, source: &str, dry_run: bool) -> Result<()> {
+        let verb = if dry_run { "Would import" } else { "Imported" };
+        for name in &self.imported {
+            println!("  + {}", name);
+        }
+        for note in &self.notes {
+            println!("ℹ️  {}", note);
+        }
+        for (item, reason) in &self.failures {
+            eprintln!("⚠️  {}: {}", item, reason);
+        }
+        println!(
+            "✅ {} {} entries from {} ({} failed)",
+            verb,
+            self.imported.len(),
+            source,
+            self.failures.len()
+        );
+
+        if !dry_run && !self.imported.is_empty() {
+            git_ops::sync_vault_with_message(
+                &config.prefix,
+                config.sync,
+                &format!("Import from {}", source),
+            )?;
+        }
+
+        if !self.failures.is_empty() {
+            anyhow::bail!("{} items failed to import", self.failures.len());
+        }
+        Ok(())
+    }
+}
+
+/// `folder/.../title` for an entry coming from another tool. Each component
+/// loses slashes and leading dots (those would be archived); a missing title
+/// becomes `untitled`.
+pub fn entry_name(folders: &[&str], title: &str) -> String {
+    let mut parts: Vec<String> = folders
+        .iter()
+        .flat_map(|part| part.split('/'))
+        .map(sanitize)
+        .filter(|p| !p.is_empty())
+        .collect();
+    parts.push(match sanitize(title) {
+        t if t.is_empty() => "untitled".to_string(),
+        t => t,
+    });
+    parts.join("/")
+}
+
+fn sanitize(part: &str) -> String {
+    part.trim()
+        .replace(['/', '\\'], "-")
+        .trim_start_matches('.')
+        .to_string()
+}
+
+/// A name that comes with its own folders, as bundle entries do, made
+/// safe to join onto the vault: absolute paths and empty, `.` or `..`
+/// parts are refused rather than repaired, as are `.git` and passman's own
+/// files. Hidden (archived) parts keep their leading dot.
+pub fn checked_name(name: &str) -> Result<String> {
+    let unsafe_name = || anyhow::anyhow!("`{}` is not a safe entry name", name.escape_debug());
+    if name.starts_with('/') {
+        return Err(unsafe_name());
+    }
+    name.split('/')
+        .map(|part| {
+            let clean = sanitize(part);
+            if clean.is_empty() || part == ".git" || part.starts_with(".passman-") {
+                return Err(unsafe_name());
+            }
+            Ok(if part.starts_with('.') {
+                format!(".{}", clean)
+            } else {
+                clean
+            })
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(|parts| parts.join("/"))
+}
+
+/// The entry name to write, honoring `--on-conflict`; `None` means skip.
+/// `taken` holds names already used by this run, so dry runs agree with
+/// real ones.
+pub fn pick_name(
+    config: &Config,
+    base: &str,
+    on_conflict: OnConflict,
+    taken: &HashSet<String>,
+) -> Result<Option<String>> {
+    let free = |name: &str| -> Result<bool> {
+        Ok(!taken.contains(name) && !config.entry_path(name)?.exists())
+    };
+    if free(base)? {
+        return Ok(Some(base.to_string()));
+    }
+    if matches!(on_conflict, OnConflict::Skip) {
+        return Ok(None);
+    }
+    for n in 2.. {
+        let name = format!("{}-{}", base, n);
+        if free(&name)? {
+            return Ok(Some(name));
+        }
+    }
+    unreachable!("every suffix is taken")
+}
+
+/// Encrypt `plaintext` for the recipients of `name`, creating its folder
+pub fn write_entry(config: &Config, name: &str, plaintext: &str) -> Result<()> {
+    let target = config.entry_path(name)?;
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+    let recipients = config.recipients_for(&target)?;
+    crypto::encrypt(&recipients, &target, &plaintext.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_cannot_escape_or_hide() {
+        assert_eq!(entry_name(&["imported", "a/b"], "x/y"), "imported/a/b/x-y");
+        assert_eq!(entry_name(&["", "..hidden"], "..secret"), "hidden/secret");
+        assert_eq!(entry_name(&["imported"], " ... "), "imported/untitled");
+    }
+
+    #[test]
+    fn bundle_names_are_checked() {
+        assert_eq!(checked_name("web/github").unwrap(), "web/github");
+        assert_eq!(checked_name(".archive/old").unwrap(), ".archive/old");
+        for bad in [
+            "../x",
+            "/etc/passwd",
+            "a/../../b",
+            "a//b",
+            ".",
+            ".git/hooks/x",
+            "",
+        ] {
+            assert!(checked_name(bad).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use crate::commands::import::ImportReport;
+use crate::config::{Config, RECIPIENTS_FILENAME, parse_recipients};
+use crate::crypto::{self, SecretString};
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::{env, fs};
+use walkdir::WalkDir;
+
+type GpgDecryptFn = fn(&Path) -> Result<String>;
+
+thread_local! {
+    static GPG_HOOK: RefCell<GpgDecryptFn> = RefCell::new(gpg_decrypt);
+}
+
+/// Test-only hook to stand in for the `gpg` binary
+#[cfg(test)]
+pub fn set_gpg_hook(f: GpgDecryptFn) {
+    GPG_HOOK.with(|h| *h.borrow_mut() = f);
+}
+
+/// Import a pass (password-store) tree into the vault.
+///
+/// Usage:
+///     passman import pass [--store ~/.password-store] [--recipient-map FILE] [--dry-run]
+///
+/// Every `*.gpg` file is decrypted with the local `gpg` binary and encrypted
+/// for the passman recipients at the same relative path. A `.gpg-id` in a
+/// subfolder becomes a `.recipients` file when every GPG id in it has an age
+/// recipient in the map (lines of `<gpg-id> <age-recipient>`); otherwise the
+/// secrets it governs are skipped and reported, rather than encrypted to
+/// recipients their owners never chose.
+pub fn run(
+    config: &Config,
+    store: Option<PathBuf>,
+    recipient_map: Option<PathBuf>,
+    dry_run: bool,
+) -> Result<()> {
+    let store = store.unwrap_or_else(default_store);
+    if !store.is_dir() {
+        anyhow::bail!("No password store at {}", store.display());
+    }
+    let map = match recipient_map {
+        Some(path) => load_recipient_map(&path)?,
+        None => HashMap::new(),
+    };
+
+    // Collect everything first: recipients files must exist before the
+    // entries beneath them are encrypted
+    let mut gpg_ids = Vec::new();
+    let mut secrets = Vec::new();
+    let walker = WalkDir::new(&store)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git");
+    for entry in walker.filter_map(Result::ok) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(&store)?.to_path_buf();
+        if entry.file_name() == ".gpg-id" {
+            gpg_ids.push(rel);
+        } else if rel.extension().and_then(|e| e.to_str()) == Some("gpg") {
+            secrets.push(rel);
+        }
+    }
+
+    let mut report = ImportReport::default();
+    let gpg_folders: HashSet<PathBuf> = gpg_ids
+        .iter()
+        .map(|rel| rel.parent().unwrap_or(Path::new("")).to_path_buf())
+        .collect();
+    let mut unmapped_folders = HashSet::new();
+
+    for rel in gpg_ids {
+        let folder = rel.parent().unwrap_or(Path::new(""));
+        if folder.as_os_str().is_empty() {
+            report
+                .notes
+                .push("top-level .gpg-id: entries use the vault recipients".into());
+            continue;
+        }
+
+        let raw = fs::read_to_string(store.join(&rel))
+            .with_context(|| format!("Failed to read {}", rel.display()))?;
+        let ids = parse_recipients(&raw);
+        let unmapped: Vec<_> = ids.iter().filter(|id| !map.contains_key(*id)).collect();
+        if ids.is_empty() || !unmapped.is_empty() {
+            let missing = unmapped
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            report.fail(
+                rel.display().to_string(),
+                format!(
+                    "no age recipient for [{}]; the secrets in {} are skipped",
+                    missing,
+                    folder.display()
+                ),
+            );
+            unmapped_folders.insert(folder.to_path_buf());
+            continue;
+        }
+
+        let target = config.prefix.join(folder).join(RECIPIENTS_FILENAME);
+        if target.exists() {
+            report.notes.push(format!(
+                "{} already exists; left unchanged",
+                target.display()
+            ));
+            continue;
+        }
+        let mut contents = format!("# imported from {}\n", rel.display());
+        for id in &ids {
+            contents.push_str(&format!("# {}\n{}\n", id, map[id]));
+        }
+        if !dry_run {
+            fs::create_dir_all(target.parent().unwrap_or(&config.prefix))?;
+            fs::write(&target, contents)
+                .with_context(|| format!("Failed to write {}", target.display()))?;
+        }
+        report.notes.push(format!(
+            "{} → {}/{}",
+            rel.display(),
+            folder.display(),
+            RECIPIENTS_FILENAME
+        ));
+    }
+
+    for rel in secrets {
+        let name = rel.with_extension("").to_string_lossy().to_string();
+        // the nearest .gpg-id decides who may read a secret, as in pass
+        let governing = rel.ancestors().skip(1).find(|f| gpg_folders.contains(*f));
+        if let Some(folder) = governing.filter(|f| unmapped_folders.contains(*f)) {
+            report.fail(
+                rel.display().to_string(),
+                format!("skipped: {}/.gpg-id is not mapped", folder.display()),
+            );
+            continue;
+        }
+        if let Err(e) = import_one(config, &store.join(&rel), &name, dry_run) {
+            report.fail(rel.display().to_string(), format!("{:#}", e));
+        } else {
+            report.imported.push(name);
+        }
+    }
+
+    report.finish(config, "pass", dry_run)
+}
+
+fn import_one(config: &Config, source: &Path, name: &str, dry_run: bool) -> Result<()> {
+    let target = config.entry_path(name)?;
+    if target.exists() {
+        anyhow::bail!("{} already exists", target.display());
+    }
+
+    let plaintext = SecretString::new(GPG_HOOK.with(|h| (h.borrow())(source))?);
+    let recipients = config.recipients_for(&target)?;
+    if dry_run {
+        return Ok(());
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+    crypto::encrypt(&recipients, &target, &plaintext.expose().trim_end().into())
+}
+
+/// `$PASSWORD_STORE_DIR`, like pass itself, else `~/.password-store`
+fn default_store() -> PathBuf {
+    env::var("PASSWORD_STORE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            dirs::home_dir()
+                .expect("HOME directory not set")
+                .join(".password-store")
+        })
+}
+
+/// Lines of `<gpg-id> <age-recipient>`; `#` comments allowed
+fn load_recipient_map(path: &Path) -> Result<HashMap<String, String>> {
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read recipient map {}", path.display()))?;
+    parse_recipients(&raw)
+        .into_iter()
+        .map(|line| {
+            let (id, recipient) = line
+                .split_once(char::is_whitespace)
+                .with_context(|| format!("Expected `<gpg-id> <age-recipient>`, got `{}`", line))?;
+            Ok((id.to_string(), recipient.trim().to_string()))
+        })
+        .collect()
+}
+
+fn gpg_decrypt(file: &Path) -> Result<String> {
+    let output = Command::new("gpg")
+        .arg("--quiet")
+        .arg("--decrypt")
+        .arg(file)
+        .stdout(Stdio::piped())
+        .output()
+        .with_context(|| format!("Failed to run `gpg --decrypt` on {}", file.display()))?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "gpg decryption failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    String::from_utf8(output.stdout).context("Decrypted entry is not valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::testing;
+    use tempfile::TempDir;
+
+    /// Fails for files named `broken.gpg`, otherwise echoes the ciphertext
+    fn fake_gpg(file: &Path) -> Result<String> {
+        if file.ends_with("broken.gpg") {
+            anyhow::bail!("gpg: decryption failed: No secret key");
+        }
+        Ok(fs::read_to_string(file)? + "\n")
+    }
+
+    fn make_store(tmp: &TempDir) -> PathBuf {
+        let store = tmp.path().join("store");
+        fs::create_dir_all(store.join("team")).unwrap();
+        fs::create_dir_all(store.join(".git")).unwrap();
+        fs::write(store.join(".gpg-id"), "me@example.com\n").unwrap();
+        fs::write(store.join("mail.gpg"), "hunter2").unwrap();
+        fs::write(
+            store.join("team/.gpg-id"),
+            "me@example.com\nyou@example.com\n",
+        )
+        .unwrap();
+        fs::write(store.join("team/db.gpg"), "s3cret\nuser: admin").unwrap();
+        fs::write(store.join(".git/HEAD.gpg"), "not an entry").unwrap();
+        store
+    }
+
+    fn install_fakes() {
+        set_gpg_hook(fake_gpg);
+    }
+
+    #[test]
+    fn imports_tree_and_maps_gpg_ids() -> Result<()> {
+        install_fakes();
+        let tmp = TempDir::new()?;
+        let cfg = testing::recording_vault(tmp.path());
+        let store = make_store(&tmp);
+        let map = tmp.path().join("map.txt");
+        fs::write(&map, "me@example.com age1me\nyou@example.com age1you\n")?;
+
+        run(&cfg, Some(store), Some(map), false)?;
+
+        let recipients = fs::read_to_string(cfg.prefix.join("team/.recipients"))?;
+        assert_eq!(parse_recipients(&recipients), vec!["age1me", "age1you"]);
+        assert_eq!(
+            fs::read_to_string(cfg.prefix.join("mail.rage"))?,
+            "age1me|hunter2"
+        );
+        assert_eq!(
+            fs::read_to_string(cfg.prefix.join("team/db.rage"))?,
+            "age1me,age1you|s3cret\nuser: admin"
+        );
+        assert!(!cfg.prefix.join(".git/HEAD.rage").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn unmapped_gpg_ids_and_bad_files_are_reported() -> Result<()> {
+        install_fakes();
+        let tmp = TempDir::new()?;
+        let cfg = testing::recording_vault(tmp.path());
+        let store = make_store(&tmp);
+        fs::write(store.join("broken.gpg"), "")?;
+        fs::write(cfg.prefix.join("mail.rage"), "already here")?;
+
+        let err = run(&cfg, Some(store), None, false).unwrap_err();
+        // team/.gpg-id unmapped so team/db skipped, broken.gpg
+        // undecryptable, mail already exists
+        assert_eq!(err.to_string(), "4 items failed to import");
+
+        assert!(!cfg.prefix.join("team/.recipients").exists());
+        assert!(!cfg.prefix.join("team/db.rage").exists());
+        assert_eq!(
+            fs::read_to_string(cfg.prefix.join("mail.rage"))?,
+            "already here"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dry_run_writes_nothing() -> Result<()> {
+        install_fakes();
+        let tmp = TempDir::new()?;
+        let cfg = testing::recording_vault(tmp.path());
+        let store = make_store(&tmp);
+        let map = tmp.path().join("map.txt");
+        fs::write(&map, "me@example.com age1me\nyou@example.com age1you\n")?;
+
+        run(&cfg, Some(store), Some(map), true)?;
+
+        assert!(fs::read_dir(&cfg.prefix)?.next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn missing_store_errors() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::recording_vault(tmp.path());
+        let err = run(&cfg, Some(tmp.path().join("nope")), None, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("No password store at {}", tmp.path().join("nope").display())
+        );
+        Ok(())
+    }
+}
@@ -1,16 +1,35 @@
-use crate::{completions, config::Config, utils::keygen};
+use crate::{
+    completions,
+    config::{self, Config, FileConfig},
+    crypto::{SecretString, detect, identity, ssh},
+    utils::{keygen, secure_fs},
+};
 use anyhow::{Context, Result};
-use dialoguer::{Confirm, Input};
-use std::{env, fs, path::Path, process::ExitStatus};
+use dialoguer::{Confirm, Input, Password};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+};
 
 /// Run the init command: generate keys, initialize git, install completions.
 pub fn run(config: &Config) -> Result<()> {
     let secret_path = &config.secret;
     let public_path = &config.base_dir.join("public.key");
+    // keys and plaintext-adjacent state live here; keep other users out
+    secure_fs::private_dir(&config.base_dir)?;
 
     // 1) Keypair generation prompt
     let mut do_generate = true;
-    if secret_path.exists() || public_path.exists() {
+    let mut identity_path = secret_path.clone();
+    if ssh::is_private_key(secret_path) {
+        // never offer to delete someone's SSH key
+        println!(
+            "✅ Using SSH key {} as the identity; skipping key generation.",
+            secret_path.display()
+        );
+        do_generate = false;
+    } else if secret_path.exists() || public_path.exists() {
         let prompt = format!(
             "⚠️  DANGER: Overwriting your secret key is irreversible!\n\
 🔐 Private key: {}\n\
@@ -28,8 +47,31 @@ Do you REALLY want to overwrite these files?",
             fs::remove_file(public_path).ok();
         }
     }
-    if do_generate {
+    let ssh_key = if do_generate {
+        SSH_KEYS_HOOK
+            .with(|h| (h.borrow())())
+            .into_iter()
+            .find(|key| {
+                let prompt = format!(
+                    "Use your existing SSH key {} instead of creating an age identity?",
+                    key.display()
+                );
+                CONFIRM_HOOK.with(|h| (h.borrow())(&prompt, false))
+            })
+    } else {
+        None
+    };
+    if let Some(key) = ssh_key {
+        SSH_KEY_HOOK.with(|h| (h.borrow())(&key, public_path))?;
+        identity_path = key;
+    } else if do_generate {
         KEYGEN_HOOK.with(|h| (h.borrow())(&config.secret, &config.base_dir.join("public.key")))?;
+        let prompt = "Protect the new identity with a passphrase? \
+(asked once per command; set $PASSMAN_PASSPHRASE_FILE or $PASSMAN_ASKPASS for scripts)";
+        if CONFIRM_HOOK.with(|h| (h.borrow())(prompt, false)) {
+            PROTECT_HOOK.with(|h| (h.borrow())(secret_path))?;
+            println!("🔒 {} is now passphrase-protected.", secret_path.display());
+        }
     } else {
         println!("✅ Existing keypair remains intact.");
     }
@@ -43,7 +85,7 @@ Do you REALLY want to overwrite these files?",
             vault_dir.display()
         );
         if CONFIRM_HOOK.with(|h| (h.borrow())(&prompt, true)) {
-            fs::create_dir_all(&vault_dir).with_context(|| {
+            secure_fs::private_dir(&vault_dir).with_context(|| {
                 format!("Failed to create vault directory {}", vault_dir.display())
             })?;
             println!("🔧 git init in {}…", vault_dir.display());
@@ -57,9 +99,13 @@ Do you REALLY want to overwrite these files?",
                 )
             });
 
-            let remote_url = INPUT_HOOK.with(|i| {
-                (i.borrow())("Enter GitHub remote URL (SSH or HTTPS), or leave blank to skip")
-            });
+            // A remote configured for this vault skips the prompt
+            let remote_url = match &config.remote {
+                Some(url) => url.clone(),
+                None => INPUT_HOOK.with(|i| {
+                    (i.borrow())("Enter GitHub remote URL (SSH or HTTPS), or leave blank to skip")
+                }),
+            };
             if !remote_url.trim().is_empty() {
                 GIT_HOOK.with(|g| {
                     (g.borrow())(&["remote", "add", "origin", remote_url.trim()], &vault_dir)
@@ -131,6 +177,13 @@ Do you REALLY want to overwrite these files?",
         rc, rc
     );
 
+    // 4) Persist settings so later runs skip backend detection
+    let config = Config {
+        secret: identity_path,
+        ..config.clone()
+    };
+    SAVE_CONFIG_HOOK.with(|h| (h.borrow())(&config))?;
+
     Ok(())
 }
 
@@ -141,6 +194,9 @@ type InputFn = fn(&str) -> String;
 type KeygenFn = fn(&Path, &Path) -> Result<()>;
 type InstallFn = fn() -> Result<()>;
 type GitFn = fn(&[&str], &Path) -> Result<ExitStatus>;
+type SaveConfigFn = fn(&Config) -> Result<()>;
+type ProtectFn = fn(&Path) -> Result<()>;
+type SshKeysFn = fn() -> Vec<PathBuf>;
 
 fn default_confirm(prompt: &str, def: bool) -> bool {
     Confirm::new()
@@ -159,9 +215,32 @@ fn default_input(prompt: &str) -> String {
 fn default_keygen(secret: &Path, public: &Path) -> Result<()> {
     keygen::generate_keypair(secret, public)
 }
+fn default_protect(secret: &Path) -> Result<()> {
+    let passphrase: SecretString = Password::new()
+        .with_prompt("Identity passphrase")
+        .with_confirmation("Repeat passphrase", "Passphrases don't match")
+        .interact()
+        .context("Failed to read passphrase")?
+        .into();
+    identity::protect(secret, &passphrase)
+}
+fn default_ssh_keys() -> Vec<PathBuf> {
+    detect::detect_ssh_keys()
+}
+fn default_use_ssh_key(key: &Path, public: &Path) -> Result<()> {
+    keygen::use_ssh_key(key, public)
+}
 fn default_install() -> Result<()> {
     completions::install()
 }
+fn default_save_config(config: &Config) -> Result<()> {
+    let path = config::config_file_path();
+    let mut file = FileConfig::load(&path)?;
+    file.record(config);
+    file.save(&path)?;
+    println!("📝 Settings saved to {}", path.display());
+    Ok(())
+}
 fn default_git(args: &[&str], cwd: &Path) -> Result<ExitStatus> {
     Ok(std::process::Command::new("git")
         .args(args)
@@ -180,6 +259,14 @@ thread_local! {
         std::cell::RefCell::new(default_install);
     static GIT_HOOK:     std::cell::RefCell<GitFn>     =
         std::cell::RefCell::new(default_git);
+    static SAVE_CONFIG_HOOK: std::cell::RefCell<SaveConfigFn> =
+        std::cell::RefCell::new(default_save_config);
+    static PROTECT_HOOK: std::cell::RefCell<ProtectFn> =
+        std::cell::RefCell::new(default_protect);
+    static SSH_KEYS_HOOK: std::cell::RefCell<SshKeysFn> =
+        std::cell::RefCell::new(default_ssh_keys);
+    static SSH_KEY_HOOK: std::cell::RefCell<KeygenFn> =
+        std::cell::RefCell::new(default_use_ssh_key);
 }
 
 #[cfg(test)]
@@ -203,9 +290,30 @@ pub fn set_git_hook(f: GitFn) {
     GIT_HOOK.with(|h| *h.borrow_mut() = f);
 }
 
+#[cfg(test)]
+pub fn set_save_config_hook(f: SaveConfigFn) {
+    SAVE_CONFIG_HOOK.with(|h| *h.borrow_mut() = f);
+}
+
+#[cfg(test)]
+pub fn set_protect_hook(f: ProtectFn) {
+    PROTECT_HOOK.with(|h| *h.borrow_mut() = f);
+}
+
+#[cfg(test)]
+pub fn set_ssh_keys_hook(f: SshKeysFn) {
+    SSH_KEYS_HOOK.with(|h| *h.borrow_mut() = f);
+}
+
+#[cfg(test)]
+pub fn set_ssh_key_hook(f: KeygenFn) {
+    SSH_KEY_HOOK.with(|h| *h.borrow_mut() = f);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::SyncPolicy;
     use anyhow::Result;
     use std::cell::RefCell;
     use std::{
@@ -221,6 +329,11 @@ mod tests {
             secret: tmp.path().join("secret.agekey"),
             crypto_extension: "rage".into(),
             public_key_filename: "public.key".into(),
+            editor: None,
+            clipboard_timeout: 45,
+            sync: SyncPolicy::Manual,
+            vault: None,
+            remote: None,
         }
     }
 
@@ -262,6 +375,9 @@ mod tests {
     fn spy_install() -> Result<()> {
         Ok(())
     }
+    fn spy_save_config(_c: &Config) -> Result<()> {
+        Ok(())
+    }
     fn input_blank(_p: &str) -> String {
         "".into()
     }
@@ -299,6 +415,7 @@ mod tests {
         set_input_hook(input_blank);
         install_git_spy();
         set_install_hook(spy_install);
+        set_save_config_hook(spy_save_config);
 
         run(&cfg)?;
         assert!(GIT_CALLS.with(|c| c.borrow().is_empty()));
@@ -317,6 +434,7 @@ mod tests {
         set_input_hook(input_blank);
         install_git_spy();
         set_install_hook(spy_install);
+        set_save_config_hook(spy_save_config);
 
         run(&cfg)?;
         let calls = GIT_CALLS.with(|c| c.borrow().clone());
@@ -342,6 +460,7 @@ mod tests {
         set_input_hook(|_| "git@host:repo.git".into());
         install_git_spy();
         set_install_hook(spy_install);
+        set_save_config_hook(spy_save_config);
 
         run(&cfg)?;
         let calls = GIT_CALLS.with(|c| c.borrow().clone());
@@ -371,6 +490,7 @@ mod tests {
         install_git_spy();
         set_git_fail_at(Some(4));
         set_install_hook(spy_install);
+        set_save_config_hook(spy_save_config);
 
         run(&cfg)?;
         let calls = GIT_CALLS.with(|c| c.borrow().clone());
@@ -392,6 +512,7 @@ mod tests {
         install_git_spy();
         set_git_fail_at(Some(5));
         set_install_hook(spy_install);
+        set_save_config_hook(spy_save_config);
 
         run(&cfg)?;
         let calls = GIT_CALLS.with(|c| c.borrow().clone());
@@ -400,6 +521,63 @@ mod tests {
         Ok(())
     }
 
+    thread_local! {
+        static SAVED_BACKEND: RefCell<Option<String>> = const { RefCell::new(None) };
+    }
+    fn recording_save_config(c: &Config) -> Result<()> {
+        SAVED_BACKEND.with(|s| *s.borrow_mut() = Some(c.crypto_extension.clone()));
+        Ok(())
+    }
+
+    #[test]
+    fn persists_settings_after_setup() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+
+        set_keygen_hook(spy_keygen);
+        set_confirm_init_repo(false);
+        set_confirm_hook(stub_confirm);
+        install_git_spy();
+        set_install_hook(spy_install);
+        set_save_config_hook(recording_save_config);
+
+        run(&cfg)?;
+        assert_eq!(
+            SAVED_BACKEND.with(|s| s.borrow().clone()),
+            Some("rage".into())
+        );
+        Ok(())
+    }
+
+    fn input_must_not_be_called(_p: &str) -> String {
+        panic!("remote prompt should be skipped when a remote is configured");
+    }
+
+    #[test]
+    fn configured_remote_skips_prompt() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let mut cfg = make_config(&tmp);
+        cfg.remote = Some("git@host:team.git".into());
+
+        set_keygen_hook(spy_keygen);
+        set_confirm_init_repo(true);
+        set_confirm_rebase_retry(false);
+        set_confirm_hook(stub_confirm);
+        set_input_hook(input_must_not_be_called);
+        install_git_spy();
+        set_install_hook(spy_install);
+        set_save_config_hook(spy_save_config);
+
+        run(&cfg)?;
+        let calls = GIT_CALLS.with(|c| c.borrow().clone());
+        assert!(
+            calls
+                .iter()
+                .any(|a| a == &vec!["remote", "add", "origin", "git@host:team.git"])
+        );
+        Ok(())
+    }
+
     #[test]
     fn already_repo_skips_all() -> Result<()> {
         let tmp = TempDir::new()?;
@@ -411,8 +589,64 @@ mod tests {
         set_confirm_hook(stub_confirm);
         install_git_spy();
         set_install_hook(spy_install);
+        set_save_config_hook(spy_save_config);
 
         run(&cfg)?;
         Ok(())
     }
+
+    #[test]
+    fn new_identity_can_be_passphrase_protected() -> Result<()> {
+        thread_local! {
+            static PROTECTED: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+        }
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+
+        set_keygen_hook(spy_keygen);
+        set_confirm_hook(|prompt, _| prompt.starts_with("Protect the new identity"));
+        set_protect_hook(|secret| {
+            PROTECTED.with(|p| *p.borrow_mut() = Some(secret.to_path_buf()));
+            Ok(())
+        });
+        install_git_spy();
+        set_install_hook(spy_install);
+        set_save_config_hook(spy_save_config);
+
+        run(&cfg)?;
+        assert_eq!(PROTECTED.with(|p| p.borrow().clone()), Some(cfg.secret));
+        Ok(())
+    }
+
+    #[test]
+    fn existing_ssh_key_can_be_the_identity() -> Result<()> {
+        thread_local! {
+            static SAVED: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+        }
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+
+        set_keygen_hook(|_, _| panic!("no age key should be generated"));
+        set_ssh_keys_hook(|| vec![PathBuf::from("/home/me/.ssh/id_ed25519")]);
+        set_ssh_key_hook(|key, public| {
+            assert_eq!(key, Path::new("/home/me/.ssh/id_ed25519"));
+            fs::write(public, "ssh-ed25519 AAAA me@laptop\n")?;
+            Ok(())
+        });
+        set_confirm_hook(|prompt, _| prompt.starts_with("Use your existing SSH key"));
+        install_git_spy();
+        set_install_hook(spy_install);
+        set_save_config_hook(|c| {
+            SAVED.with(|s| *s.borrow_mut() = Some(c.secret.clone()));
+            Ok(())
+        });
+
+        run(&cfg)?;
+        assert_eq!(
+            SAVED.with(|s| s.borrow().clone()),
+            Some(PathBuf::from("/home/me/.ssh/id_ed25519"))
+        );
+        assert!(fs::read_to_string(tmp.path().join("public.key"))?.starts_with("ssh-ed25519"));
+        Ok(())
+    }
 }
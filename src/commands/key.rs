@@ -0,0 +1,364 @@
+use crate::cli::KeyAction;
+use crate::config::Config;
+use crate::crypto::identity;
+use crate::utils::qr::print_qr;
+use crate::utils::secure_fs;
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::RngCore;
+use sharks::{Share, Sharks};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroize;
+
+/// Start of every share, so shares can be picked out of pasted text
+const SHARE_PREFIX: &str = "passman-share-v1";
+
+/// Back up the identity as Shamir shares, or rebuild it from them.
+///
+/// Usage:
+///     passman key split [--shares 5] [--threshold 3]
+///     passman key combine [FILE...] [--output FILE]
+///
+/// `split` cuts the identity file into `--shares` shares, printed as text
+/// and as QR codes; any `--threshold` of them rebuild it, fewer reveal
+/// nothing. The file is split as stored, so a passphrase-protected
+/// identity still needs its passphrase once rebuilt.
+///
+/// `combine` reads shares from the files given, or pasted one per line,
+/// and only writes the identity (to the configured path unless
+/// `--output`) when it matches the public key file.
+pub fn run(config: &Config, action: KeyAction) -> Result<()> {
+    match action {
+        KeyAction::Split { shares, threshold } => split(config, shares, threshold),
+        KeyAction::Combine { files, output } => combine(config, &files, output),
+    }
+}
+
+fn split(config: &Config, shares: u8, threshold: u8) -> Result<()> {
+    let mut secret = fs::read(&config.secret)
+        .with_context(|| format!("Failed to read identity {}", config.secret.display()))?;
+    let texts = split_secret(&secret, shares, threshold);
+    secret.zeroize();
+    let texts = texts?;
+
+    println!(
+        "🧩 {} shares of {}; any {} of them rebuild it with `passman key combine`.",
+        shares,
+        config.secret.display(),
+        threshold
+    );
+    println!(
+        "   Keep each one in a different place or with a different person: {} together are your identity.",
+        threshold
+    );
+    if identity::is_protected(&config.secret) {
+        println!(
+            "   The identity is passphrase-protected; the rebuilt file still needs the passphrase."
+        );
+    }
+    for (i, text) in texts.iter().enumerate() {
+        println!("\n── share {} of {} ──", i + 1, shares);
+        println!("{}", text);
+        if print_qr(text).is_err() {
+            println!("(too long for a QR code; keep the text)");
+        }
+    }
+    Ok(())
+}
+
+fn combine(config: &Config, files: &[PathBuf], output: Option<PathBuf>) -> Result<()> {
+    let target = output.unwrap_or_else(|| config.secret.clone());
+    if target.exists() {
+        anyhow::bail!(
+            "{} already exists; pass --output to rebuild the identity somewhere else",
+            target.display()
+        );
+    }
+
+    let shares = if files.is_empty() {
+        read_pasted()?
+    } else {
+        let mut shares = Vec::new();
+        for file in files {
+            let raw = fs::read_to_string(file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            shares.extend(find_shares(&raw));
+        }
+        shares
+    };
+    let mut secret = combine_shares(&shares)?;
+
+    // check the rebuilt file before it takes the identity's place
+    let dir = target.parent().unwrap_or(Path::new("."));
+    secure_fs::private_dir(dir)?;
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Failed to create a file in {}", dir.display()))?;
+    secure_fs::private_file(tmp.path())?;
+    let written = tmp.write_all(&secret);
+    secret.zeroize();
+    written?;
+    let recipient =
+        identity::recipient(tmp.path()).context("The shares don't rebuild a usable identity")?;
+
+    let public = config.base_dir.join(&config.public_key_filename);
+    if public.exists() {
+        if !config.read_recipients()?.contains(&recipient) {
+            anyhow::bail!(
+                "The rebuilt identity ({}) is not in {}; the shares belong to another identity",
+                recipient,
+                public.display()
+            );
+        }
+    } else {
+        fs::write(&public, format!("{}\n", recipient))
+            .with_context(|| format!("Failed to write {}", public.display()))?;
+        println!("🟢 Wrote the missing {}", public.display());
+    }
+    tmp.persist(&target)
+        .with_context(|| format!("Failed to write {}", target.display()))?;
+    println!("✅ Rebuilt {} ({})", target.display(), recipient);
+    Ok(())
+}
+
+/// `secret` as `shares` share texts, any `threshold` of which rebuild it
+fn split_secret(secret: &[u8], shares: u8, threshold: u8) -> Result<Vec<String>> {
+    if threshold < 2 {
+        anyhow::bail!("--threshold must be at least 2; one share would be a plain copy");
+    }
+    if shares < threshold {
+        anyhow::bail!("--shares must be at least --threshold ({})", threshold);
+    }
+    // tells shares of different splits apart
+    let mut set = [0u8; 4];
+    rand::thread_rng().fill_bytes(&mut set);
+    let set: String = set.iter().map(|b| format!("{:02x}", b)).collect();
+
+    Ok(Sharks(threshold)
+        .dealer(secret)
+        .take(shares as usize)
+        .map(|share| {
+            let mut bytes = Vec::from(&share);
+            let text = format!(
+                "{}:{}:{}:{}",
+                SHARE_PREFIX,
+                set,
+                threshold,
+                URL_SAFE_NO_PAD.encode(&bytes)
+            );
+            bytes.zeroize();
+            text
+        })
+        .collect())
+}
+
+/// Every share text in `raw`, one per line, ignoring anything else
+fn find_shares(raw: &str) -> Vec<String> {
+    raw.lines()
+        .map(str::trim)
+        .filter(|l| l.starts_with(SHARE_PREFIX))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Read pasted shares until there are enough different ones
+fn read_pasted() -> Result<Vec<String>> {
+    let terminal = io::stdin().is_terminal();
+    if terminal {
+        println!("Paste the shares, one per line (Ctrl-D when done):");
+    }
+    let mut shares: Vec<String> = Vec::new();
+    let mut xs: BTreeSet<u8> = BTreeSet::new();
+    let mut needed = None;
+    for line in io::stdin().lock().lines() {
+        for text in find_shares(&line?) {
+            if shares.contains(&text) {
+                continue;
+            }
+            if let Ok(parsed) = parse(&text) {
+                needed.get_or_insert(parsed.threshold);
+                xs.insert(parsed.share.x.0);
+            }
+            shares.push(text);
+        }
+        if needed.is_some_and(|n| xs.len() >= n as usize) {
+            break;
+        }
+        if terminal && let Some(n) = needed {
+            println!("   {} of {}", xs.len(), n);
+        }
+    }
+    Ok(shares)
+}
+
+struct Parsed {
+    set: String,
+    threshold: u8,
+    share: Share,
+}
+
+fn parse(text: &str) -> Result<Parsed> {
+    let mut parts = text.trim().splitn(4, ':');
+    let (Some(SHARE_PREFIX), Some(set), Some(threshold), Some(body)) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        anyhow::bail!("Not a passman share");
+    };
+    let threshold = threshold.parse().context("Share is damaged")?;
+    let mut bytes = URL_SAFE_NO_PAD.decode(body).context("Share is damaged")?;
+    let share =
+        Share::try_from(bytes.as_slice()).map_err(|e| anyhow::anyhow!("Share is damaged: {}", e));
+    bytes.zeroize();
+    Ok(Parsed {
+        set: set.to_string(),
+        threshold,
+        share: share?,
+    })
+}
+
+/// Rebuild the secret from share texts of one split
+fn combine_shares(texts: &[String]) -> Result<Vec<u8>> {
+    let parsed = texts
+        .iter()
+        .enumerate()
+        .map(|(i, t)| parse(t).with_context(|| format!("Share {} is unreadable", i + 1)))
+        .collect::<Result<Vec<_>>>()?;
+    let Some(first) = parsed.first() else {
+        anyhow::bail!("No shares given");
+    };
+    if parsed
+        .iter()
+        .any(|p| p.set != first.set || p.threshold != first.threshold)
+    {
+        anyhow::bail!("The shares come from different splits");
+    }
+    // a repeated point breaks the interpolation, so each share counts once
+    let mut unique: BTreeMap<u8, &Share> = BTreeMap::new();
+    for p in &parsed {
+        let x = p.share.x.0;
+        if let Some(seen) = unique.insert(x, &p.share)
+            && seen.y != p.share.y
+        {
+            anyhow::bail!(
+                "Two different shares are numbered {}; one of them is damaged",
+                x
+            );
+        }
+    }
+    if unique.len() < first.threshold as usize {
+        anyhow::bail!(
+            "{} different shares given, {} needed",
+            unique.len(),
+            first.threshold
+        );
+    }
+    Sharks(first.threshold)
+        .recover(unique.into_values())
+        .map_err(|e| anyhow::anyhow!("Failed to combine the shares: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use age::secrecy::ExposeSecret;
+    use tempfile::TempDir;
+
+    fn make_config(tmp: &TempDir) -> Config {
+        let id = age::x25519::Identity::generate();
+        let cfg = test_config(tmp.path());
+        fs::write(&cfg.secret, format!("{}\n", id.to_string().expose_secret())).unwrap();
+        fs::write(
+            tmp.path().join("public.key"),
+            format!("{}\n", id.to_public()),
+        )
+        .unwrap();
+        cfg
+    }
+
+    #[test]
+    fn any_threshold_shares_rebuild_the_secret() -> Result<()> {
+        let shares = split_secret(b"AGE-SECRET-KEY-1ABC\n", 5, 3)?;
+        assert_eq!(shares.len(), 5);
+        let picked = vec![shares[4].clone(), shares[0].clone(), shares[2].clone()];
+        assert_eq!(combine_shares(&picked)?, b"AGE-SECRET-KEY-1ABC\n");
+
+        let too_few = vec![shares[1].clone(), shares[1].clone(), shares[3].clone()];
+        assert!(combine_shares(&too_few).is_err());
+        let other = split_secret(b"AGE-SECRET-KEY-1XYZ\n", 5, 3)?;
+        let mixed = vec![shares[0].clone(), shares[1].clone(), other[2].clone()];
+        assert!(combine_shares(&mixed).is_err());
+        assert!(split_secret(b"x", 2, 3).is_err());
+        assert!(split_secret(b"x", 5, 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_shares_count_once() -> Result<()> {
+        let shares = split_secret(b"AGE-SECRET-KEY-1ABC\n", 5, 3)?;
+        let repeated = vec![
+            shares[0].clone(),
+            shares[0].clone(),
+            shares[2].clone(),
+            shares[4].clone(),
+        ];
+        assert_eq!(combine_shares(&repeated)?, b"AGE-SECRET-KEY-1ABC\n");
+
+        // same number, different body
+        let (head, body) = shares[0].rsplit_once(':').unwrap();
+        let mut bytes = URL_SAFE_NO_PAD.decode(body)?;
+        bytes[1] ^= 1;
+        let damaged = format!("{}:{}", head, URL_SAFE_NO_PAD.encode(&bytes));
+        let clash = vec![
+            shares[0].clone(),
+            damaged,
+            shares[2].clone(),
+            shares[4].clone(),
+        ];
+        let err = combine_shares(&clash).unwrap_err();
+        assert!(
+            err.to_string().contains("one of them is damaged"),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn combine_checks_the_identity_against_the_public_key() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        let original = fs::read(&cfg.secret)?;
+        let shares = split_secret(&original, 3, 2)?;
+        let file = tmp.path().join("shares.txt");
+        fs::write(&file, format!("share 1:\n{}\n\n{}\n", shares[0], shares[2]))?;
+
+        let rebuilt = tmp.path().join("rebuilt.rage");
+        run(
+            &cfg,
+            KeyAction::Combine {
+                files: vec![file.clone()],
+                output: Some(rebuilt.clone()),
+            },
+        )?;
+        assert_eq!(fs::read(&rebuilt)?, original);
+
+        // shares of another identity don't pass
+        let tmp2 = TempDir::new()?;
+        let other = make_config(&tmp2);
+        let err = run(
+            &other,
+            KeyAction::Combine {
+                files: vec![file],
+                output: Some(tmp2.path().join("rebuilt.rage")),
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("another identity"), "{}", err);
+        assert!(!tmp2.path().join("rebuilt.rage").exists());
+        Ok(())
+    }
+}
@@ -1,15 +1,46 @@
-use crate::config::Config;
+use crate::commands::expiring;
+use crate::config::{Config, strip_entry_extension};
+use crate::utils::{entries, expiry, index, manifest, signing};
 use anyhow::Result;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::{fs, path::Path, path::PathBuf};
 
-pub fn run(config: &Config, path: Option<String>, show_all: bool) -> Result<()> {
-    // Determine crypto file extension (ensure it starts with a dot)
-    let ext = if config.crypto_extension.starts_with('.') {
-        config.crypto_extension.clone()
-    } else {
-        format!(".{}", config.crypto_extension)
-    };
+#[derive(Default)]
+pub struct Options {
+    /// Also include archived (dot-prefixed) entries
+    pub show_all: bool,
+    /// Mark entries past their `expires:` / `rotate-every:` date
+    pub stale: bool,
+    /// Only show entries carrying this tag
+    pub tag: Option<String>,
+    /// Print each entry's tags after its name
+    pub show_tags: bool,
+    /// One entry name per line instead of a tree (for shell completion)
+    pub flat: bool,
+}
+
+/// What to print besides the tree itself
+#[derive(Default)]
+struct View {
+    /// Entry file → notes printed after its name
+    marks: HashMap<PathBuf, Vec<String>>,
+    /// When filtering, the only entry files to show
+    only: Option<HashSet<PathBuf>>,
+}
 
+/// Print the vault as a tree. `--stale` decrypts every entry to mark
+/// overdue ones; tags come from the metadata index when there is one.
+pub fn run(config: &Config, path: Option<String>, opts: Options) -> Result<()> {
+    let show_all = opts.show_all;
+    if opts.flat {
+        for e in entries::collect(&config.prefix, show_all)? {
+            println!("{}", e.name);
+        }
+        return Ok(());
+    }
+    if let Some(named) = manifest::entries(&config.prefix)? {
+        return run_hidden(config, path, named, &opts);
+    }
     // Resolve base directory
     let base: PathBuf = match path {
         Some(sub) => {
@@ -17,8 +48,8 @@ pub fn run(config: &Config, path: Option<String>, show_all: bool) -> Result<()>
             if full_path.is_dir() {
                 full_path
             } else {
-                let with_ext = config.prefix.join(format!("{sub}{ext}"));
-                if with_ext.exists() {
+                // Either `.age` or `.rage` counts as an entry
+                if config.entry_path(&sub)?.exists() {
                     println!("{}", sub);
                     return Ok(());
                 } else {
@@ -42,19 +73,187 @@ pub fn run(config: &Config, path: Option<String>, show_all: bool) -> Result<()>
         .unwrap_or_else(|_| base.display().to_string());
     println!("📂 {}", label);
 
+    let view = make_view(config, &opts)?;
+
     // Walk tree
-    walk(&base, vec![], show_all, &ext)?;
+    walk(&base, vec![], show_all, &view)?;
+    Ok(())
+}
+
+/// Build the view (tags, stale marks) the options ask for
+fn make_view(config: &Config, opts: &Options) -> Result<View> {
+    let mut view = View::default();
+    if opts.show_tags || opts.tag.is_some() {
+        add_tags(config, &mut view, opts.tag.as_deref())?;
+    }
+    if opts.stale {
+        add_stale_marks(config, &mut view, opts.show_all)?;
+    }
+    Ok(view)
+}
+
+/// Show tags next to entries, keeping only those tagged `filter` if given
+fn add_tags(config: &Config, view: &mut View, filter: Option<&str>) -> Result<()> {
+    let filter = filter.map(|t| t.trim_start_matches('#').to_lowercase());
+    let mut only = HashSet::new();
+    for (name, meta) in index::metadata(config)? {
+        if filter.as_ref().is_some_and(|t| !meta.tags.contains(t)) {
+            continue;
+        }
+        let path = config.entry_path(&name)?;
+        if !meta.tags.is_empty() {
+            let tags: Vec<String> = meta.tags.iter().map(|t| format!("#{}", t)).collect();
+            view.marks
+                .entry(path.clone())
+                .or_default()
+                .push(tags.join(" "));
+        }
+        only.insert(path);
+    }
+    if filter.is_some() {
+        view.only = Some(only);
+    }
+    Ok(())
+}
+
+/// Mark entries that are overdue (or whose expiry can't be read)
+fn add_stale_marks(config: &Config, view: &mut View, show_all: bool) -> Result<()> {
+    let today = expiry::today();
+    for e in expiring::scan(config, show_all, today)? {
+        let note = match e.due {
+            Ok(due) if due.date <= today => {
+                format!("⚠️  {}", expiring::describe(due.date, today))
+            }
+            Ok(_) => continue,
+            Err(err) => format!("⚠️  {:#}", err),
+        };
+        view.marks.entry(e.path).or_default().push(note);
+    }
+    Ok(())
+}
+
+/// A folder of a hidden-names vault, rebuilt from the manifest's names
+#[derive(Default)]
+struct Folder {
+    folders: BTreeMap<String, Folder>,
+    /// Entry name → its (ID-named) file
+    entries: BTreeMap<String, PathBuf>,
+}
+
+impl Folder {
+    fn insert(&mut self, name: &str, file: PathBuf) {
+        match name.split_once('/') {
+            Some((dir, rest)) => self
+                .folders
+                .entry(dir.to_string())
+                .or_default()
+                .insert(rest, file),
+            None => {
+                self.entries.insert(name.to_string(), file);
+            }
+        }
+    }
+
+    fn shows_anything(&self, view: &View) -> bool {
+        match &view.only {
+            Some(only) => {
+                self.entries.values().any(|f| only.contains(f))
+                    || self.folders.values().any(|f| f.shows_anything(view))
+            }
+            None => true,
+        }
+    }
+}
+
+/// `run` for vaults whose files are named by ID: the tree comes from the
+/// manifest instead of the directory
+fn run_hidden(
+    config: &Config,
+    path: Option<String>,
+    named: Vec<(String, PathBuf)>,
+    opts: &Options,
+) -> Result<()> {
+    let sub = path.as_deref().map(|p| p.trim_matches('/')).unwrap_or("");
+    let folder = format!("{}/", sub);
+    let mut root = Folder::default();
+    for (name, file) in named {
+        if !opts.show_all && index::is_hidden(&name) {
+            continue;
+        }
+        if sub.is_empty() {
+            root.insert(&name, file);
+        } else if name == sub {
+            println!("{}", sub);
+            return Ok(());
+        } else if let Some(rest) = name.strip_prefix(&folder) {
+            root.insert(rest, file);
+        }
+    }
+    if !sub.is_empty() && root.entries.is_empty() && root.folders.is_empty() {
+        anyhow::bail!("Entry not found: {}", sub);
+    }
+
+    if sub.is_empty() {
+        println!("📂 vault");
+    } else {
+        println!("📂 vault/{}", sub);
+    }
+    let view = make_view(config, opts)?;
+    print_folder(&root, vec![], &view);
     Ok(())
 }
 
-fn walk(dir: &Path, prefix_parts: Vec<bool>, show_all: bool, ext: &str) -> Result<()> {
+fn print_folder(folder: &Folder, prefix_parts: Vec<bool>, view: &View) {
+    let mut children: Vec<(&String, Option<&Folder>, Option<&PathBuf>)> = folder
+        .folders
+        .iter()
+        .filter(|(_, f)| f.shows_anything(view))
+        .map(|(n, f)| (n, Some(f), None))
+        .chain(
+            folder
+                .entries
+                .iter()
+                .filter(|(_, p)| view.only.as_ref().is_none_or(|only| only.contains(*p)))
+                .map(|(n, p)| (n, None, Some(p))),
+        )
+        .collect();
+    children.sort_by_key(|(n, _, _)| *n);
+    let last_idx = children.len().saturating_sub(1);
+
+    for (i, (name, sub, file)) in children.into_iter().enumerate() {
+        let is_last = i == last_idx;
+        for &draw_line in &prefix_parts {
+            print!("{}", if draw_line { "│   " } else { "    " });
+        }
+        let branch = if is_last { "└── " } else { "├── " };
+        match (sub, file.and_then(|f| view.marks.get(f))) {
+            (Some(sub), _) => {
+                println!("{}{}", branch, name);
+                let mut new_prefix = prefix_parts.clone();
+                new_prefix.push(!is_last);
+                print_folder(sub, new_prefix, view);
+            }
+            (None, Some(marks)) => println!("{}{}  {}", branch, name, marks.join("  ")),
+            (None, None) => println!("{}{}", branch, name),
+        }
+    }
+}
+
+fn walk(dir: &Path, prefix_parts: Vec<bool>, show_all: bool, view: &View) -> Result<()> {
     let mut entries = fs::read_dir(dir)?
         .filter_map(Result::ok)
         .filter(|e| {
             let file_name_os = e.file_name();
             let name = file_name_os.to_string_lossy();
-            // Always skip .git, and skip hidden unless --all
-            name != ".git" && (show_all || !name.starts_with('.'))
+            // Always skip .git and signatures, and skip hidden unless --all
+            name != ".git"
+                && name != signing::SIGNATURES_DIR
+                && (show_all || !name.starts_with('.'))
+        })
+        .filter(|e| match &view.only {
+            // folders stay if anything below them matched
+            Some(only) => only.iter().any(|p| p.starts_with(e.path())),
+            None => true,
         })
         .collect::<Vec<_>>();
 
@@ -81,10 +280,15 @@ fn walk(dir: &Path, prefix_parts: Vec<bool>, show_all: bool, ext: &str) -> Resul
             println!("{}{}", branch, name);
             let mut new_prefix = prefix_parts.clone();
             new_prefix.push(!is_last);
-            walk(&path, new_prefix, show_all, ext)?;
-        } else if name.ends_with(ext) {
-            let short = name.strip_suffix(ext).unwrap_or(&name);
-            println!("{}{}", branch, short);
+            walk(&path, new_prefix, show_all, view)?;
+        } else if let Some(short) = strip_entry_extension(&name)
+            && short != index::INDEX_NAME
+            && short != manifest::MANIFEST_NAME
+        {
+            match view.marks.get(&path) {
+                Some(marks) => println!("{}{}  {}", branch, short, marks.join("  ")),
+                None => println!("{}{}", branch, short),
+            }
         }
     }
 
@@ -94,7 +298,7 @@ fn walk(dir: &Path, prefix_parts: Vec<bool>, show_all: bool, ext: &str) -> Resul
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::Config;
+    use crate::config::{Config, SyncPolicy};
     use std::fs::File;
     use tempfile::TempDir;
 
@@ -106,6 +310,11 @@ mod tests {
             secret: tmp.path().join("secret.key"),
             crypto_extension: ext.into(),
             public_key_filename: "public.key".into(),
+            editor: None,
+            clipboard_timeout: 45,
+            sync: SyncPolicy::Manual,
+            vault: None,
+            remote: None,
         }
     }
 
@@ -114,7 +323,7 @@ mod tests {
         let tmp = TempDir::new().unwrap();
         let cfg = make_config(&tmp, "rage");
 
-        let err = run(&cfg, Some("nope".to_string()), false).unwrap_err();
+        let err = run(&cfg, Some("nope".to_string()), Options::default()).unwrap_err();
         assert_eq!(err.to_string(), "Entry not found: nope");
     }
 
@@ -125,7 +334,7 @@ mod tests {
         // create foo.rage
         File::create(tmp.path().join("foo.rage")).unwrap();
 
-        assert!(run(&cfg, Some("foo".to_string()), false).is_ok());
+        assert!(run(&cfg, Some("foo".to_string()), Options::default()).is_ok());
     }
 
     #[test]
@@ -136,7 +345,7 @@ mod tests {
         let sub = tmp.path().join("subdir");
         fs::create_dir_all(&sub).unwrap();
 
-        assert!(run(&cfg, Some("subdir".to_string()), false).is_ok());
+        assert!(run(&cfg, Some("subdir".to_string()), Options::default()).is_ok());
     }
 
     #[test]
@@ -144,20 +353,29 @@ mod tests {
         let tmp = TempDir::new().unwrap();
         // ext without dot
         let cfg1 = make_config(&tmp, "age");
-        let err1 = run(&cfg1, Some("x".to_string()), false).unwrap_err();
+        let err1 = run(&cfg1, Some("x".to_string()), Options::default()).unwrap_err();
         assert_eq!(err1.to_string(), "Entry not found: x");
 
         // ext with dot
         let cfg2 = make_config(&tmp, ".age");
-        let err2 = run(&cfg2, Some("x".to_string()), false).unwrap_err();
+        let err2 = run(&cfg2, Some("x".to_string()), Options::default()).unwrap_err();
         assert_eq!(err2.to_string(), "Entry not found: x");
 
         // create a.age and x.age
         File::create(tmp.path().join("a.age")).unwrap();
-        assert!(run(&cfg2, Some("a".to_string()), false).is_ok());
+        assert!(run(&cfg2, Some("a".to_string()), Options::default()).is_ok());
 
         File::create(tmp.path().join("b.age")).unwrap();
-        assert!(run(&cfg1, Some("b".to_string()), false).is_ok());
+        assert!(run(&cfg1, Some("b".to_string()), Options::default()).is_ok());
+    }
+
+    #[test]
+    fn finds_entries_with_the_other_extension() {
+        let tmp = TempDir::new().unwrap();
+        let cfg = make_config(&tmp, "rage");
+        File::create(tmp.path().join("legacy.age")).unwrap();
+
+        assert!(run(&cfg, Some("legacy".to_string()), Options::default()).is_ok());
     }
 
     #[test]
@@ -165,7 +383,7 @@ mod tests {
         let tmp = TempDir::new().unwrap();
         let cfg = make_config(&tmp, "rage");
         // empty directory
-        assert!(run(&cfg, None, false).is_ok());
+        assert!(run(&cfg, None, Options::default()).is_ok());
     }
 
     #[test]
@@ -177,8 +395,46 @@ mod tests {
         File::create(tmp.path().join(".h.rage")).unwrap();
 
         // without show_all, Ok and skip hidden
-        assert!(run(&cfg, None, false).is_ok());
+        assert!(run(&cfg, None, Options::default()).is_ok());
         // with show_all, Ok and include hidden in walk
-        assert!(run(&cfg, None, true).is_ok());
+        assert!(
+            run(
+                &cfg,
+                None,
+                Options {
+                    show_all: true,
+                    ..Default::default()
+                }
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn tag_filter_keeps_matching_entries() -> Result<()> {
+        use crate::crypto::{CryptoBackend, SecretString, set_decrypt_factory};
+        struct PlainBackend;
+        impl CryptoBackend for PlainBackend {
+            fn encrypt(&self, _r: &[String], _o: &Path, _p: &SecretString) -> Result<()> {
+                unreachable!()
+            }
+            fn decrypt(&self, _i: &Path, e: &Path) -> Result<SecretString> {
+                Ok(fs::read_to_string(e)?.into())
+            }
+        }
+        set_decrypt_factory(|_: &Path| Box::new(PlainBackend));
+
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp, "rage");
+        fs::create_dir_all(tmp.path().join("db"))?;
+        fs::write(tmp.path().join("db/main.rage"), "pw\ntags: prod, db")?;
+        fs::write(tmp.path().join("web.rage"), "pw\ntags: dev")?;
+
+        let mut view = View::default();
+        add_tags(&cfg, &mut view, Some("#PROD"))?;
+        let main = tmp.path().join("db/main.rage");
+        assert_eq!(view.only, Some(HashSet::from([main.clone()])));
+        assert_eq!(view.marks[&main], vec!["#prod #db"]);
+        Ok(())
     }
 }
@@ -1,7 +1,26 @@
+pub mod access;
+pub mod agent;
 pub mod archive;
+pub mod audit;
+pub mod convert;
 pub mod create;
+pub mod device;
+pub mod doctor;
 pub mod edit;
+pub mod expiring;
+pub mod export;
+pub mod find;
+pub mod fsck;
+pub mod generate;
+pub mod import;
 pub mod init;
+pub mod key;
 pub mod list;
+pub mod mv;
+pub mod names;
+pub mod reindex;
 pub mod remove;
 pub mod show;
+pub mod tags;
+pub mod vault;
+pub mod verify;
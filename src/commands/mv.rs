@@ -0,0 +1,253 @@
+use crate::config::Config;
+use crate::crypto;
+use crate::utils::{entries, git_ops, index, manifest};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Move or rename an entry or a whole folder.
+///
+/// Usage:
+///     passman mv <from> <to>
+///
+/// Entries keep their extension. When the move puts an entry under a
+/// different `.recipients` file, it is re-encrypted for its new recipients
+/// so the folder's access rules still hold; if that fails, the move is
+/// undone. In a hidden-names vault only the manifest changes; no file is
+/// touched.
+pub fn run(config: &Config, from: &str, to: &str) -> Result<()> {
+    let (from, to) = (from.trim_end_matches('/'), to.trim_end_matches('/'));
+    if manifest::is_enabled(&config.prefix) {
+        if from.is_empty() || to.starts_with(&format!("{}/", from)) {
+            anyhow::bail!("Cannot move {} into itself", from);
+        }
+        manifest::rename(config, from, to)?;
+        return finish(config, from, to);
+    }
+    let entry = config.entry_path(from)?;
+    let folder = config.prefix.join(from);
+
+    // (old file, new file) for every entry that moves
+    let (source, target, moves) = if entry.is_file() {
+        let file_name = entry.file_name().unwrap_or_default().to_string_lossy();
+        let ext = file_name.rsplit('.').next().unwrap_or_default();
+        let target = config.prefix.join(format!("{}.{}", to, ext));
+        (entry.clone(), target.clone(), vec![(entry, target)])
+    } else if folder.is_dir() && !from.is_empty() {
+        let target = config.prefix.join(to);
+        let moves = entries::collect(&folder, true)?
+            .into_iter()
+            .map(|e| {
+                let rel = e
+                    .path
+                    .strip_prefix(&folder)
+                    .unwrap_or(&e.path)
+                    .to_path_buf();
+                (e.path, target.join(rel))
+            })
+            .collect();
+        (folder, target, moves)
+    } else {
+        anyhow::bail!("No such entry or folder: {}", from);
+    };
+
+    if target.exists() || config.entry_path(to)?.exists() {
+        anyhow::bail!("{} already exists", to);
+    }
+    if target.starts_with(&source) {
+        anyhow::bail!("Cannot move {} into itself", from);
+    }
+
+    let before: Vec<Vec<String>> = moves
+        .iter()
+        .map(|(old, _)| config.recipients_for(old))
+        .collect::<Result<_>>()?;
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+    fs::rename(&source, &target)
+        .with_context(|| format!("Failed to move {} → {}", source.display(), target.display()))?;
+
+    if let Err(e) = reencrypt_moved(config, &moves, &before) {
+        return Err(match fs::rename(&target, &source) {
+            Ok(()) => e.context(format!("Nothing was moved; {} is unchanged", from)),
+            Err(_) => e.context(format!(
+                "{} was moved to {} but not re-encrypted; run `passman fsck --fix`",
+                from, to
+            )),
+        });
+    }
+    finish(config, from, to)
+}
+
+/// Re-encrypt the moved files whose recipients changed. On failure the
+/// files already re-encrypted get their old contents back, so the move can
+/// be undone as a whole.
+fn reencrypt_moved(
+    config: &Config,
+    moves: &[(PathBuf, PathBuf)],
+    before: &[Vec<String>],
+) -> Result<()> {
+    let mut done: Vec<(&Path, Vec<u8>)> = Vec::new();
+    for ((_, new), old_recipients) in moves.iter().zip(before) {
+        let original =
+            fs::read(new).with_context(|| format!("Failed to read {}", display(config, new)))?;
+        match reencrypt_if_needed(config, new, old_recipients) {
+            Ok(true) => done.push((new, original)),
+            Ok(false) => {}
+            Err(e) => {
+                for (file, contents) in done.into_iter().chain([(new.as_path(), original)]) {
+                    let _ = fs::write(file, contents);
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn finish(config: &Config, from: &str, to: &str) -> Result<()> {
+    index::rename(config, from, to);
+    println!("✅ Moved {} to {}", from, to);
+    git_ops::sync_vault_with_message(
+        &config.prefix,
+        config.sync,
+        &format!("Move {} to {}", from, to),
+    )
+}
+
+/// Re-encrypt `file` if its recipients are no longer `old_recipients`;
+/// returns whether it did
+fn reencrypt_if_needed(config: &Config, file: &Path, old_recipients: &[String]) -> Result<bool> {
+    let recipients = config.recipients_for(file)?;
+    if recipients == old_recipients {
+        return Ok(false);
+    }
+    let plaintext = crypto::decrypt(&config.secret, file)
+        .with_context(|| format!("Failed to decrypt {}", display(config, file)))?;
+    crypto::encrypt(&recipients, file, &plaintext)
+        .with_context(|| format!("Failed to re-encrypt {}", display(config, file)))?;
+    println!(
+        "🔑 Re-encrypted {} for its new recipients",
+        display(config, file)
+    );
+    Ok(true)
+}
+
+fn display(config: &Config, file: &Path) -> String {
+    file.strip_prefix(&config.prefix)
+        .unwrap_or(file)
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RECIPIENTS_FILENAME;
+    use crate::crypto::testing::{self, RecordingBackend};
+    use crate::crypto::{CryptoBackend, SecretString, set_encrypt_factory};
+    use tempfile::TempDir;
+
+    #[test]
+    fn renames_entries_keeping_their_extension() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::recording_vault(tmp.path());
+        fs::write(cfg.prefix.join("old.age"), "age1me|pw")?;
+
+        run(&cfg, "old", "web/new")?;
+
+        assert!(!cfg.prefix.join("old.age").exists());
+        assert_eq!(
+            fs::read_to_string(cfg.prefix.join("web/new.age"))?,
+            "age1me|pw",
+            "same recipients, so the file is moved untouched"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn moving_into_a_shared_folder_reencrypts() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::recording_vault(tmp.path());
+        fs::create_dir_all(cfg.prefix.join("mine/sub"))?;
+        fs::write(cfg.entry_path("mine/a")?, "age1me|a")?;
+        fs::write(cfg.entry_path("mine/sub/b")?, "age1me|b")?;
+        fs::create_dir_all(cfg.prefix.join("team"))?;
+        fs::write(
+            cfg.prefix.join("team").join(RECIPIENTS_FILENAME),
+            "age1me\nage1you\n",
+        )?;
+
+        run(&cfg, "mine", "team/mine")?;
+
+        assert!(!cfg.prefix.join("mine").exists());
+        assert_eq!(
+            fs::read_to_string(cfg.entry_path("team/mine/a")?)?,
+            "age1me,age1you|a"
+        );
+        assert_eq!(
+            fs::read_to_string(cfg.entry_path("team/mine/sub/b")?)?,
+            "age1me,age1you|b"
+        );
+        Ok(())
+    }
+
+    /// Re-encrypts fine until a file holds "b"
+    struct FailsOnB;
+    impl CryptoBackend for FailsOnB {
+        fn encrypt(&self, r: &[String], o: &Path, p: &SecretString) -> Result<()> {
+            if p.expose() == "b" {
+                fs::write(o, "garbage")?;
+                anyhow::bail!("disk full");
+            }
+            RecordingBackend.encrypt(r, o, p)
+        }
+        fn decrypt(&self, i: &Path, e: &Path) -> Result<SecretString> {
+            RecordingBackend.decrypt(i, e)
+        }
+    }
+
+    #[test]
+    fn failed_reencryption_undoes_the_move() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::recording_vault(tmp.path());
+        set_encrypt_factory(|_: &Path| Box::new(FailsOnB));
+        fs::create_dir_all(cfg.prefix.join("mine"))?;
+        fs::write(cfg.entry_path("mine/a")?, "age1me|a")?;
+        fs::write(cfg.entry_path("mine/b")?, "age1me|b")?;
+        fs::create_dir_all(cfg.prefix.join("team"))?;
+        fs::write(
+            cfg.prefix.join("team").join(RECIPIENTS_FILENAME),
+            "age1you\n",
+        )?;
+
+        let err = run(&cfg, "mine", "team/mine").unwrap_err();
+        assert!(err.to_string().contains("Nothing was moved"), "{:#}", err);
+        assert!(!cfg.prefix.join("team/mine").exists());
+        assert_eq!(fs::read_to_string(cfg.entry_path("mine/a")?)?, "age1me|a");
+        assert_eq!(fs::read_to_string(cfg.entry_path("mine/b")?)?, "age1me|b");
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_to_overwrite() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::recording_vault(tmp.path());
+        fs::write(cfg.entry_path("a")?, "age1me|a")?;
+        fs::write(cfg.entry_path("b")?, "age1me|b")?;
+
+        assert_eq!(
+            run(&cfg, "a", "b").unwrap_err().to_string(),
+            "b already exists"
+        );
+        assert_eq!(
+            run(&cfg, "nope", "c").unwrap_err().to_string(),
+            "No such entry or folder: nope"
+        );
+        assert_eq!(fs::read_to_string(cfg.entry_path("b")?)?, "age1me|b");
+        Ok(())
+    }
+}
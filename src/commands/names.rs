@@ -0,0 +1,216 @@
+use crate::cli::NamesAction;
+use crate::config::{Config, RECIPIENTS_FILENAME};
+use crate::utils::{entries, git_ops, manifest};
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Switch a vault between readable file names and random file IDs.
+///
+/// Usage:
+///     passman names hide
+///     passman names reveal
+///
+/// `hide` renames every entry to `<random id>.<ext>` at the vault root and
+/// records the real names in an encrypted `.passman-manifest`, so a copy of
+/// the repository no longer shows which accounts you have. `reveal` undoes
+/// it. Names already committed stay in the git history either way.
+pub fn run(config: &Config, action: NamesAction) -> Result<()> {
+    match action {
+        NamesAction::Hide => hide(config),
+        NamesAction::Reveal => reveal(config),
+    }
+}
+
+fn hide(config: &Config) -> Result<()> {
+    if manifest::is_enabled(&config.prefix) {
+        anyhow::bail!("This vault already hides its entry names");
+    }
+    // every file sits at the root afterwards, so per-folder recipients
+    // would silently stop applying
+    if let Some(folder) = folder_recipients(&config.prefix) {
+        anyhow::bail!(
+            "{} sets its own recipients; hidden names need a single recipient list",
+            folder
+        );
+    }
+
+    // the manifest is written before any file moves, so a failure never
+    // leaves files under IDs nothing maps back to a name
+    let mut names = BTreeMap::new();
+    let mut moves = Vec::new();
+    for e in entries::collect(&config.prefix, true)? {
+        let id = manifest::new_id();
+        let ext = e.path.extension().unwrap_or_default().to_string_lossy();
+        let target = config.prefix.join(format!("{}.{}", id, ext));
+        moves.push((e.name.clone(), e.path, target));
+        names.insert(e.name, id);
+    }
+    manifest::save(config, &names)?;
+
+    for (i, (name, file, target)) in moves.iter().enumerate() {
+        if let Err(e) = fs::rename(file, target) {
+            for (_, file, target) in &moves[..i] {
+                let _ = fs::rename(target, file);
+            }
+            manifest::remove(&config.prefix)?;
+            return Err(e).with_context(|| {
+                format!(
+                    "Failed to move {} → {}; the vault is unchanged",
+                    name,
+                    target.display()
+                )
+            });
+        }
+    }
+    remove_empty_dirs(&config.prefix)?;
+
+    println!("🙈 Hid the names of {} entries", names.len());
+    println!("   Names committed before now are still in the git history.");
+    git_ops::sync_vault_with_message(&config.prefix, config.sync, "Hide entry names")
+}
+
+fn reveal(config: &Config) -> Result<()> {
+    let Some(named) = manifest::entries(&config.prefix)? else {
+        anyhow::bail!("This vault does not hide its entry names");
+    };
+
+    let moves: Vec<_> = named
+        .into_iter()
+        .map(|(name, file)| {
+            let ext = file.extension().unwrap_or_default().to_string_lossy();
+            let target = config.prefix.join(format!("{}.{}", name, ext));
+            (name, file, target)
+        })
+        .collect();
+    if let Some((name, ..)) = moves.iter().find(|(_, _, target)| target.exists()) {
+        anyhow::bail!("{} already exists", name);
+    }
+
+    for (name, file, target) in &moves {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        fs::rename(file, target).with_context(|| format!("Failed to restore {}", name))?;
+    }
+    manifest::remove(&config.prefix)?;
+
+    println!("👀 Restored the names of {} entries", moves.len());
+    git_ops::sync_vault_with_message(&config.prefix, config.sync, "Reveal entry names")
+}
+
+/// The first folder below the root with its own recipients file
+fn folder_recipients(prefix: &Path) -> Option<String> {
+    WalkDir::new(prefix)
+        .min_depth(2)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+        .filter_map(Result::ok)
+        .find(|e| e.file_name() == RECIPIENTS_FILENAME)
+        .and_then(|e| {
+            let folder = e.path().parent()?.strip_prefix(prefix).ok()?;
+            Some(folder.display().to_string())
+        })
+}
+
+/// Remove folders left empty once their entries moved, deepest first
+fn remove_empty_dirs(prefix: &Path) -> Result<()> {
+    let dirs: Vec<_> = WalkDir::new(prefix)
+        .min_depth(1)
+        .contents_first(true)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_dir())
+        .collect();
+    for dir in dirs {
+        if fs::read_dir(dir.path())?.next().is_none() {
+            fs::remove_dir(dir.path())
+                .with_context(|| format!("Failed to remove {}", dir.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{CryptoBackend, SecretString, set_encrypt_factory, testing};
+    use tempfile::TempDir;
+
+    fn make_config(tmp: &TempDir) -> Config {
+        let cfg = testing::plain_vault(tmp.path());
+        crate::config::register(&cfg);
+        cfg
+    }
+
+    #[test]
+    fn hide_and_reveal_round_trip() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        fs::create_dir_all(cfg.prefix.join("bank/old"))?;
+        fs::write(cfg.prefix.join("bank/old/chase.age"), "a")?;
+        fs::write(cfg.prefix.join("mail.rage"), "b")?;
+
+        run(&cfg, NamesAction::Hide)?;
+        assert!(!cfg.prefix.join("bank").exists());
+        let chase = cfg.entry_path("bank/old/chase")?;
+        assert!(chase.to_string_lossy().ends_with(".age"));
+        assert_eq!(fs::read_to_string(&chase)?, "a");
+        let names: Vec<String> = entries::collect(&cfg.prefix, false)?
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, vec!["bank/old/chase", "mail"]);
+
+        run(&cfg, NamesAction::Reveal)?;
+        assert!(!manifest::is_enabled(&cfg.prefix));
+        assert_eq!(
+            fs::read_to_string(cfg.prefix.join("bank/old/chase.age"))?,
+            "a"
+        );
+        assert_eq!(fs::read_to_string(cfg.prefix.join("mail.rage"))?, "b");
+        Ok(())
+    }
+
+    struct BrokenBackend;
+    impl CryptoBackend for BrokenBackend {
+        fn encrypt(&self, _r: &[String], _o: &Path, _p: &SecretString) -> Result<()> {
+            anyhow::bail!("no recipients reachable")
+        }
+        fn decrypt(&self, _i: &Path, _e: &Path) -> Result<SecretString> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn hide_moves_nothing_when_the_manifest_cant_be_saved() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        set_encrypt_factory(|_: &Path| Box::new(BrokenBackend));
+        fs::create_dir_all(cfg.prefix.join("bank"))?;
+        fs::write(cfg.prefix.join("bank/chase.rage"), "a")?;
+
+        assert!(run(&cfg, NamesAction::Hide).is_err());
+        assert_eq!(fs::read_to_string(cfg.prefix.join("bank/chase.rage"))?, "a");
+        assert!(!manifest::is_enabled(&cfg.prefix));
+        Ok(())
+    }
+
+    #[test]
+    fn hide_refuses_folder_recipients() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        fs::create_dir_all(cfg.prefix.join("team"))?;
+        fs::write(cfg.prefix.join("team").join(RECIPIENTS_FILENAME), "age1x\n")?;
+        fs::write(cfg.prefix.join("team/db.rage"), "a")?;
+
+        let err = run(&cfg, NamesAction::Hide).unwrap_err();
+        assert!(err.to_string().starts_with("team sets its own recipients"));
+        assert!(cfg.prefix.join("team/db.rage").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use crate::config::Config;
+use crate::utils::index;
+use anyhow::Result;
+
+/// Rebuild the encrypted search index from scratch.
+///
+/// Usage:
+///     passman reindex
+///
+/// Every entry is decrypted once and its path, field names, username, URL,
+/// tags and modification time are written to `.passman-index` in the vault.
+/// From then on `new`, `edit`, `mv` and `remove` keep it up to date, and
+/// `find --meta`, `tags` and `list --tag` only need to decrypt the index.
+pub fn run(config: &Config) -> Result<()> {
+    let index = index::rebuild(config)?;
+    println!(
+        "🗂️  Indexed {} entries into {}",
+        index.entries.len(),
+        index::index_path(config)?.display()
+    );
+    Ok(())
+}
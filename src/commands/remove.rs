@@ -1,11 +1,11 @@
 use crate::config::Config;
-use crate::utils::git_ops;
+use crate::utils::{git_ops, index};
 use anyhow::{Context, Result};
 use std::fs;
 
 /// Delete a stored password entry
 pub fn run(config: &Config, path: String) -> Result<()> {
-    let file_path = config.prefix.join(format!("{}.rage", path));
+    let file_path = config.entry_path(&path)?;
 
     if !file_path.exists() {
         anyhow::bail!("No such entry: {}", file_path.display());
@@ -13,10 +13,11 @@ pub fn run(config: &Config, path: String) -> Result<()> {
 
     fs::remove_file(&file_path)
         .with_context(|| format!("Failed to delete file {:?}", file_path))?;
+    index::forget(config, &path);
 
     // run from the vault dir ie the git root,
     // to pick up the deletion
-    git_ops::sync_vault(&config.prefix)?;
+    git_ops::sync_vault(&config.prefix, config.sync)?;
 
     println!("✅ Removed entry `{}`", path);
     Ok(())
@@ -25,7 +26,7 @@ pub fn run(config: &Config, path: String) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::Config;
+    use crate::config::{Config, SyncPolicy};
     use anyhow::Result;
     use std::fs::File;
     use tempfile::TempDir;
@@ -38,6 +39,11 @@ mod tests {
             secret: tmp.path().join("secret.key"),
             crypto_extension: "rage".into(),
             public_key_filename: "public.key".into(),
+            editor: None,
+            clipboard_timeout: 45,
+            sync: SyncPolicy::Manual,
+            vault: None,
+            remote: None,
         }
     }
 
@@ -51,6 +57,19 @@ mod tests {
         assert_eq!(err.to_string(), expected);
     }
 
+    #[test]
+    fn age_entry_removed_from_rage_vault() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+
+        let path = tmp.path().join("legacy.age");
+        File::create(&path)?;
+
+        run(&cfg, "legacy".to_string())?;
+        assert!(!path.exists(), "file should have been deleted");
+        Ok(())
+    }
+
     #[test]
     fn existing_entry_removed_successfully() -> Result<()> {
         let tmp = TempDir::new()?;
@@ -1,30 +1,31 @@
 use crate::config::Config;
-use crate::crypto;
+use crate::crypto::{self, SecretString};
 use crate::utils::qr::print_qr;
 use anyhow::Result;
 
 /// Show a password entry, optionally only a single line.
 pub fn run(config: &Config, path: String, qrcode: bool, line: Option<usize>) -> Result<()> {
-    let file_path = config.entry_path(&path);
+    let file_path = config.entry_path(&path)?;
     if !file_path.exists() {
         anyhow::bail!("No such password: {}", file_path.display());
     }
 
     let decrypted = crypto::decrypt(&config.secret, &file_path)?;
 
-    let output = match line {
-        None | Some(0) => decrypted.clone(),
+    let output: SecretString = match line {
+        None | Some(0) => decrypted,
         Some(n) => decrypted
+            .expose()
             .lines()
             .nth(n - 1)
             .ok_or_else(|| anyhow::anyhow!("File {} has fewer than {} lines", path, n))?
-            .to_string(),
+            .into(),
     };
 
     if qrcode {
-        print_qr(&output)?;
+        print_qr(output.expose())?;
     } else {
-        println!("{}", output);
+        println!("{}", output.expose());
     }
 
     Ok(())
@@ -33,7 +34,7 @@ pub fn run(config: &Config, path: String, qrcode: bool, line: Option<usize>) ->
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::Config;
+    use crate::config::{Config, SyncPolicy};
     use crate::crypto::{CryptoBackend, set_decrypt_factory};
     use anyhow::Result;
     use std::fs::File;
@@ -48,6 +49,11 @@ mod tests {
             secret: tmp.path().to_path_buf(), // still safe to point here
             crypto_extension: "rage".into(),
             public_key_filename: "public.key".into(),
+            editor: None,
+            clipboard_timeout: 45,
+            sync: SyncPolicy::Manual,
+            vault: None,
+            remote: None,
         }
     }
 
@@ -57,9 +63,9 @@ mod tests {
     impl CryptoBackend for MockBackend {
         fn encrypt(
             &self,
-            _recipient: &str,
+            _recipients: &[String],
             _output_file: &std::path::Path,
-            _plaintext: &str,
+            _plaintext: &SecretString,
         ) -> Result<()> {
             unreachable!("encrypt not used in show::run tests");
         }
@@ -68,7 +74,7 @@ mod tests {
             &self,
             _identity_file: &std::path::Path,
             _encrypted_file: &std::path::Path,
-        ) -> Result<String> {
+        ) -> Result<SecretString> {
             // simulate a file whose plaintext is exactly three lines
             Ok("first line\nsecond line\nthird line".into())
         }
@@ -87,7 +93,7 @@ mod tests {
         let err = run(&cfg, "does_not_exist".into(), false, None).unwrap_err();
         let expected = format!(
             "No such password: {}",
-            cfg.entry_path("does_not_exist").display()
+            cfg.entry_path("does_not_exist")?.display()
         );
         assert_eq!(err.to_string(), expected);
         Ok(())
@@ -101,7 +107,7 @@ mod tests {
         let cfg = make_test_config(&tmp);
 
         // create the file with the proper extension
-        let entry = cfg.entry_path("mypw");
+        let entry = cfg.entry_path("mypw")?;
         File::create(&entry)?.write_all(b"")?;
 
         // should not error
@@ -116,7 +122,7 @@ mod tests {
         let tmp = tempfile::tempdir()?;
         let cfg = make_test_config(&tmp);
 
-        let entry = cfg.entry_path("mypw");
+        let entry = cfg.entry_path("mypw")?;
         File::create(&entry)?.write_all(b"")?;
 
         // asking for line 2 should succeed ("second line")
@@ -131,7 +137,7 @@ mod tests {
         let tmp = tempfile::tempdir()?;
         let cfg = make_test_config(&tmp);
 
-        let entry = cfg.entry_path("mypw");
+        let entry = cfg.entry_path("mypw")?;
         File::create(&entry)?; // empty file
 
         let err = run(&cfg, "mypw".into(), false, Some(10)).unwrap_err();
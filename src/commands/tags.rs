@@ -0,0 +1,77 @@
+use crate::config::Config;
+use crate::utils::index::{self, Meta};
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+/// Show every tag with the number of entries carrying it.
+///
+/// Usage:
+///     passman tags [--all]
+///
+/// Tags come from the `tags:` field of each entry. Once `passman reindex`
+/// has built the search index, this only decrypts the index (plus any
+/// entries that changed behind its back) instead of every entry.
+pub fn run(config: &Config, show_all: bool) -> Result<()> {
+    let entries = index::metadata(config)?;
+
+    let counts = count(&entries, show_all);
+    if counts.is_empty() {
+        println!("No tagged entries; add a `tags:` line to an entry to tag it");
+        return Ok(());
+    }
+    let width = counts
+        .iter()
+        .map(|(t, _)| t.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (tag, n) in counts {
+        println!("{:<width$}  {}", tag, n);
+    }
+    Ok(())
+}
+
+/// Tags with their entry counts, most used first
+pub fn count(entries: &BTreeMap<String, Meta>, show_all: bool) -> Vec<(String, usize)> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for (name, meta) in entries {
+        if !show_all && index::is_hidden(name) {
+            continue;
+        }
+        for tag in &meta.tags {
+            *counts.entry(tag).or_insert(0) += 1;
+        }
+    }
+    let mut counts: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(t, n)| (t.to_string(), n))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(tags: &[&str]) -> Meta {
+        Meta {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn counts_most_used_first() {
+        let entries = BTreeMap::from([
+            ("a".to_string(), meta(&["web", "prod"])),
+            ("b".to_string(), meta(&["prod"])),
+            (".old/c".to_string(), meta(&["prod", "legacy"])),
+        ]);
+        assert_eq!(
+            count(&entries, false),
+            vec![("prod".to_string(), 2), ("web".to_string(), 1)]
+        );
+        assert_eq!(count(&entries, true)[0], ("prod".to_string(), 3));
+        assert_eq!(count(&entries, true).len(), 3);
+    }
+}
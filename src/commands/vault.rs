@@ -0,0 +1,209 @@
+use crate::cli::VaultAction;
+use crate::config::{self, DEFAULT_VAULT_NAME, FileConfig, VaultConfig};
+use crate::utils::secure_fs;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Manage the named vaults stored in the config file.
+///
+/// Usage:
+///     passman vault list
+///     passman vault add <name> --dir <path> [--identity F] [--recipients F] [--remote URL]
+///     passman vault default <name>
+pub fn run(action: VaultAction) -> Result<()> {
+    run_with(&config::config_file_path(), action)
+}
+
+fn run_with(config_path: &Path, action: VaultAction) -> Result<()> {
+    let mut file = FileConfig::load(config_path)?;
+
+    match action {
+        VaultAction::List => {
+            println!("📂 vaults ({})", config_path.display());
+            if file.has_unnamed_vault() {
+                let marker = if file.default_vault.is_none() {
+                    "*"
+                } else {
+                    " "
+                };
+                let dir = file
+                    .base_dir
+                    .as_ref()
+                    .map(|d| d.display().to_string())
+                    .unwrap_or_else(|| "<XDG data dir>".into());
+                println!("{} {:<12} {}", marker, DEFAULT_VAULT_NAME, dir);
+            }
+            for (name, vault) in &file.vaults {
+                let marker = if file.default_vault.as_deref() == Some(name) {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{} {:<12} {}", marker, name, vault.base_dir.display());
+            }
+            return Ok(());
+        }
+
+        VaultAction::Add {
+            name,
+            dir,
+            identity,
+            recipients,
+            remote,
+        } => {
+            if name.is_empty() || name.contains(['/', ':']) || name.starts_with('.') {
+                anyhow::bail!("Invalid vault name `{}`", name);
+            }
+            if name == DEFAULT_VAULT_NAME {
+                anyhow::bail!("`{}` is reserved for the unnamed vault", DEFAULT_VAULT_NAME);
+            }
+            if file.vaults.contains_key(&name) {
+                anyhow::bail!("Vault `{}` already exists", name);
+            }
+
+            let vault_dir = dir.join("vault");
+            secure_fs::private_dir(&vault_dir).with_context(|| {
+                format!("Failed to create vault directory {}", vault_dir.display())
+            })?;
+
+            file.vaults.insert(
+                name.clone(),
+                VaultConfig {
+                    base_dir: dir,
+                    identity,
+                    recipients,
+                    remote,
+                },
+            );
+            println!("✅ Added vault `{}`", name);
+            println!(
+                "   Run `passman --vault {} init` to create its keys and git repo.",
+                name
+            );
+        }
+
+        VaultAction::Default { name } => {
+            if name == DEFAULT_VAULT_NAME {
+                file.default_vault = None;
+            } else if file.vaults.contains_key(&name) {
+                file.default_vault = Some(name.clone());
+            } else {
+                anyhow::bail!("Unknown vault `{}` (see `passman vault list`)", name);
+            }
+            println!("✅ Default vault is now `{}`", name);
+        }
+    }
+
+    file.save(config_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn add(name: &str, dir: PathBuf) -> VaultAction {
+        VaultAction::Add {
+            name: name.into(),
+            dir,
+            identity: None,
+            recipients: None,
+            remote: Some("git@host:team.git".into()),
+        }
+    }
+
+    #[test]
+    fn add_creates_vault_and_records_it() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg_path = tmp.path().join("config.toml");
+
+        run_with(&cfg_path, add("team", tmp.path().join("team")))?;
+
+        assert!(tmp.path().join("team/vault").is_dir());
+        let file = FileConfig::load(&cfg_path)?;
+        assert_eq!(file.vaults["team"].base_dir, tmp.path().join("team"));
+        assert_eq!(
+            file.vaults["team"].remote.as_deref(),
+            Some("git@host:team.git")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_and_reserved_names_error() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg_path = tmp.path().join("config.toml");
+        run_with(&cfg_path, add("team", tmp.path().join("team")))?;
+
+        let err = run_with(&cfg_path, add("team", tmp.path().join("x"))).unwrap_err();
+        assert_eq!(err.to_string(), "Vault `team` already exists");
+
+        let err = run_with(&cfg_path, add("default", tmp.path().join("x"))).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`default` is reserved for the unnamed vault"
+        );
+
+        let err = run_with(&cfg_path, add("a/b", tmp.path().join("x"))).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid vault name `a/b`");
+        Ok(())
+    }
+
+    #[test]
+    fn default_switches_and_resets() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg_path = tmp.path().join("config.toml");
+        run_with(&cfg_path, add("team", tmp.path().join("team")))?;
+
+        run_with(
+            &cfg_path,
+            VaultAction::Default {
+                name: "team".into(),
+            },
+        )?;
+        let file = FileConfig::load(&cfg_path)?;
+        assert_eq!(file.default_vault.as_deref(), Some("team"));
+        assert!(!file.has_unnamed_vault());
+
+        run_with(
+            &cfg_path,
+            VaultAction::Default {
+                name: "default".into(),
+            },
+        )?;
+        assert_eq!(FileConfig::load(&cfg_path)?.default_vault, None);
+        Ok(())
+    }
+
+    #[test]
+    fn default_unknown_vault_errors() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg_path = tmp.path().join("config.toml");
+
+        let err = run_with(
+            &cfg_path,
+            VaultAction::Default {
+                name: "nope".into(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown vault `nope` (see `passman vault list`)"
+        );
+        assert!(!cfg_path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn list_does_not_write_config() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg_path = tmp.path().join("config.toml");
+
+        run_with(&cfg_path, VaultAction::List)?;
+        assert!(!cfg_path.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use crate::config::Config;
+use crate::utils::signing;
+use anyhow::Result;
+
+/// Check that every file in the vault is vouched for by a trusted device.
+///
+/// Usage:
+///     passman verify [--trust <key>] [--sign]
+///
+/// Each device keeps a signed manifest of file hashes, adding the files it
+/// changed whenever it syncs; `verify` (and every sync) compares the files
+/// against the manifests of devices listed in `<base_dir>/trusted-signers`,
+/// and a sync stops while anything doesn't verify. `--trust` adds the
+/// device with that signing key to the list; device names are whatever
+/// each device calls itself, so they aren't accepted. `--sign` vouches
+/// for the vault as it is now, e.g. after reviewing an unsigned change.
+pub fn run(config: &Config, trust: Option<String>, sign: bool) -> Result<()> {
+    if let Some(wanted) = trust {
+        let report = signing::verify(config)?;
+        let Some(signer) = report.untrusted.iter().find(|s| s.key == wanted) else {
+            let named: Vec<&str> = report
+                .untrusted
+                .iter()
+                .filter(|s| s.device == wanted)
+                .map(|s| s.key.as_str())
+                .collect();
+            if !named.is_empty() {
+                anyhow::bail!(
+                    "Any device can call itself `{}`; trust it by key instead: {}",
+                    wanted,
+                    named.join(", ")
+                );
+            }
+            anyhow::bail!(
+                "No untrusted device with key `{}` has signed this vault",
+                wanted
+            );
+        };
+        signing::trust(config, signer)?;
+        println!("🔏 Now trusting `{}` ({})", signer.device, signer.key);
+    }
+    if sign {
+        signing::sign(config)?;
+        println!("🔏 Signed the vault as `{}`", signing::device_name());
+    }
+
+    let report = signing::verify(config)?;
+    report.print();
+    if !report.is_clean() {
+        anyhow::bail!("Vault contents could not be verified");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn devices_are_trusted_by_key_not_name() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let laptop = test_config(&tmp.path().join("laptop"));
+        let other = Config {
+            prefix: laptop.prefix.clone(),
+            ..test_config(&tmp.path().join("other"))
+        };
+        fs::write(laptop.prefix.join("a.rage"), "ciphertext")?;
+        signing::sign(&other)?;
+        let name = signing::device_name();
+
+        let err = run(&laptop, Some(name), false).unwrap_err();
+        assert!(err.to_string().contains("trust it by key"), "{}", err);
+        assert!(signing::trusted(&laptop)?.is_empty());
+
+        let key = signing::public_key(&signing::device_key(&other)?);
+        run(&laptop, Some(key), false)?;
+        assert_eq!(signing::trusted(&laptop)?.len(), 1);
+        Ok(())
+    }
+}
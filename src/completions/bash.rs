@@ -8,26 +8,50 @@ use std::{
 
 use crate::cli::Cli;
 
+/// Shell test for a hidden-names vault, whose files are named by ID so
+/// names have to come from `passman list --flat`
+fn hidden_names_test(vault_dir: &str) -> String {
+    format!(
+        r#"compgen -G "{vault}/.passman-manifest.*" >/dev/null"#,
+        vault = vault_dir
+    )
+}
+
 /// complete directories under vault, excluding `.git` and its contents
-fn vault_list_pipeline(vault_dir: &str) -> String {
+fn vault_list_pipeline(bin_name: &str, vault_dir: &str) -> String {
     let find_cmd = format!(
         r#"cd "{vault}" && \
         find . -mindepth 1 \
         \( -path './.git' -o -path './.git/*' \) -prune -o -type d -printf '%P/\n' | sort -u"#,
         vault = vault_dir
     );
-    format!("dirs=$({})", find_cmd)
+    let names_cmd = format!(
+        r#"{bin} list --flat 2>/dev/null | \
+        awk -F/ '{{p=""; for (i = 1; i < NF; i++) {{ p = p $i "/"; print p }}}}' | sort -u"#,
+        bin = bin_name
+    );
+    format!(
+        "if {}; then dirs=$({}); else dirs=$({}); fi",
+        hidden_names_test(vault_dir),
+        names_cmd,
+        find_cmd
+    )
 }
 
 /// complete files under vault, stripping .rage and .age extensions
-fn vault_show_pipeline(vault_dir: &str) -> String {
+fn vault_show_pipeline(bin_name: &str, vault_dir: &str) -> String {
     let find_cmd = format!(
         r#"cd "{vault}" && \
-        find . \( -name '*.rage' -o -name '*.age' \) -printf '%P\n' | \
+        find . \( -name '*.rage' -o -name '*.age' \) ! -name '.passman-*' -printf '%P\n' | \
         sed -e 's|\.rage$||' -e 's|\.age$||' | sort -u"#,
         vault = vault_dir
     );
-    format!("files=$({})", find_cmd)
+    format!(
+        "if {}; then files=$({} list --flat 2>/dev/null); else files=$({}); fi",
+        hidden_names_test(vault_dir),
+        bin_name,
+        find_cmd
+    )
 }
 
 /// Generate & install both the base clap completions and
@@ -87,8 +111,8 @@ pub fn install_file_path_completion(bin_name: &str) -> Result<()> {
     };
     if !already {
         let mut f = OpenOptions::new().append(true).open(&completion_file)?;
-        let list_pipe = vault_list_pipeline(&vault_dir_str);
-        let show_pipe = vault_show_pipeline(&vault_dir_str);
+        let list_pipe = vault_list_pipeline(bin_name, &vault_dir_str);
+        let show_pipe = vault_show_pipeline(bin_name, &vault_dir_str);
         let wrapper = format!(
             r#"
 # === `{bin}` custom wrapper for file-based completions ===
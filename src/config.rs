@@ -1,10 +1,32 @@
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::crypto::detect_backend;
+use crate::crypto::{detect_backend, ssh};
+use crate::utils::secure_fs;
 
+/// Default number of seconds a copied secret stays on the clipboard
+pub const DEFAULT_CLIPBOARD_TIMEOUT: u64 = 45;
+
+/// Extensions an encrypted entry may have; both backends share one format
+pub const ENTRY_EXTENSIONS: [&str; 2] = ["age", "rage"];
+
+/// Per-folder recipients file; overrides the vault's public key for every
+/// entry beneath the folder it sits in
+pub const RECIPIENTS_FILENAME: &str = ".recipients";
+
+/// Comment naming the recipient on the next line of a recipients file, as
+/// `passman device` writes them
+pub const DEVICE_COMMENT: &str = "# device:";
+
+/// Name reported for the vault described by the top-level config keys
+pub const DEFAULT_VAULT_NAME: &str = "default";
+
+#[derive(Clone)]
 pub struct Config {
     /// Where we keep our vault, key, etc.
     pub base_dir: PathBuf,
@@ -20,28 +42,329 @@ pub struct Config {
 
     /// Name of the public key file, e.g. "public.key"
     pub public_key_filename: String,
+
+    /// Editor from the config file; `$EDITOR` still takes precedence
+    pub editor: Option<String>,
+
+    /// Seconds before a copied secret is cleared from the clipboard
+    pub clipboard_timeout: u64,
+
+    /// What to do with git after the vault changes
+    pub sync: SyncPolicy,
+
+    /// Name of the selected `[vaults.<name>]` profile, if any
+    pub vault: Option<String>,
+
+    /// Git remote configured for this vault, used by `init`
+    pub remote: Option<String>,
+}
+
+/// How much git work to do after a change to the vault
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncPolicy {
+    /// Commit, pull --rebase and push
+    #[default]
+    Auto,
+    /// Commit locally, never touch the remote
+    Commit,
+    /// Leave git alone entirely
+    Manual,
+}
+
+/// Where a resolved config value came from
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Default,
+    File,
+    Env,
+    Flag,
+    Detected,
+}
+
+/// Per-key provenance of a loaded `Config`
+pub type Sources = BTreeMap<&'static str, Source>;
+
+/// The persisted settings in `$XDG_CONFIG_HOME/passman/config.toml`.
+/// Every key is optional; anything missing falls back to the defaults.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    /// "age" or "rage"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_dir: Option<PathBuf>,
+
+    /// Path to the private identity file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<PathBuf>,
+
+    /// Public key filename, relative to `base_dir`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clipboard_timeout: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync: Option<SyncPolicy>,
+
+    /// Vault used when `--vault` isn't given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_vault: Option<String>,
+
+    /// Named vault profiles, e.g. `[vaults.team]`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vaults: BTreeMap<String, VaultConfig>,
+}
+
+/// One `[vaults.<name>]` table
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct VaultConfig {
+    pub base_dir: PathBuf,
+
+    /// Path to the private identity file; defaults to `<base_dir>/private.<backend>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<PathBuf>,
+
+    /// Recipients (public key) filename, relative to `base_dir`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipients: Option<String>,
+
+    /// Git remote URL for the vault repository
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+}
+
+impl FileConfig {
+    /// Read the config file, returning defaults when it doesn't exist
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&raw)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// Write the config file, creating its directory if needed
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let raw = toml::to_string_pretty(self).context("Failed to serialize config")?;
+        fs::write(path, raw)
+            .with_context(|| format!("Failed to write config file {}", path.display()))
+    }
+
+    /// The top-level keys describe a vault of their own unless every
+    /// command has been pointed at a named one instead
+    pub fn has_unnamed_vault(&self) -> bool {
+        self.default_vault.is_none() || self.base_dir.is_some()
+    }
+
+    /// Record a loaded `Config` so the next run doesn't need to detect anything.
+    /// Paths go into the vault's own table when a named vault is selected.
+    pub fn record(&mut self, cfg: &Config) {
+        self.backend = Some(cfg.crypto_extension.clone());
+        self.clipboard_timeout = Some(cfg.clipboard_timeout);
+        self.sync = Some(cfg.sync);
+
+        match &cfg.vault {
+            Some(name) => {
+                let vault = self.vaults.entry(name.clone()).or_default();
+                vault.base_dir = cfg.base_dir.clone();
+                vault.identity = Some(cfg.secret.clone());
+                vault.recipients = Some(cfg.public_key_filename.clone());
+            }
+            None => {
+                self.base_dir = Some(cfg.base_dir.clone());
+                self.identity = Some(cfg.secret.clone());
+                self.public_key = Some(cfg.public_key_filename.clone());
+            }
+        }
+    }
 }
 
 impl Config {
-    /// Return the path to the encrypted entry for a given name
-    pub fn entry_path(&self, name: &str) -> PathBuf {
-        self.prefix
-            .join(format!("{}.{}", name, self.crypto_extension))
+    /// Return the path to the encrypted entry for a given name. An existing
+    /// file with either extension is found, so vaults mixing `.age` and
+    /// `.rage` entries work; new entries get `crypto_extension`.
+    ///
+    /// In hidden-names mode the name is looked up in the vault's manifest
+    /// and the file is the entry's random ID instead.
+    pub fn entry_path(&self, name: &str) -> Result<PathBuf> {
+        Ok(match crate::utils::manifest::id_for(self, name)? {
+            Some(id) => self.file_path(&id),
+            None => self.file_path(name),
+        })
+    }
+
+    /// `<prefix>/<stem>.<ext>` for whichever extension exists on disk
+    fn file_path(&self, stem: &str) -> PathBuf {
+        let preferred = self.crypto_extension.trim_start_matches('.');
+        let others = ENTRY_EXTENSIONS.into_iter().filter(|ext| *ext != preferred);
+        std::iter::once(preferred)
+            .chain(others)
+            .map(|ext| self.prefix.join(format!("{}.{}", stem, ext)))
+            .find(|path| path.exists())
+            .unwrap_or_else(|| self.prefix.join(format!("{}.{}", stem, preferred)))
+    }
+
+    /// The file `recipients_for` reads for an entry
+    pub fn recipients_file_for(&self, entry: &Path) -> PathBuf {
+        let mut dir = entry.parent();
+        while let Some(d) = dir {
+            if !d.starts_with(&self.prefix) {
+                break;
+            }
+            let candidate = d.join(RECIPIENTS_FILENAME);
+            if candidate.is_file() {
+                return candidate;
+            }
+            dir = d.parent();
+        }
+        self.base_dir.join(&self.public_key_filename)
     }
 
-    /// Read and trim the public key file from disk
-    pub fn read_public(&self) -> Result<String> {
+    /// Read the vault's public key file, one recipient per line
+    pub fn read_recipients(&self) -> Result<Vec<String>> {
         let path = self.base_dir.join(&self.public_key_filename);
-        fs::read_to_string(&path)
-            .with_context(|| format!("Failed to read public key: {}", path.display()))
-            .map(|s| s.trim().to_string())
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read public key: {}", path.display()))?;
+        let recipients = parse_recipients(&raw);
+        if recipients.is_empty() {
+            anyhow::bail!("No recipients in {}", path.display());
+        }
+        Ok(recipients)
+    }
+
+    /// Recipients for an entry: the nearest `.recipients` file between the
+    /// entry's folder and the vault root, else the vault's public key file
+    pub fn recipients_for(&self, entry: &Path) -> Result<Vec<String>> {
+        let candidate = self.recipients_file_for(entry);
+        if !candidate.starts_with(&self.prefix) {
+            return self.read_recipients();
+        }
+        let raw = fs::read_to_string(&candidate)
+            .with_context(|| format!("Failed to read {}", candidate.display()))?;
+        let recipients = parse_recipients(&raw);
+        if recipients.is_empty() {
+            anyhow::bail!("No recipients in {}", candidate.display());
+        }
+        Ok(recipients)
+    }
+
+    /// `$EDITOR` if set, otherwise the configured editor, otherwise `vi`
+    pub fn editor(&self) -> String {
+        env::var("EDITOR")
+            .ok()
+            .or_else(|| self.editor.clone())
+            .unwrap_or_else(|| "vi".to_string())
+    }
+}
+
+thread_local! {
+    /// Vaults this process works on, keyed by `prefix`
+    static REGISTERED: RefCell<HashMap<PathBuf, Config>> = RefCell::new(HashMap::new());
+}
+
+/// Remember `config` so code that only has the vault path (entry listing,
+/// git sync) can still reach its keys and settings
+pub fn register(config: &Config) {
+    REGISTERED.with(|r| {
+        r.borrow_mut()
+            .entry(config.prefix.clone())
+            .or_insert_with(|| config.clone());
+    });
+}
+
+/// The config registered for the vault at `prefix`
+pub fn registered(prefix: &Path) -> Option<Config> {
+    REGISTERED.with(|r| r.borrow().get(prefix).cloned())
+}
+
+/// `$XDG_CONFIG_HOME/passman/config.toml` (or `~/.config/passman/config.toml`)
+pub fn config_file_path() -> PathBuf {
+    let config_home = env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            dirs::home_dir()
+                .expect("HOME directory not set")
+                .join(".config")
+        });
+    config_home.join("passman").join("config.toml")
+}
+
+/// Load the config for `vault` (or the default vault when `None`)
+pub fn load_config(vault: Option<&str>) -> Result<Config> {
+    load_config_with_sources(vault).map(|(cfg, _)| cfg)
+}
+
+/// Load the config file, layer the environment on top, and record
+/// where each value ended up coming from.
+pub fn load_config_with_sources(vault: Option<&str>) -> Result<(Config, Sources)> {
+    let file = FileConfig::load(&config_file_path())?;
+    resolve(&file, vault)
+}
+
+/// Load every configured vault, paired with its name. The unnamed vault
+/// from the top-level keys comes first, as `default`.
+pub fn load_all_vaults() -> Result<Vec<(String, Config)>> {
+    let mut file = FileConfig::load(&config_file_path())?;
+    let mut all = Vec::new();
+    for name in file.vaults.keys() {
+        all.push((name.clone(), resolve(&file, Some(name))?.0));
+    }
+    if file.has_unnamed_vault() {
+        file.default_vault = None;
+        let (cfg, _) = resolve(&file, None)?;
+        all.insert(0, (DEFAULT_VAULT_NAME.to_string(), cfg));
+    }
+    // hidden-names vaults look their manifest's keys up by path
+    for (_, cfg) in &all {
+        register(cfg);
     }
+    Ok(all)
 }
 
-pub fn load_config() -> Result<Config> {
-    // Determine base dir
-    let base_dir = if let Ok(dir) = env::var("PASSMAN_DIR") {
-        PathBuf::from(dir)
+fn resolve(file: &FileConfig, vault: Option<&str>) -> Result<(Config, Sources)> {
+    let mut sources = Sources::new();
+
+    // Pick the vault profile: `--vault`, then `default_vault`, then none
+    let (vault_name, vault_source) = match (vault, &file.default_vault) {
+        (Some(name), _) => (Some(name.to_string()), Source::Flag),
+        (None, Some(name)) => (Some(name.clone()), Source::File),
+        (None, None) => (None, Source::Default),
+    };
+    sources.insert("vault", vault_source);
+    let profile = match &vault_name {
+        Some(name) => Some(
+            file.vaults
+                .get(name)
+                .with_context(|| format!("Unknown vault `{}` (see `passman vault list`)", name))?,
+        ),
+        None => None,
+    };
+
+    // Determine base dir: env, then file, then XDG data dir. An explicit
+    // `--vault` always wins over `PASSMAN_DIR`.
+    let env_dir = env::var("PASSMAN_DIR").ok().filter(|_| vault.is_none());
+    let (base_dir, base_source) = if let Some(dir) = env_dir {
+        (PathBuf::from(dir), Source::Env)
+    } else if let Some(profile) = profile {
+        (profile.base_dir.clone(), Source::File)
+    } else if let Some(dir) = &file.base_dir {
+        (dir.clone(), Source::File)
     } else {
         let data_home = env::var("XDG_DATA_HOME")
             .map(PathBuf::from)
@@ -51,28 +374,521 @@ pub fn load_config() -> Result<Config> {
                     .join(".local")
                     .join("share")
             });
-        data_home.join("passman")
+        (data_home.join("passman"), Source::Default)
     };
+    sources.insert("base_dir", base_source);
 
-    // Determine crypto backend: rage or age
-    let crypto_extension = detect_backend()?;
+    // Determine crypto backend: the persisted choice, or detect rage/age
+    let crypto_extension = match &file.backend {
+        Some(backend) if backend == "age" || backend == "rage" => {
+            sources.insert("crypto_extension", Source::File);
+            backend.clone()
+        }
+        Some(other) => {
+            anyhow::bail!(
+                "Unknown backend `{}` in config (expected `age` or `rage`)",
+                other
+            )
+        }
+        None => {
+            sources.insert("crypto_extension", Source::Detected);
+            detect_backend()?
+        }
+    };
 
     // Construct paths
     let prefix = base_dir.join("vault");
-    let secret = base_dir.join(format!("private.{}", crypto_extension));
+    sources.insert("prefix", base_source);
+    let identity = match profile {
+        Some(profile) => profile.identity.as_ref(),
+        None => file.identity.as_ref(),
+    };
+    let secret = match identity {
+        // A PASSMAN_DIR override moves the whole vault, so the persisted
+        // identity (which belongs to the other vault) doesn't apply
+        Some(path) if base_source != Source::Env => {
+            sources.insert("secret", Source::File);
+            path.clone()
+        }
+        _ => {
+            sources.insert("secret", Source::Default);
+            base_dir.join(format!("private.{}", crypto_extension))
+        }
+    };
 
-    // Ensure vault dir exists
-    fs::create_dir_all(&prefix).context("Failed to create passman vault directory")?;
+    // Ensure vault dir exists, readable by us only
+    secure_fs::private_dir(&base_dir)?;
+    secure_fs::private_dir(&prefix).context("Failed to create passman vault directory")?;
 
     // Public key filename override
-    let public_key_filename =
-        env::var("PASSMAN_PUBLIC_KEY").unwrap_or_else(|_| "public.key".to_string());
+    let public_key_filename = if let Ok(name) = env::var("PASSMAN_PUBLIC_KEY") {
+        sources.insert("public_key_filename", Source::Env);
+        name
+    } else if let Some(name) = profile
+        .and_then(|p| p.recipients.as_ref())
+        .or(file.public_key.as_ref())
+    {
+        sources.insert("public_key_filename", Source::File);
+        name.clone()
+    } else {
+        sources.insert("public_key_filename", Source::Default);
+        "public.key".to_string()
+    };
+
+    let editor = file.editor.clone();
+    sources.insert(
+        "editor",
+        if env::var("EDITOR").is_ok() {
+            Source::Env
+        } else if editor.is_some() {
+            Source::File
+        } else {
+            Source::Default
+        },
+    );
+
+    let clipboard_timeout = match file.clipboard_timeout {
+        Some(secs) => {
+            sources.insert("clipboard_timeout", Source::File);
+            secs
+        }
+        None => {
+            sources.insert("clipboard_timeout", Source::Default);
+            DEFAULT_CLIPBOARD_TIMEOUT
+        }
+    };
+
+    let sync = match file.sync {
+        Some(policy) => {
+            sources.insert("sync", Source::File);
+            policy
+        }
+        None => {
+            sources.insert("sync", Source::Default);
+            SyncPolicy::default()
+        }
+    };
+
+    Ok((
+        Config {
+            base_dir,
+            prefix,
+            secret,
+            crypto_extension,
+            public_key_filename,
+            editor,
+            clipboard_timeout,
+            sync,
+            vault: vault_name,
+            remote: profile.and_then(|p| p.remote.clone()),
+        },
+        sources,
+    ))
+}
+
+/// Parse an age recipients file: one recipient per line, `#` comments allowed.
+/// SSH public keys lose their comment, and key types age can't encrypt to
+/// are skipped, so a GitHub `.keys` file can be used as is.
+pub fn parse_recipients(raw: &str) -> Vec<String> {
+    raw.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| {
+            if ssh::is_public_key(l) {
+                ssh::recipient(l)
+            } else {
+                Some(l.to_string())
+            }
+        })
+        .collect()
+}
+
+/// A recipient with the name its recipients file gives it
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedRecipient {
+    pub name: Option<String>,
+    pub recipient: String,
+}
+
+impl NamedRecipient {
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or("(unnamed)")
+    }
+}
+
+/// Like `parse_recipients`, keeping each recipient's name: the
+/// `# device:` line above it, else an SSH key's comment
+pub fn parse_named_recipients(raw: &str) -> Vec<NamedRecipient> {
+    let mut named = Vec::new();
+    let mut name = None;
+    for line in raw.lines().map(str::trim) {
+        if let Some(device) = line.strip_prefix(DEVICE_COMMENT) {
+            name = Some(device.trim().to_string());
+            continue;
+        }
+        let Some(recipient) = parse_recipients(line).pop() else {
+            continue;
+        };
+        let comment = ssh::is_public_key(line)
+            .then(|| line.split_whitespace().nth(2).map(str::to_string))
+            .flatten();
+        named.push(NamedRecipient {
+            name: name.take().or(comment),
+            recipient,
+        });
+    }
+    named
+}
 
-    Ok(Config {
-        base_dir,
+/// Strip a known entry extension from a file name, e.g. `chase.rage` → `chase`
+pub fn strip_entry_extension(name: &str) -> Option<&str> {
+    ENTRY_EXTENSIONS
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext)?.strip_suffix('.'))
+}
+
+/// A config for tests: keys and settings in `base`, an empty vault in
+/// `base/vault`, nothing else on disk
+#[cfg(test)]
+pub fn test_config(base: &Path) -> Config {
+    let prefix = base.join("vault");
+    fs::create_dir_all(&prefix).unwrap();
+    Config {
+        base_dir: base.to_path_buf(),
         prefix,
-        secret,
-        crypto_extension,
-        public_key_filename,
-    })
+        secret: base.join("secret.key"),
+        crypto_extension: "rage".into(),
+        public_key_filename: "public.key".into(),
+        editor: None,
+        clipboard_timeout: 45,
+        sync: SyncPolicy::Manual,
+        vault: None,
+        remote: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    fn clear_env() {
+        unsafe {
+            env::remove_var("PASSMAN_DIR");
+            env::remove_var("PASSMAN_PUBLIC_KEY");
+            env::remove_var("EDITOR");
+        }
+    }
+
+    #[test]
+    fn file_config_roundtrips_through_toml() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let path = tmp.path().join("passman/config.toml");
+        let file = FileConfig {
+            backend: Some("age".into()),
+            base_dir: Some(tmp.path().to_path_buf()),
+            identity: None,
+            public_key: Some("team.key".into()),
+            editor: Some("nvim".into()),
+            clipboard_timeout: Some(10),
+            sync: Some(SyncPolicy::Commit),
+            default_vault: Some("team".into()),
+            vaults: BTreeMap::from([(
+                "team".to_string(),
+                VaultConfig {
+                    base_dir: tmp.path().join("team"),
+                    remote: Some("git@host:team.git".into()),
+                    ..Default::default()
+                },
+            )]),
+        };
+        file.save(&path)?;
+
+        let raw = fs::read_to_string(&path)?;
+        assert!(raw.contains("sync = \"commit\""), "got: {raw}");
+        assert!(raw.contains("[vaults.team]"), "got: {raw}");
+        assert_eq!(FileConfig::load(&path)?, file);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_file_loads_defaults() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let file = FileConfig::load(&tmp.path().join("nope.toml"))?;
+        assert_eq!(file, FileConfig::default());
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let path = tmp.path().join("config.toml");
+        fs::write(&path, "backnd = \"age\"\n")?;
+        assert!(FileConfig::load(&path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn file_values_are_used_without_detection() -> Result<()> {
+        clear_env();
+        let tmp = TempDir::new()?;
+        let file = FileConfig {
+            backend: Some("age".into()),
+            base_dir: Some(tmp.path().to_path_buf()),
+            editor: Some("nano".into()),
+            ..Default::default()
+        };
+
+        let (cfg, sources) = resolve(&file, None)?;
+        assert_eq!(cfg.crypto_extension, "age");
+        assert_eq!(cfg.prefix, tmp.path().join("vault"));
+        assert_eq!(cfg.secret, tmp.path().join("private.age"));
+        assert_eq!(cfg.editor(), "nano");
+        assert_eq!(cfg.sync, SyncPolicy::Auto);
+        assert_eq!(sources["crypto_extension"], Source::File);
+        assert_eq!(sources["base_dir"], Source::File);
+        assert_eq!(sources["sync"], Source::Default);
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn env_overrides_file() -> Result<()> {
+        clear_env();
+        let from_file = TempDir::new()?;
+        let from_env = TempDir::new()?;
+        unsafe {
+            env::set_var("PASSMAN_DIR", from_env.path());
+            env::set_var("PASSMAN_PUBLIC_KEY", "env.key");
+        }
+        let file = FileConfig {
+            backend: Some("rage".into()),
+            base_dir: Some(from_file.path().to_path_buf()),
+            identity: Some(from_file.path().join("private.rage")),
+            public_key: Some("file.key".into()),
+            ..Default::default()
+        };
+
+        let (cfg, sources) = resolve(&file, None)?;
+        clear_env();
+
+        assert_eq!(cfg.base_dir, from_env.path());
+        assert_eq!(cfg.secret, from_env.path().join("private.rage"));
+        assert_eq!(cfg.public_key_filename, "env.key");
+        assert_eq!(sources["base_dir"], Source::Env);
+        assert_eq!(sources["public_key_filename"], Source::Env);
+        Ok(())
+    }
+
+    fn two_vaults(tmp: &TempDir) -> FileConfig {
+        let vault = |name: &str| VaultConfig {
+            base_dir: tmp.path().join(name),
+            recipients: Some(format!("{name}.key")),
+            ..Default::default()
+        };
+        FileConfig {
+            backend: Some("rage".into()),
+            default_vault: Some("personal".into()),
+            vaults: BTreeMap::from([
+                ("personal".to_string(), vault("personal")),
+                ("team".to_string(), vault("team")),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn every_vault_is_registered() -> Result<()> {
+        clear_env();
+        let tmp = TempDir::new()?;
+        unsafe { env::set_var("XDG_CONFIG_HOME", tmp.path()) };
+        two_vaults(&tmp).save(&config_file_path())?;
+        let all = load_all_vaults();
+        unsafe { env::remove_var("XDG_CONFIG_HOME") };
+
+        for (_, cfg) in all? {
+            assert!(
+                registered(&cfg.prefix).is_some(),
+                "{}",
+                cfg.prefix.display()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn default_vault_is_selected() -> Result<()> {
+        clear_env();
+        let tmp = TempDir::new()?;
+        let (cfg, sources) = resolve(&two_vaults(&tmp), None)?;
+
+        assert_eq!(cfg.vault.as_deref(), Some("personal"));
+        assert_eq!(cfg.base_dir, tmp.path().join("personal"));
+        assert_eq!(cfg.public_key_filename, "personal.key");
+        assert_eq!(sources["vault"], Source::File);
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn vault_flag_beats_env_dir() -> Result<()> {
+        clear_env();
+        let tmp = TempDir::new()?;
+        unsafe {
+            env::set_var("PASSMAN_DIR", tmp.path().join("elsewhere"));
+        }
+        let (cfg, sources) = resolve(&two_vaults(&tmp), Some("team"))?;
+        clear_env();
+
+        assert_eq!(cfg.vault.as_deref(), Some("team"));
+        assert_eq!(cfg.prefix, tmp.path().join("team/vault"));
+        assert_eq!(cfg.secret, tmp.path().join("team/private.rage"));
+        assert_eq!(sources["vault"], Source::Flag);
+        assert_eq!(sources["base_dir"], Source::File);
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn unknown_vault_errors() -> Result<()> {
+        clear_env();
+        let tmp = TempDir::new()?;
+        let err = resolve(&two_vaults(&tmp), Some("nope")).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Unknown vault `nope` (see `passman vault list`)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn record_writes_into_the_selected_vault() -> Result<()> {
+        clear_env();
+        let tmp = TempDir::new()?;
+        let mut file = two_vaults(&tmp);
+        let (cfg, _) = resolve(&file, Some("team"))?;
+
+        file.record(&cfg);
+        assert_eq!(file.base_dir, None);
+        assert_eq!(
+            file.vaults["team"].identity,
+            Some(tmp.path().join("team/private.rage"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn entry_path_finds_either_extension() -> Result<()> {
+        clear_env();
+        let tmp = TempDir::new()?;
+        let file = FileConfig {
+            backend: Some("rage".into()),
+            base_dir: Some(tmp.path().to_path_buf()),
+            ..Default::default()
+        };
+        let (cfg, _) = resolve(&file, None)?;
+        fs::write(cfg.prefix.join("old.age"), "")?;
+
+        assert_eq!(cfg.entry_path("old")?, cfg.prefix.join("old.age"));
+        assert_eq!(cfg.entry_path("new")?, cfg.prefix.join("new.rage"));
+
+        // the configured backend wins when both exist
+        fs::write(cfg.prefix.join("old.rage"), "")?;
+        assert_eq!(cfg.entry_path("old")?, cfg.prefix.join("old.rage"));
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn recipients_come_from_the_nearest_folder_file() -> Result<()> {
+        clear_env();
+        let tmp = TempDir::new()?;
+        let file = FileConfig {
+            backend: Some("rage".into()),
+            base_dir: Some(tmp.path().to_path_buf()),
+            ..Default::default()
+        };
+        let (cfg, _) = resolve(&file, None)?;
+        fs::write(tmp.path().join("public.key"), "# me\nage1me\n")?;
+        fs::create_dir_all(cfg.prefix.join("team/ops"))?;
+        fs::write(cfg.prefix.join("team/.recipients"), "age1me\n\nage1you\n")?;
+
+        assert_eq!(
+            cfg.recipients_for(&cfg.prefix.join("mail.rage"))?,
+            vec!["age1me"]
+        );
+        assert_eq!(
+            cfg.recipients_for(&cfg.prefix.join("team/ops/db.rage"))?,
+            vec!["age1me", "age1you"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn github_keys_files_are_recipients() {
+        let keys = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHsK\n\
+                    ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTI\n\
+                    ssh-rsa AAAAB3NzaC1yc2EAAAADAQAB alice@laptop\n\
+                    sk-ssh-ed25519@openssh.com AAAAGnNrLXNzaC1lZDI1NTE5\n";
+        assert_eq!(
+            parse_recipients(keys),
+            vec![
+                "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHsK",
+                "ssh-rsa AAAAB3NzaC1yc2EAAAADAQAB"
+            ]
+        );
+    }
+
+    #[test]
+    fn recipients_are_named_by_device_lines_or_ssh_comments() {
+        let raw = "# shared with the team\n\
+                   # device: desk\n\
+                   age1desk\n\
+                   ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHsK alice@laptop\n\
+                   age1unnamed\n";
+        let labels: Vec<(String, String)> = parse_named_recipients(raw)
+            .iter()
+            .map(|r| (r.label().to_string(), r.recipient.clone()))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("desk".into(), "age1desk".into()),
+                (
+                    "alice@laptop".into(),
+                    "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHsK".into()
+                ),
+                ("(unnamed)".into(), "age1unnamed".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn strips_known_extensions_only() {
+        assert_eq!(strip_entry_extension("bank/chase.rage"), Some("bank/chase"));
+        assert_eq!(strip_entry_extension("chase.age"), Some("chase"));
+        assert_eq!(strip_entry_extension("chase.gpg"), None);
+        assert_eq!(strip_entry_extension("chaseage"), None);
+    }
+
+    #[test]
+    #[serial]
+    fn bad_backend_errors() {
+        clear_env();
+        let file = FileConfig {
+            backend: Some("gpg".into()),
+            ..Default::default()
+        };
+        let err = resolve(&file, None).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Unknown backend `gpg` in config (expected `age` or `rage`)"
+        );
+    }
 }
@@ -2,12 +2,13 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::{Command, Stdio};
 
+use crate::crypto::SecretString;
 use crate::crypto::backend::CryptoBackend;
 
 pub struct Age;
 
 impl CryptoBackend for Age {
-    fn decrypt(&self, identity_file: &Path, encrypted_file: &Path) -> Result<String> {
+    fn decrypt(&self, identity_file: &Path, encrypted_file: &Path) -> Result<SecretString> {
         let output = Command::new("age")
             .arg("-d")
             .arg("-i")
@@ -24,13 +25,54 @@ impl CryptoBackend for Age {
             );
         }
 
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        Ok(SecretString::from_utf8_lossy(output.stdout))
     }
 
-    fn encrypt(&self, recipient: &str, output_file: &Path, plaintext: &str) -> Result<()> {
+    /// The unlocked key goes in on stdin, so it never touches a file
+    fn decrypt_unlocked(
+        &self,
+        identity: &SecretString,
+        encrypted_file: &Path,
+    ) -> Result<SecretString> {
         let mut child = Command::new("age")
-            .arg("-r")
-            .arg(recipient)
+            .arg("-d")
+            .arg("-i")
+            .arg("/dev/stdin")
+            .arg(encrypted_file)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run `age -d` on {}", encrypted_file.display()))?;
+
+        use std::io::Write;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(identity.expose().as_bytes())
+                .context("Failed to pass the identity to age")?;
+        }
+        let output = child
+            .wait_with_output()
+            .context("Failed to wait on age process")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "age decryption failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        Ok(SecretString::from_utf8_lossy(output.stdout))
+    }
+
+    fn encrypt(
+        &self,
+        recipients: &[String],
+        output_file: &Path,
+        plaintext: &SecretString,
+    ) -> Result<()> {
+        let mut child = Command::new("age")
+            .args(recipients.iter().flat_map(|r| ["-r", r.as_str()]))
             .arg("-o")
             .arg(output_file)
             .stdin(Stdio::piped())
@@ -40,7 +82,7 @@ impl CryptoBackend for Age {
         use std::io::Write;
         if let Some(stdin) = &mut child.stdin {
             stdin
-                .write_all(plaintext.as_bytes())
+                .write_all(plaintext.expose().as_bytes())
                 .context("Failed to write plaintext to age stdin")?;
         }
 
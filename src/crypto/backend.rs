@@ -1,10 +1,38 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
 use std::path::Path;
 
+use crate::crypto::SecretString;
+use crate::utils::secure_fs;
+
 pub trait CryptoBackend {
     /// Decrypts the given file (using this backend) and returns its plaintext.
-    fn decrypt(&self, identity_file: &Path, encrypted_file: &Path) -> Result<String>;
+    fn decrypt(&self, identity_file: &Path, encrypted_file: &Path) -> Result<SecretString>;
+
+    /// Encrypts the given plaintext for every recipient and writes to `output_file`.
+    fn encrypt(
+        &self,
+        recipients: &[String],
+        output_file: &Path,
+        plaintext: &SecretString,
+    ) -> Result<()>;
 
-    /// Encrypts the given plaintext for the recipient and writes to `output_file`.
-    fn encrypt(&self, recipient: &str, output_file: &Path, plaintext: &str) -> Result<()>;
+    /// Decrypts with an identity that is already in memory, i.e. a
+    /// passphrase-protected one that has been unlocked. By default the key
+    /// is handed to `decrypt` through a private scratch file that is removed
+    /// straight after.
+    fn decrypt_unlocked(
+        &self,
+        identity: &SecretString,
+        encrypted_file: &Path,
+    ) -> Result<SecretString> {
+        let mut file = secure_fs::scratch_file()?;
+        file.write_all(identity.expose().as_bytes())
+            .context("Failed to write the unlocked identity")?;
+        let plaintext = self.decrypt(file.path(), encrypted_file);
+        // overwrite before unlinking; tmpfs pages may otherwise linger
+        let _ = fs::write(file.path(), vec![0u8; identity.expose().len()]);
+        plaintext
+    }
 }
@@ -1,9 +1,12 @@
 use anyhow::Result;
 use std::path::Path;
 
+use crate::crypto::SecretString;
 use crate::crypto::age::Age; // ← now actually used below
 use crate::crypto::backend::CryptoBackend;
-use crate::crypto::rage::Rage; // ← now actually used below
+use crate::crypto::identity;
+use crate::crypto::rage::Rage;
+use crate::utils::agent;
 
 /// Pick “Age” if the extension is `.age`, otherwise “Rage”
 fn default_encrypt_backend(output: &Path) -> Box<dyn CryptoBackend> {
@@ -30,14 +33,26 @@ thread_local! {
 }
 
 /// Exactly your old `encrypt`, but now driven by `ENCRYPT_FACTORY`
-pub fn encrypt(recipient: &str, output_file: &Path, plaintext: &str) -> Result<()> {
+pub fn encrypt(recipients: &[String], output_file: &Path, plaintext: &SecretString) -> Result<()> {
     let backend = ENCRYPT_FACTORY.with(|f| (f.borrow())(output_file));
-    backend.encrypt(recipient, output_file, plaintext)
+    backend.encrypt(recipients, output_file, plaintext)
 }
 
-/// Exactly your old `decrypt`, but now driven by `DECRYPT_FACTORY`
-pub fn decrypt(identity_file: &Path, encrypted_file: &Path) -> Result<String> {
+/// Exactly your old `decrypt`, but now driven by `DECRYPT_FACTORY`. With a
+/// passphrase-protected identity a running `passman agent` does the work;
+/// without one the identity is unlocked here, once, and passed along in
+/// memory.
+pub fn decrypt(identity_file: &Path, encrypted_file: &Path) -> Result<SecretString> {
     let backend = DECRYPT_FACTORY.with(|f| (f.borrow())(encrypted_file));
+    if identity::is_protected(identity_file) {
+        if !identity::is_unlocked(identity_file)
+            && let Some(plaintext) = agent::decrypt(identity_file, encrypted_file)?
+        {
+            return Ok(plaintext);
+        }
+        let key = identity::unlock(identity_file)?;
+        return backend.decrypt_unlocked(&key, encrypted_file);
+    }
     backend.decrypt(identity_file, encrypted_file)
 }
 
@@ -64,11 +79,11 @@ mod tests {
 
     struct MockBackend;
     impl CryptoBackend for MockBackend {
-        fn encrypt(&self, _r: &str, _o: &Path, plaintext: &str) -> Result<()> {
-            assert_eq!(plaintext, "hello-test");
+        fn encrypt(&self, _r: &[String], _o: &Path, plaintext: &SecretString) -> Result<()> {
+            assert_eq!(plaintext.expose(), "hello-test");
             Ok(())
         }
-        fn decrypt(&self, _i: &Path, _e: &Path) -> Result<String> {
+        fn decrypt(&self, _i: &Path, _e: &Path) -> Result<SecretString> {
             Ok("mocked".into())
         }
     }
@@ -80,7 +95,7 @@ mod tests {
     #[test]
     fn encrypt_is_mockable() -> Result<()> {
         set_encrypt_factory(mock_factory);
-        encrypt("you", Path::new("foo.age"), "hello-test")?;
+        encrypt(&["you".into()], Path::new("foo.age"), &"hello-test".into())?;
         Ok(())
     }
 
@@ -88,7 +103,7 @@ mod tests {
     fn decrypt_is_mockable() -> Result<()> {
         set_decrypt_factory(mock_factory);
         let out = decrypt(Path::new("id"), Path::new("foo.age"))?;
-        assert_eq!(out, "mocked");
+        assert_eq!(out.expose(), "mocked");
         Ok(())
     }
 }
@@ -1,7 +1,13 @@
 use anyhow::{Result, bail};
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use which::which;
 
+use crate::crypto::ssh;
+
+/// Keys in `~/.ssh` worth offering as the identity, preferred first
+const SSH_KEY_NAMES: [&str; 2] = ["id_ed25519", "id_rsa"];
+
 /// Detects which crypto backend to use: "rage" or "age"
 pub fn detect_crypto_backend() -> Result<String> {
     let has_rage = which("rage").is_ok();
@@ -35,3 +41,19 @@ pub fn detect_crypto_backend() -> Result<String> {
         }
     }
 }
+
+/// Existing SSH keys that could serve as the identity instead of a new
+/// age key: `~/.ssh/id_ed25519` and `~/.ssh/id_rsa`, if age can use them
+pub fn detect_ssh_keys() -> Vec<PathBuf> {
+    dirs::home_dir()
+        .map(|home| ssh_keys_in(&home.join(".ssh")))
+        .unwrap_or_default()
+}
+
+fn ssh_keys_in(dir: &Path) -> Vec<PathBuf> {
+    SSH_KEY_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .filter(|key| ssh::is_private_key(key) && ssh::public_key(key).is_ok())
+        .collect()
+}
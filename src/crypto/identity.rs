@@ -0,0 +1,313 @@
+use anyhow::{Context, Result};
+use dialoguer::Password;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use crate::crypto::{SecretString, ssh};
+use crate::utils::secure_fs;
+
+/// File holding the identity's passphrase, for scripts and cron jobs
+pub const PASSPHRASE_FILE_VAR: &str = "PASSMAN_PASSPHRASE_FILE";
+
+/// Program that prints the passphrase, like `ssh-askpass` or `pinentry` wrappers
+pub const ASKPASS_VAR: &str = "PASSMAN_ASKPASS";
+
+const BINARY_MAGIC: &[u8] = b"age-encryption.org/v1";
+const ARMOR_MAGIC: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+
+thread_local! {
+    /// Unlocked identities, so one run asks for the passphrase only once
+    static UNLOCKED: RefCell<HashMap<PathBuf, SecretString>> = RefCell::new(HashMap::new());
+}
+
+/// Whether `path` is an age file sealed with a passphrase rather than a
+/// plain `AGE-SECRET-KEY-1…` identity
+pub fn is_protected(path: &Path) -> bool {
+    let mut head = [0u8; ARMOR_MAGIC.len()];
+    let Ok(n) = fs::File::open(path).and_then(|mut f| f.read(&mut head)) else {
+        return false;
+    };
+    head[..n].starts_with(BINARY_MAGIC) || head[..n].starts_with(ARMOR_MAGIC)
+}
+
+/// The identity file's contents, unlocking it first if it is protected
+pub fn read(path: &Path) -> Result<SecretString> {
+    if is_protected(path) {
+        unlock(path)
+    } else {
+        Ok(SecretString::new(fs::read_to_string(path).with_context(
+            || format!("Failed to read identity {}", path.display()),
+        )?))
+    }
+}
+
+/// The identity in the form the age crate decrypts with, for code that
+/// decrypts in-process: plain or protected age keys, or an SSH key
+pub fn load(path: &Path) -> Result<Vec<Box<dyn age::Identity>>> {
+    if ssh::is_private_key(path) {
+        let file = fs::File::open(path)
+            .with_context(|| format!("Failed to read identity {}", path.display()))?;
+        let key = age::ssh::Identity::from_buffer(
+            io::BufReader::new(file),
+            Some(path.display().to_string()),
+        )?;
+        return Ok(vec![Box::new(key)]);
+    }
+    let key = read(path)?;
+    Ok(age::IdentityFile::from_buffer(key.expose().as_bytes())
+        .with_context(|| format!("Failed to read identity {}", path.display()))?
+        .into_identities()?)
+}
+
+/// The recipient that encrypts to the identity at `path`, unlocking it
+/// first if it is protected
+pub fn recipient(path: &Path) -> Result<String> {
+    if ssh::is_private_key(path) {
+        return ssh::public_key(path);
+    }
+    let key = read(path)?;
+    let secret = key
+        .expose()
+        .lines()
+        .map(str::trim)
+        .find(|l| l.starts_with("AGE-SECRET-KEY-"))
+        .with_context(|| format!("{} holds no age identity", path.display()))?;
+    let identity = age::x25519::Identity::from_str(secret)
+        .map_err(|e| anyhow::anyhow!("Invalid identity in {}: {}", path.display(), e))?;
+    Ok(identity.to_public().to_string())
+}
+
+/// Decrypt a passphrase-protected identity. The passphrase comes from
+/// `$PASSMAN_PASSPHRASE_FILE`, then `$PASSMAN_ASKPASS`, then a prompt.
+pub fn unlock(path: &Path) -> Result<SecretString> {
+    if let Some(key) = UNLOCKED.with(|u| u.borrow().get(path).cloned()) {
+        return Ok(key);
+    }
+
+    let sealed = fs::File::open(path)
+        .with_context(|| format!("Failed to read identity {}", path.display()))?;
+    let passphrase = passphrase(path)?;
+    let decryptor = age::Decryptor::new_buffered(io::BufReader::new(sealed))
+        .with_context(|| format!("{} is not an age file", path.display()))?;
+    if !decryptor.is_scrypt() {
+        anyhow::bail!(
+            "{} is encrypted to a key, not a passphrase; passman can't unlock it",
+            path.display()
+        );
+    }
+    let identity = age::scrypt::Identity::new(passphrase.expose().to_string().into());
+    let mut reader = decryptor
+        .decrypt(iter::once(&identity as &dyn age::Identity))
+        .with_context(|| format!("Wrong passphrase for {}", path.display()))?;
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let key = SecretString::from_utf8_lossy(bytes);
+
+    UNLOCKED.with(|u| u.borrow_mut().insert(path.to_path_buf(), key.clone()));
+    Ok(key)
+}
+
+/// Hold an identity that was unlocked elsewhere, as the agent does with
+/// the key `passman agent` hands it
+pub fn remember(path: &Path, key: SecretString) {
+    UNLOCKED.with(|u| u.borrow_mut().insert(path.to_path_buf(), key));
+}
+
+pub fn is_unlocked(path: &Path) -> bool {
+    UNLOCKED.with(|u| u.borrow().contains_key(path))
+}
+
+/// Drop every unlocked identity; each is wiped as it goes
+pub fn forget() {
+    UNLOCKED.with(|u| u.borrow_mut().clear());
+}
+
+/// Seal the plain identity at `path` with `passphrase`, in place. The result
+/// is what `age-keygen | age -p` writes, so `age -d -i` and `rage -d -i`
+/// accept it too.
+pub fn protect(path: &Path, passphrase: &SecretString) -> Result<()> {
+    let key = SecretString::new(
+        fs::read_to_string(path)
+            .with_context(|| format!("Failed to read identity {}", path.display()))?,
+    );
+    if is_protected(path) {
+        anyhow::bail!("{} is already passphrase-protected", path.display());
+    }
+
+    #[allow(unused_mut)]
+    let mut recipient = age::scrypt::Recipient::new(passphrase.expose().to_string().into());
+    // the default work factor takes seconds in debug builds
+    #[cfg(test)]
+    recipient.set_work_factor(4);
+    let encryptor = age::Encryptor::with_recipients(iter::once(&recipient as &dyn age::Recipient))?;
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Failed to create a file in {}", dir.display()))?;
+    let mut writer = encryptor.wrap_output(tmp.as_file_mut())?;
+    writer.write_all(key.expose().as_bytes())?;
+    writer.finish()?;
+    secure_fs::private_file(tmp.path())?;
+    tmp.persist(path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+fn passphrase(path: &Path) -> Result<SecretString> {
+    if let Some(file) = env::var_os(PASSPHRASE_FILE_VAR) {
+        let raw = SecretString::new(fs::read_to_string(&file).with_context(|| {
+            format!(
+                "Failed to read ${} ({})",
+                PASSPHRASE_FILE_VAR,
+                Path::new(&file).display()
+            )
+        })?);
+        return Ok(first_line(&raw));
+    }
+
+    let prompt = format!("Passphrase for {}", path.display());
+    if let Some(program) = env::var_os(ASKPASS_VAR) {
+        let output = Command::new(&program)
+            .arg(&prompt)
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()
+            .with_context(|| format!("Failed to run ${} ({:?})", ASKPASS_VAR, program))?;
+        let raw = SecretString::from_utf8_lossy(output.stdout);
+        if !output.status.success() {
+            anyhow::bail!("${} was cancelled", ASKPASS_VAR);
+        }
+        return Ok(first_line(&raw));
+    }
+
+    if !io::stdin().is_terminal() {
+        anyhow::bail!(
+            "{} is passphrase-protected; set ${} or ${} to unlock it without a terminal",
+            path.display(),
+            PASSPHRASE_FILE_VAR,
+            ASKPASS_VAR
+        );
+    }
+    Ok(Password::new()
+        .with_prompt(prompt)
+        .interact()
+        .context("Failed to read passphrase")?
+        .into())
+}
+
+/// Passphrase files and askpass programs end with a newline that is not
+/// part of the passphrase
+fn first_line(raw: &SecretString) -> SecretString {
+    raw.expose().lines().next().unwrap_or_default().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{self, CryptoBackend, set_decrypt_factory};
+    use age::secrecy::ExposeSecret;
+    use serial_test::serial;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    fn new_identity(tmp: &TempDir) -> Result<(PathBuf, age::x25519::Identity)> {
+        let id = age::x25519::Identity::generate();
+        let path = tmp.path().join("private.rage");
+        fs::write(&path, format!("{}\n", id.to_string().expose_secret()))?;
+        Ok((path, id))
+    }
+
+    fn use_passphrase_file(tmp: &TempDir, passphrase: &str) -> Result<()> {
+        let file = tmp.path().join("passphrase");
+        fs::write(&file, format!("{}\n", passphrase))?;
+        unsafe {
+            env::set_var(PASSPHRASE_FILE_VAR, &file);
+            env::remove_var(ASKPASS_VAR);
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn protected_identities_unlock_with_the_passphrase_file() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let (path, id) = new_identity(&tmp)?;
+        assert!(!is_protected(&path));
+
+        protect(&path, &"correct horse".into())?;
+        assert!(is_protected(&path));
+        assert!(!fs::read(&path)?.starts_with(b"AGE-SECRET-KEY"));
+
+        use_passphrase_file(&tmp, "correct horse")?;
+        let key = read(&path)?;
+        assert_eq!(key.expose().trim(), id.to_string().expose_secret());
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn wrong_or_missing_passphrases_fail() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let (path, _) = new_identity(&tmp)?;
+        protect(&path, &"correct horse".into())?;
+
+        use_passphrase_file(&tmp, "battery staple")?;
+        let err = unlock(&path).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("Wrong passphrase"),
+            "{:#}",
+            err
+        );
+
+        // no file, no askpass and (under `cargo test`) no terminal
+        unsafe { env::remove_var(PASSPHRASE_FILE_VAR) };
+        if !io::stdin().is_terminal() {
+            let err = unlock(&path).unwrap_err();
+            assert!(err.to_string().contains(PASSPHRASE_FILE_VAR));
+        }
+        Ok(())
+    }
+
+    /// In-process backend that knows nothing about protected identities
+    struct InProcess;
+    impl CryptoBackend for InProcess {
+        fn encrypt(&self, _r: &[String], _o: &Path, _p: &SecretString) -> Result<()> {
+            unreachable!()
+        }
+        fn decrypt(&self, identity: &Path, e: &Path) -> Result<SecretString> {
+            let raw = fs::read_to_string(identity)?;
+            let id = age::x25519::Identity::from_str(raw.trim()).map_err(anyhow::Error::msg)?;
+            let decryptor = age::Decryptor::new(fs::File::open(e)?)?;
+            let mut reader = decryptor.decrypt(iter::once(&id as &dyn age::Identity))?;
+            let mut out = String::new();
+            reader.read_to_string(&mut out)?;
+            Ok(out.into())
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn in_process_backends_decrypt_with_a_protected_identity() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let (path, id) = new_identity(&tmp)?;
+        let entry = tmp.path().join("entry.rage");
+        let encryptor =
+            age::Encryptor::with_recipients(iter::once(&id.to_public() as &dyn age::Recipient))?;
+        let mut writer = encryptor.wrap_output(fs::File::create(&entry)?)?;
+        writer.write_all(b"hunter2")?;
+        writer.finish()?;
+
+        protect(&path, &"correct horse".into())?;
+        use_passphrase_file(&tmp, "correct horse")?;
+        set_decrypt_factory(|_: &Path| Box::new(InProcess));
+        assert_eq!(crypto::decrypt(&path, &entry)?.expose(), "hunter2");
+        Ok(())
+    }
+}
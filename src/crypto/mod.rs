@@ -2,10 +2,16 @@ pub mod age;
 pub mod backend;
 pub mod crypto;
 pub mod detect;
+pub mod identity;
 pub mod rage;
+pub mod secret;
+pub mod ssh;
+#[cfg(test)]
+pub mod testing;
 
 // Public façade for normal code:
 pub use crypto::{decrypt, detect_backend, encrypt};
+pub use secret::SecretString;
 
 /// Test-only exports:
 #[cfg(test)]
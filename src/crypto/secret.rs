@@ -0,0 +1,124 @@
+use std::fmt;
+use zeroize::Zeroize;
+
+/// Decrypted text: a password, an entry, a key. The buffer is wiped when
+/// the value is dropped, and `Debug`/`Display` never print it, so a stray
+/// `{:?}` or log line can't leak it. Call `expose` to read the contents.
+///
+/// With the `mlock` feature the buffer is also locked into RAM so it
+/// can't be written to swap.
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(text: String) -> Self {
+        lock(&text);
+        SecretString(text)
+    }
+
+    /// Take over a decrypted byte buffer; invalid UTF-8 is replaced, and the
+    /// original bytes are wiped if that needs a copy
+    pub fn from_utf8_lossy(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => SecretString::new(text),
+            Err(e) => {
+                let mut bytes = e.into_bytes();
+                let text = String::from_utf8_lossy(&bytes).into_owned();
+                bytes.zeroize();
+                SecretString::new(text)
+            }
+        }
+    }
+
+    /// The plaintext; keep the borrow short-lived
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(text: String) -> Self {
+        SecretString::new(text)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(text: &str) -> Self {
+        SecretString::new(text.to_string())
+    }
+}
+
+impl Clone for SecretString {
+    fn clone(&self) -> Self {
+        SecretString::new(self.0.clone())
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+        unlock(&self.0);
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(<redacted>)")
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+#[cfg(feature = "mlock")]
+fn lock(text: &String) {
+    if text.capacity() > 0 {
+        // the whole allocation, which can outgrow the text; best effort,
+        // since RLIMIT_MEMLOCK may be tiny and the secret is wiped anyway
+        unsafe { libc::mlock(text.as_ptr().cast(), text.capacity()) };
+    }
+}
+
+#[cfg(not(feature = "mlock"))]
+fn lock(_text: &str) {}
+
+#[cfg(feature = "mlock")]
+fn unlock(text: &String) {
+    if text.capacity() > 0 {
+        unsafe { libc::munlock(text.as_ptr().cast(), text.capacity()) };
+    }
+}
+
+#[cfg(not(feature = "mlock"))]
+fn unlock(_text: &str) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting_never_shows_the_secret() {
+        let secret = SecretString::from("hunter2");
+        assert_eq!(format!("{}", secret), "<redacted>");
+        assert_eq!(format!("{:?}", secret), "SecretString(<redacted>)");
+        assert_eq!(
+            format!("{:?}", Some(secret.clone())),
+            "Some(SecretString(<redacted>))"
+        );
+        assert_eq!(secret.expose(), "hunter2");
+    }
+
+    #[test]
+    fn errors_that_wrap_secrets_stay_redacted() {
+        let secret = SecretString::from("hunter2");
+        let err = anyhow::anyhow!("could not store {}", secret);
+        assert!(!format!("{:#}", err).contains("hunter2"));
+        assert!(!format!("{:?}", err).contains("hunter2"));
+    }
+}
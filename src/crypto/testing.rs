@@ -0,0 +1,66 @@
+//! Stand-in backends for tests that don't care about real encryption
+
+use super::{CryptoBackend, SecretString, set_decrypt_factory, set_encrypt_factory};
+use crate::config::{Config, test_config};
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+/// Stores plaintext as-is
+pub struct PlainBackend;
+impl CryptoBackend for PlainBackend {
+    fn encrypt(&self, _r: &[String], o: &Path, p: &SecretString) -> Result<()> {
+        fs::write(o, p.expose())?;
+        Ok(())
+    }
+    fn decrypt(&self, _i: &Path, e: &Path) -> Result<SecretString> {
+        Ok(fs::read_to_string(e)?.into())
+    }
+}
+
+/// Writes "<recipients>|<plaintext>" so tests can inspect what was
+/// encrypted, and reads back the plaintext part
+pub struct RecordingBackend;
+impl CryptoBackend for RecordingBackend {
+    fn encrypt(&self, r: &[String], o: &Path, p: &SecretString) -> Result<()> {
+        fs::write(o, format!("{}|{}", r.join(","), p.expose()))?;
+        Ok(())
+    }
+    fn decrypt(&self, _i: &Path, e: &Path) -> Result<SecretString> {
+        let raw = fs::read_to_string(e)?;
+        Ok(raw
+            .split_once('|')
+            .map_or(raw.clone(), |(_, p)| p.to_string())
+            .into())
+    }
+}
+
+/// Encrypt and decrypt with [`PlainBackend`] on this thread
+pub fn use_plain() {
+    set_encrypt_factory(|_: &Path| Box::new(PlainBackend));
+    set_decrypt_factory(|_: &Path| Box::new(PlainBackend));
+}
+
+/// Encrypt and decrypt with [`RecordingBackend`] on this thread
+pub fn use_recording() {
+    set_encrypt_factory(|_: &Path| Box::new(RecordingBackend));
+    set_decrypt_factory(|_: &Path| Box::new(RecordingBackend));
+}
+
+/// A test vault in `base` encrypting to `age1me` with [`PlainBackend`]
+pub fn plain_vault(base: &Path) -> Config {
+    use_plain();
+    keyed_vault(base)
+}
+
+/// A test vault in `base` encrypting to `age1me` with [`RecordingBackend`]
+pub fn recording_vault(base: &Path) -> Config {
+    use_recording();
+    keyed_vault(base)
+}
+
+fn keyed_vault(base: &Path) -> Config {
+    let cfg = test_config(base);
+    fs::write(base.join(&cfg.public_key_filename), "age1me\n").unwrap();
+    cfg
+}
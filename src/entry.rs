@@ -0,0 +1,235 @@
+use std::fmt;
+use zeroize::Zeroize;
+
+/// The plaintext layout of an entry:
+///
+/// ```text
+/// <password>
+/// username: alice
+/// url: https://example.com
+///
+/// free-form notes...
+/// ```
+///
+/// The first line is the password. `key: value` lines follow it; the first
+/// line that isn't one starts the notes. Files that predate this layout still
+/// parse: anything after the first line simply becomes notes.
+#[derive(Default, Clone, PartialEq)]
+pub struct Entry {
+    pub password: String,
+
+    /// `key: value` pairs in file order
+    pub fields: Vec<(String, String)>,
+
+    pub notes: String,
+}
+
+impl Entry {
+    pub fn parse(text: &str) -> Self {
+        let mut lines = text.lines();
+        let password = lines.next().unwrap_or_default().to_string();
+
+        let mut fields = Vec::new();
+        let mut rest = Vec::new();
+        for line in lines.by_ref() {
+            match parse_field(line) {
+                Some((k, v)) => fields.push((k.to_string(), v.to_string())),
+                None => {
+                    rest.push(line);
+                    break;
+                }
+            }
+        }
+        rest.extend(lines);
+
+        let notes = rest.join("\n").trim().to_string();
+        Entry {
+            password,
+            fields,
+            notes,
+        }
+    }
+
+    /// First value for `key`, compared case-insensitively
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Tags from the `tags:` field, split on commas and whitespace,
+    /// lower-cased and without duplicates
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.field("tags").unwrap_or_default().split([',', ' ']) {
+            let tag = tag.trim().trim_start_matches('#').to_lowercase();
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+
+    /// Replace the first `key` field, or append one. Empty values are skipped
+    /// so imports don't litter entries with `url: ` lines, and keys from other
+    /// tools ("Security Answer") are squashed into a parseable `security-answer`.
+    pub fn set_field(&mut self, key: &str, value: &str) {
+        let value = value.trim().replace(['\r', '\n'], " ");
+        let key = normalize_key(key);
+        if value.is_empty() || key.is_empty() {
+            return;
+        }
+        match self
+            .fields
+            .iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(&key))
+        {
+            Some((_, v)) => *v = value,
+            None => self.fields.push((key, value)),
+        }
+    }
+}
+
+/// Field names are shown, values never are
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys: Vec<&str> = self.fields.iter().map(|(k, _)| k.as_str()).collect();
+        f.debug_struct("Entry")
+            .field("password", &"<redacted>")
+            .field("fields", &keys)
+            .field("notes", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        self.password.zeroize();
+        for (_, value) in &mut self.fields {
+            value.zeroize();
+        }
+        self.notes.zeroize();
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.password)?;
+        for (k, v) in &self.fields {
+            write!(f, "\n{}: {}", k, v)?;
+        }
+        if !self.notes.is_empty() {
+            write!(f, "\n\n{}", self.notes)?;
+        }
+        Ok(())
+    }
+}
+
+fn normalize_key(key: &str) -> String {
+    let key: String = key
+        .trim()
+        .chars()
+        .map(|c| if c.is_whitespace() { '-' } else { c })
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    key.to_lowercase()
+}
+
+/// `key: value`, where the key is a single word of letters, digits, `-` or `_`
+fn parse_field(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let valid_key = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_key || !(value.is_empty() || value.starts_with(' ')) {
+        return None;
+    }
+    Some((key, value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_password_fields_and_notes() {
+        let e = Entry::parse(
+            "hunter2\nusername: alice\nURL: https://x.test\n\nline one\nkey: not a field",
+        );
+        assert_eq!(e.password, "hunter2");
+        assert_eq!(e.field("username"), Some("alice"));
+        assert_eq!(e.field("url"), Some("https://x.test"));
+        assert_eq!(e.notes, "line one\nkey: not a field");
+    }
+
+    #[test]
+    fn plain_multiline_files_become_notes() {
+        let e = Entry::parse("hunter2\nsecurity question was the dog's name");
+        assert!(e.fields.is_empty());
+        assert_eq!(e.notes, "security question was the dog's name");
+    }
+
+    #[test]
+    fn urls_without_a_key_are_not_fields() {
+        let e = Entry::parse("pw\nhttps://example.com");
+        assert!(e.fields.is_empty());
+        assert_eq!(e.notes, "https://example.com");
+    }
+
+    #[test]
+    fn display_roundtrips() {
+        let mut e = Entry {
+            password: "pw".into(),
+            notes: "multi\nline".into(),
+            fields: Vec::new(),
+        };
+        e.set_field("username", "bob");
+        e.set_field("url", "   ");
+        e.set_field("totp", "otpauth://totp/x?secret=ABC");
+
+        let text = e.to_string();
+        assert_eq!(
+            text,
+            "pw\nusername: bob\ntotp: otpauth://totp/x?secret=ABC\n\nmulti\nline"
+        );
+        assert_eq!(Entry::parse(&text), e);
+    }
+
+    #[test]
+    fn foreign_keys_are_normalized() {
+        let mut e = Entry::default();
+        e.set_field("Security Answer", "blue");
+        e.set_field("!!!", "dropped");
+        assert_eq!(e.field("security-answer"), Some("blue"));
+        assert_eq!(e.fields.len(), 1);
+    }
+
+    #[test]
+    fn tags_are_split_and_normalized() {
+        let e = Entry::parse("pw\ntags: prod, #Web  db,prod");
+        assert_eq!(e.tags(), vec!["prod", "web", "db"]);
+        assert!(Entry::parse("pw").tags().is_empty());
+    }
+
+    #[test]
+    fn set_field_replaces_existing() {
+        let mut e = Entry::parse("pw\nUsername: old");
+        e.set_field("username", "new\nline");
+        assert_eq!(
+            e.fields,
+            vec![("Username".to_string(), "new line".to_string())]
+        );
+    }
+
+    #[test]
+    fn debug_hides_values() {
+        let e = Entry::parse("hunter2\nusername: bob\n\nrecovery codes");
+        let shown = format!("{:?}", e);
+        assert!(shown.contains("username"));
+        for secret in ["hunter2", "bob", "recovery"] {
+            assert!(!shown.contains(secret), "{} leaked: {}", secret, shown);
+        }
+    }
+}
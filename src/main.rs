@@ -3,17 +3,40 @@ mod commands;
 mod completions;
 mod config;
 mod crypto;
+mod entry;
 mod utils;
 
 use anyhow::Result;
 use clap::Parser;
-use cli::{Cli, Command};
+use cli::{Cli, Command, ExportFormat, ImportSource};
 use serde_json::to_string_pretty;
 use utils::gather_config::extant_config;
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    let cfg = config::load_config()?;
+
+    // Vault management edits the config file itself, so don't resolve one first
+    if let Command::Vault { action } = cli.command {
+        return commands::vault::run(action);
+    }
+    let cfg = config::load_config(cli.vault.as_deref())?;
+    config::register(&cfg);
+    // `doctor` reports a readable identity itself, and `init` may replace it
+    if !matches!(cli.command, Command::Init | Command::Doctor { .. }) {
+        utils::secure_fs::check_identity(&cfg.secret)?;
+    }
+    // a hidden-names vault is unusable without its manifest; commands that
+    // never look up an entry still run, to help repair things
+    if !matches!(
+        cli.command,
+        Command::Init
+            | Command::Doctor { .. }
+            | Command::Show { config: true, .. }
+            | Command::Key { .. }
+            | Command::Agent { .. }
+    ) {
+        utils::manifest::open(&cfg)?;
+    }
 
     match cli.command {
         Command::Init => {
@@ -22,7 +45,7 @@ fn main() -> Result<()> {
 
         Command::Show { config: true, .. } => {
             // Dump runtime config
-            let dump = extant_config()?;
+            let dump = extant_config(cli.vault.as_deref())?;
             println!("{}", to_string_pretty(&dump)?);
         }
 
@@ -53,8 +76,47 @@ fn main() -> Result<()> {
             commands::edit::run(&cfg, path)?;
         }
 
-        Command::List { path, all } => {
-            commands::list::run(&cfg, path, all)?;
+        Command::List {
+            path,
+            all,
+            stale,
+            tag,
+            tags,
+            flat,
+        } => {
+            let opts = commands::list::Options {
+                show_all: all,
+                stale,
+                tag,
+                show_tags: tags,
+                flat,
+            };
+            commands::list::run(&cfg, path, opts)?;
+        }
+
+        Command::Tags { all } => {
+            commands::tags::run(&cfg, all)?;
+        }
+
+        Command::Reindex => {
+            commands::reindex::run(&cfg)?;
+        }
+
+        Command::Mv { from, to } => {
+            commands::mv::run(&cfg, &from, &to)?;
+        }
+
+        Command::Generate {
+            path,
+            length,
+            no_symbols,
+            in_place,
+        } => {
+            commands::generate::run(&cfg, path, length, no_symbols, in_place)?;
+        }
+
+        Command::Expiring { within, all } => {
+            commands::expiring::run(&cfg, &within, all)?;
         }
 
         Command::Archive { path, folder } => {
@@ -64,7 +126,137 @@ fn main() -> Result<()> {
         Command::Remove { path } => {
             commands::remove::run(&cfg, path)?;
         }
+
+        Command::Convert { to } => {
+            commands::convert::run(&cfg, &to)?;
+        }
+
+        Command::Find { pattern, meta, all } => {
+            commands::find::run(&pattern, meta, all)?;
+        }
+
+        Command::Audit {
+            json,
+            all,
+            breached,
+            hibp_api,
+        } => {
+            let opts = commands::audit::Options {
+                json,
+                show_all: all,
+                breached,
+                hibp_api,
+            };
+            commands::audit::run(&cfg, opts)?;
+        }
+
+        Command::Export {
+            subtree,
+            format,
+            json,
+            glob,
+            no_notes,
+            output,
+            force,
+            recipient,
+            passphrase,
+            all,
+        } => {
+            let opts = commands::export::Options {
+                format: if json { ExportFormat::Json } else { format },
+                output,
+                subtree,
+                glob,
+                no_notes,
+                recipients: recipient,
+                passphrase,
+                show_all: all,
+                force,
+            };
+            commands::export::run(&cfg, opts)?;
+        }
+
+        Command::Import { source } => match source {
+            ImportSource::Pass {
+                store,
+                recipient_map,
+                dry_run,
+            } => commands::import::pass::run(&cfg, store, recipient_map, dry_run)?,
+            ImportSource::Csv {
+                format,
+                file,
+                prefix,
+                on_conflict,
+                dry_run,
+            } => commands::import::csv::run(&cfg, format, &file, &prefix, on_conflict, dry_run)?,
+            ImportSource::Bundle {
+                file,
+                identity,
+                on_conflict,
+                restore_recipients,
+                dry_run,
+            } => commands::import::bundle::run(
+                &cfg,
+                &file,
+                identity,
+                on_conflict,
+                restore_recipients,
+                dry_run,
+            )?,
+            ImportSource::Keepass {
+                file,
+                key_file,
+                prefix,
+                on_conflict,
+                dry_run,
+            } => commands::import::keepass::run(
+                &cfg,
+                &file,
+                key_file,
+                &prefix,
+                on_conflict,
+                dry_run,
+            )?,
+        },
+
+        Command::Names { action } => {
+            commands::names::run(&cfg, action)?;
+        }
+
+        Command::Fsck { fix } => {
+            commands::fsck::run(&cfg, fix)?;
+        }
+
+        Command::Access { path } => {
+            commands::access::run(&cfg, &path)?;
+        }
+
+        Command::Verify { trust, sign } => {
+            commands::verify::run(&cfg, trust, sign)?;
+        }
+
+        Command::Device { action } => {
+            commands::device::run(&cfg, action)?;
+        }
+
+        Command::Key { action } => {
+            commands::key::run(&cfg, action)?;
+        }
+
+        Command::Agent { timeout, action } => {
+            commands::agent::run(&cfg, timeout, action)?;
+        }
+
+        Command::Doctor { json } => {
+            commands::doctor::run(&cfg, json)?;
+        }
+
+        Command::Vault { .. } => {
+            unreachable!("`vault` is handled before the config is loaded");
+        }
     }
 
+    // IDs handed out for new entries are only saved once, at the end
+    utils::manifest::flush(&cfg.prefix)?;
     Ok(())
 }
@@ -0,0 +1,235 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+const VERSION_LINE: &str = "age-encryption.org/v1";
+const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const ARMOR_END: &str = "-----END AGE ENCRYPTED FILE-----";
+
+/// Stanza bodies are wrapped at this many base64 characters; a shorter
+/// line ends the body
+const BODY_LINE: usize = 64;
+
+/// One recipient stanza: `-> <tag> <args...>` plus its wrapped body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stanza {
+    pub tag: String,
+    pub args: Vec<String>,
+}
+
+impl Stanza {
+    /// age and rage add a random stanza or two so parsers stay lenient;
+    /// they don't stand for a recipient
+    pub fn is_grease(&self) -> bool {
+        self.tag.ends_with("-grease")
+    }
+
+    /// What the stanza reveals about its recipient: the key type, plus the
+    /// key's short tag for SSH recipients. X25519 stanzas can't be told
+    /// apart without the matching identity.
+    pub fn kind(&self) -> String {
+        match (self.tag.as_str(), self.args.first()) {
+            ("ssh-ed25519" | "ssh-rsa", Some(key_tag)) => format!("{} {}", self.tag, key_tag),
+            _ => self.tag.clone(),
+        }
+    }
+}
+
+/// The parsed header of an age file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    /// Recipient stanzas, grease included
+    pub stanzas: Vec<Stanza>,
+    /// Whether the file is ASCII-armored
+    pub armored: bool,
+}
+
+impl Header {
+    /// `kind()` of every real recipient stanza, sorted
+    pub fn recipient_kinds(&self) -> Vec<String> {
+        let mut kinds: Vec<String> = self
+            .stanzas
+            .iter()
+            .filter(|s| !s.is_grease())
+            .map(Stanza::kind)
+            .collect();
+        kinds.sort();
+        kinds
+    }
+}
+
+/// Read and parse the header of the age file at `path`
+pub fn read(path: &Path) -> Result<Header> {
+    let mut raw = Vec::new();
+    File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?
+        .read_to_end(&mut raw)?;
+    parse(&raw).with_context(|| format!("{} is not a valid age file", path.display()))
+}
+
+/// Parse an age header from the start of `raw`, armored or binary
+pub fn parse(raw: &[u8]) -> Result<Header> {
+    let trimmed = raw.trim_ascii_start();
+    if trimmed.starts_with(ARMOR_BEGIN.as_bytes()) {
+        let text = std::str::from_utf8(trimmed).context("Armored file is not UTF-8")?;
+        let body: String = text
+            .lines()
+            .skip(1)
+            .take_while(|l| l.trim() != ARMOR_END)
+            .map(str::trim)
+            .collect();
+        let binary = STANDARD
+            .decode(body)
+            .context("Armored file is not valid base64")?;
+        return Ok(Header {
+            armored: true,
+            ..parse_binary(&binary)?
+        });
+    }
+    parse_binary(raw)
+}
+
+fn parse_binary(raw: &[u8]) -> Result<Header> {
+    let mut lines = BufReader::new(raw).split(b'\n');
+    let mut next_line = || -> Result<String> {
+        let line = lines.next().context("Header ends early")??;
+        String::from_utf8(line).context("Header is not text")
+    };
+
+    let version = next_line()?;
+    if version != VERSION_LINE {
+        anyhow::bail!("Unknown header line `{}`", version);
+    }
+
+    let mut stanzas = Vec::new();
+    loop {
+        let line = next_line()?;
+        if line.starts_with("---") {
+            break;
+        }
+        let Some(rest) = line.strip_prefix("-> ") else {
+            anyhow::bail!("Unexpected header line `{}`", line);
+        };
+        let mut parts = rest.split(' ');
+        let tag = parts.next().unwrap_or_default().to_string();
+        if tag.is_empty() {
+            anyhow::bail!("Stanza without a type");
+        }
+        stanzas.push(Stanza {
+            tag,
+            args: parts.map(str::to_string).collect(),
+        });
+        // skip the body
+        while next_line()?.len() >= BODY_LINE {}
+    }
+    if stanzas.is_empty() {
+        anyhow::bail!("Header has no recipients");
+    }
+    Ok(Header {
+        stanzas,
+        armored: false,
+    })
+}
+
+/// The `kind()` a stanza for `recipient` would have, if the recipient
+/// string is one age understands
+pub fn recipient_kind(recipient: &str) -> Option<String> {
+    let recipient = recipient.trim();
+    if let Some(rest) = recipient.strip_prefix("age1") {
+        // bech32 data never contains `1`, so a second one marks a plugin
+        // recipient: age1<plugin>1...
+        return Some(if rest.contains('1') {
+            let plugin = rest.split('1').next().unwrap_or_default();
+            format!("plugin:{}", plugin)
+        } else {
+            "X25519".to_string()
+        });
+    }
+    let mut parts = recipient.split_whitespace();
+    let key_type = parts.next()?;
+    if key_type != "ssh-ed25519" && key_type != "ssh-rsa" {
+        return None;
+    }
+    let blob = STANDARD.decode(parts.next()?).ok()?;
+    Some(format!("{} {}", key_type, ssh_tag(&blob)))
+}
+
+/// age's short SSH key tag: the first four bytes of the SHA-256 of the
+/// key's wire encoding
+pub fn ssh_tag(blob: &[u8]) -> String {
+    STANDARD_NO_PAD.encode(&Sha256::digest(blob)[..4])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::str::FromStr;
+
+    fn encrypt(recipients: &[String], armor: bool) -> Vec<u8> {
+        let recipients: Vec<age::x25519::Recipient> = recipients
+            .iter()
+            .map(|r| age::x25519::Recipient::from_str(r).unwrap())
+            .collect();
+        let encryptor =
+            age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+                .unwrap();
+        let mut out = Vec::new();
+        let mut writer = encryptor.wrap_output(&mut out).unwrap();
+        writer.write_all(b"secret").unwrap();
+        writer.finish().unwrap();
+        if armor {
+            let b64 = STANDARD.encode(&out);
+            let lines: Vec<&str> = b64
+                .as_bytes()
+                .chunks(BODY_LINE)
+                .map(|c| std::str::from_utf8(c).unwrap())
+                .collect();
+            out = format!("{}\n{}\n{}\n", ARMOR_BEGIN, lines.join("\n"), ARMOR_END).into_bytes();
+        }
+        out
+    }
+
+    fn keys(n: usize) -> Vec<String> {
+        (0..n)
+            .map(|_| age::x25519::Identity::generate().to_public().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parses_binary_and_armored_headers() -> Result<()> {
+        let header = parse(&encrypt(&keys(2), false))?;
+        assert!(!header.armored);
+        assert_eq!(header.recipient_kinds(), vec!["X25519", "X25519"]);
+
+        let header = parse(&encrypt(&keys(1), true))?;
+        assert!(header.armored);
+        assert_eq!(header.recipient_kinds(), vec!["X25519"]);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(parse(b"hunter2\n").is_err());
+        assert!(parse(b"").is_err());
+        assert!(parse(b"age-encryption.org/v1\n--- abc\n").is_err());
+    }
+
+    #[test]
+    fn recipient_kinds_match_stanzas() {
+        assert_eq!(recipient_kind(&keys(1)[0]).as_deref(), Some("X25519"));
+        assert_eq!(recipient_kind("not a key"), None);
+        let ssh = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHsKLqeplhpW+uObz5dvMgjz1OxfM/XXUB+VHtZ6isGN me@host";
+        let kind = recipient_kind(ssh).unwrap();
+        assert!(kind.starts_with("ssh-ed25519 "));
+        let stanza = Stanza {
+            tag: "ssh-ed25519".into(),
+            args: vec![kind[12..].to_string(), "ephemeral".into()],
+        };
+        assert_eq!(stanza.kind(), kind);
+    }
+}
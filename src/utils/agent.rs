@@ -0,0 +1,365 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use zeroize::Zeroize;
+
+use crate::crypto::SecretString;
+use crate::crypto::{self, identity};
+use crate::utils::secure_fs;
+
+/// Socket file inside `secure_fs::scratch_dir`
+pub const SOCKET_NAME: &str = "agent.sock";
+
+/// How often an idle agent checks whether its time is up
+const POLL: Duration = Duration::from_millis(200);
+
+/// One line of JSON from the CLI to the agent
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Request {
+    Decrypt { identity: PathBuf, file: PathBuf },
+    Status,
+    Lock,
+}
+
+/// One line of JSON back. No `Debug`: it can carry plaintext.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "reply", rename_all = "lowercase")]
+pub enum Reply {
+    Plaintext {
+        plaintext: String,
+    },
+    /// The agent holds a different identity; decrypt directly instead
+    Unavailable,
+    Error {
+        message: String,
+    },
+    Status {
+        pid: u32,
+        identity: PathBuf,
+        idle_left: u64,
+    },
+    Locked,
+}
+
+impl Drop for Reply {
+    fn drop(&mut self) {
+        if let Reply::Plaintext { plaintext } = self {
+            plaintext.zeroize();
+        }
+    }
+}
+
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(secure_fs::scratch_dir()?.join(SOCKET_NAME))
+}
+
+/// Send one request to the agent listening on `socket`. `None` means no
+/// agent is running there.
+pub fn request(socket: &Path, request: &Request) -> Result<Option<Reply>> {
+    let mut stream = match UnixStream::connect(socket) {
+        Ok(stream) => stream,
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+            return Ok(None);
+        }
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to reach the agent at {}", socket.display()));
+        }
+    };
+    stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+
+    let mut raw = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut raw)
+        .context("No reply from the agent")?;
+    let reply = serde_json::from_str(&raw).context("Garbled reply from the agent");
+    raw.zeroize();
+    reply.map(Some)
+}
+
+/// Decrypt through a running agent. `None` means no agent holds this
+/// identity, and the caller should decrypt by itself.
+pub fn decrypt(identity: &Path, file: &Path) -> Result<Option<SecretString>> {
+    let Ok(socket) = socket_path() else {
+        return Ok(None);
+    };
+    let request = Request::Decrypt {
+        identity: std::path::absolute(identity)?,
+        file: std::path::absolute(file)?,
+    };
+    match self::request(&socket, &request)? {
+        None | Some(Reply::Unavailable) => Ok(None),
+        Some(Reply::Plaintext { ref plaintext }) => Ok(Some(plaintext.as_str().into())),
+        Some(Reply::Error { ref message }) => Err(anyhow::anyhow!(message.clone())),
+        Some(_) => anyhow::bail!("Unexpected reply from the agent"),
+    }
+}
+
+/// The daemon side: holds one unlocked identity (see
+/// `identity::remember`) and decrypts for clients of the same user until
+/// it is locked or has been idle for `timeout`
+pub struct Server {
+    pub identity: PathBuf,
+    pub timeout: Duration,
+    /// The only user allowed to connect
+    pub uid: u32,
+}
+
+impl Server {
+    pub fn new(identity: PathBuf, timeout: Duration) -> Self {
+        Server {
+            identity,
+            timeout,
+            uid: unsafe { libc::getuid() },
+        }
+    }
+
+    /// Bind `socket`, replacing a stale one left by an agent that died
+    pub fn bind(socket: &Path) -> Result<UnixListener> {
+        if socket.exists() {
+            if UnixStream::connect(socket).is_ok() {
+                anyhow::bail!("An agent is already listening on {}", socket.display());
+            }
+            fs::remove_file(socket)?;
+        }
+        let listener = UnixListener::bind(socket)
+            .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+        secure_fs::private_file(socket)?;
+        Ok(listener)
+    }
+
+    /// Answer requests until `lock` or the idle timeout, then wipe the key
+    pub fn serve(&self, listener: &UnixListener) -> Result<()> {
+        listener.set_nonblocking(true)?;
+        let mut last_used = Instant::now();
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let idle_left = self.timeout.saturating_sub(last_used.elapsed());
+                    match self.handle(stream, idle_left) {
+                        Ok(Request::Lock) => break,
+                        Ok(Request::Decrypt { .. }) => last_used = Instant::now(),
+                        Ok(Request::Status) => {}
+                        Err(e) => eprintln!("passman agent: {:#}", e),
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    if last_used.elapsed() >= self.timeout {
+                        break;
+                    }
+                    thread::sleep(POLL);
+                }
+                Err(e) => return Err(e).context("Agent socket failed"),
+            }
+        }
+        identity::forget();
+        Ok(())
+    }
+
+    /// Answer one connection; returns the request it carried
+    fn handle(&self, mut stream: UnixStream, idle_left: Duration) -> Result<Request> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+        let peer = peer_uid(&stream)?;
+        if peer != self.uid {
+            send(
+                &mut stream,
+                Reply::Error {
+                    message: format!("passman agent refused a connection from uid {}", peer),
+                },
+            )?;
+            anyhow::bail!("refused a connection from uid {}", peer);
+        }
+
+        let mut raw = String::new();
+        BufReader::new(&stream).read_line(&mut raw)?;
+        let request: Request = serde_json::from_str(&raw).context("Malformed request")?;
+
+        let reply = match &request {
+            Request::Decrypt { identity, file } if identity == &self.identity => {
+                match crypto::decrypt(identity, file) {
+                    Ok(plaintext) => Reply::Plaintext {
+                        plaintext: plaintext.expose().to_string(),
+                    },
+                    Err(e) => Reply::Error {
+                        message: format!("{:#}", e),
+                    },
+                }
+            }
+            Request::Decrypt { .. } => Reply::Unavailable,
+            Request::Status => Reply::Status {
+                pid: std::process::id(),
+                identity: self.identity.clone(),
+                idle_left: idle_left.as_secs(),
+            },
+            Request::Lock => Reply::Locked,
+        };
+        send(&mut stream, reply)?;
+        Ok(request)
+    }
+}
+
+fn send(stream: &mut UnixStream, reply: Reply) -> Result<()> {
+    let mut line = serde_json::to_vec(&reply)?;
+    line.push(b'\n');
+    let sent = stream.write_all(&line);
+    line.zeroize();
+    Ok(sent?)
+}
+
+/// The uid of the process on the other end of `stream`
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let rc = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&mut cred as *mut libc::ucred).cast(),
+            &mut len,
+        )
+    };
+    if rc != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let (mut uid, mut gid) = (0, 0);
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{CryptoBackend, set_decrypt_factory};
+    use tempfile::TempDir;
+
+    struct KeyEcho;
+    impl CryptoBackend for KeyEcho {
+        fn encrypt(&self, _r: &[String], _o: &Path, _p: &SecretString) -> Result<()> {
+            unreachable!()
+        }
+        /// "<identity contents>:<file name>", to show which key was used
+        fn decrypt(&self, i: &Path, e: &Path) -> Result<SecretString> {
+            let key = fs::read_to_string(i)?;
+            let name = e.file_name().unwrap().to_string_lossy();
+            Ok(format!("{}:{}", key, name).into())
+        }
+    }
+
+    /// Run an agent for `identity` in a thread, with `configure` applied
+    fn spawn(
+        tmp: &TempDir,
+        identity: &Path,
+        configure: fn(&mut Server),
+    ) -> Result<(PathBuf, thread::JoinHandle<Result<()>>)> {
+        let socket = tmp.path().join(SOCKET_NAME);
+        let listener = Server::bind(&socket)?;
+        let identity = identity.to_path_buf();
+        let handle = thread::spawn(move || {
+            set_decrypt_factory(|_: &Path| Box::new(KeyEcho));
+            identity::remember(&identity, "AGE-SECRET-KEY-1UNLOCKED".into());
+            let mut server = Server::new(identity, Duration::from_secs(60));
+            configure(&mut server);
+            server.serve(&listener)
+        });
+        Ok((socket, handle))
+    }
+
+    fn protected_identity(tmp: &TempDir) -> Result<PathBuf> {
+        let path = tmp.path().join("private.rage");
+        fs::write(&path, "age-encryption.org/v1\n-> scrypt …")?;
+        Ok(path)
+    }
+
+    #[test]
+    fn agent_decrypts_with_the_key_it_holds_until_locked() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let id = protected_identity(&tmp)?;
+        let (socket, agent) = spawn(&tmp, &id, |_| {})?;
+
+        let decrypt = Request::Decrypt {
+            identity: id.clone(),
+            file: tmp.path().join("web.rage"),
+        };
+        match request(&socket, &decrypt)? {
+            Some(Reply::Plaintext { ref plaintext }) => {
+                assert_eq!(plaintext, "AGE-SECRET-KEY-1UNLOCKED:web.rage")
+            }
+            _ => panic!("expected plaintext"),
+        }
+
+        let other = Request::Decrypt {
+            identity: tmp.path().join("other.rage"),
+            file: tmp.path().join("web.rage"),
+        };
+        assert!(matches!(
+            request(&socket, &other)?,
+            Some(Reply::Unavailable)
+        ));
+        assert!(matches!(
+            request(&socket, &Request::Status)?,
+            Some(Reply::Status { ref identity, .. }) if identity == &id
+        ));
+
+        assert!(matches!(
+            request(&socket, &Request::Lock)?,
+            Some(Reply::Locked)
+        ));
+        agent.join().unwrap()?;
+        assert!(request(&socket, &Request::Status)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn other_users_are_refused() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let id = protected_identity(&tmp)?;
+        let (socket, agent) = spawn(&tmp, &id, |s| {
+            s.uid = s.uid.wrapping_add(1);
+            s.timeout = Duration::from_millis(500);
+        })?;
+
+        match request(&socket, &Request::Lock)? {
+            Some(Reply::Error { ref message }) => {
+                assert!(message.contains("refused"), "{}", message)
+            }
+            _ => panic!("expected a refusal"),
+        }
+        // the refused lock did nothing; the agent stops once idle
+        agent.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn idle_agents_exit() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let id = protected_identity(&tmp)?;
+        let (_, agent) = spawn(&tmp, &id, |s| s.timeout = Duration::from_millis(10))?;
+        agent.join().unwrap()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use crate::crypto::{SecretString, ssh};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::iter;
+use std::path::Path;
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+/// Bundle layout version, bumped on incompatible changes
+pub const VERSION: u32 = 1;
+
+/// Everything needed to rebuild a vault elsewhere. Serialized as JSON and only
+/// ever written to disk inside a single age file.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Bundle {
+    pub version: u32,
+
+    /// Entry name → decrypted contents
+    #[serde(with = "secret_values")]
+    pub entries: BTreeMap<String, SecretString>,
+
+    /// Folder (relative to the vault, `""` for the root) → its `.recipients` file
+    #[serde(default)]
+    pub recipients: BTreeMap<String, String>,
+}
+
+impl PartialEq for Bundle {
+    fn eq(&self, other: &Self) -> bool {
+        self.version == other.version
+            && self.recipients == other.recipients
+            && self.entries.len() == other.entries.len()
+            && self
+                .entries
+                .iter()
+                .zip(&other.entries)
+                .all(|((a, x), (b, y))| a == b && x.expose() == y.expose())
+    }
+}
+
+/// `SecretString` keeps no serde impls of its own, so nothing else can
+/// serialize a secret by accident
+mod secret_values {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        map: &BTreeMap<String, SecretString>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(k, v)| (k, v.expose())))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<String, SecretString>, D::Error> {
+        let raw = BTreeMap::<String, String>::deserialize(deserializer)?;
+        Ok(raw
+            .into_iter()
+            .map(|(k, v)| (k, SecretString::new(v)))
+            .collect())
+    }
+}
+
+/// Who can open a bundle
+pub enum Seal {
+    Recipients(Vec<String>),
+    Passphrase(SecretString),
+}
+
+/// Encrypt `bundle` into `out` as one age file
+pub fn seal(bundle: &Bundle, seal: &Seal, out: impl Write) -> Result<()> {
+    let encryptor = match seal {
+        Seal::Recipients(list) => {
+            let recipients = list
+                .iter()
+                .map(|r| parse_recipient(r))
+                .collect::<Result<Vec<_>>>()?;
+            age::Encryptor::with_recipients(recipients.iter().map(|r| r.as_ref()))?
+        }
+        Seal::Passphrase(passphrase) => {
+            #[allow(unused_mut)]
+            let mut recipient = age::scrypt::Recipient::new(age::secrecy::SecretString::from(
+                passphrase.expose().to_string(),
+            ));
+            // the default work factor takes seconds in debug builds
+            #[cfg(test)]
+            recipient.set_work_factor(4);
+            age::Encryptor::with_recipients(iter::once(&recipient as &dyn age::Recipient))?
+        }
+    };
+
+    let json = Zeroizing::new(serde_json::to_vec(bundle)?);
+    let mut writer = encryptor.wrap_output(out)?;
+    writer.write_all(&json)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// An `age1…` recipient or an SSH public key, as recipients files hold them
+fn parse_recipient(r: &str) -> Result<Box<dyn age::Recipient>> {
+    if ssh::is_public_key(r) {
+        let key = ssh::recipient(r)
+            .with_context(|| format!("age can't encrypt to the SSH key `{}`", r))?;
+        let recipient = age::ssh::Recipient::from_str(&key)
+            .map_err(|e| anyhow::anyhow!("Invalid SSH recipient `{}`: {:?}", r, e))?;
+        return Ok(Box::new(recipient));
+    }
+    let recipient = age::x25519::Recipient::from_str(r)
+        .map_err(|e| anyhow::anyhow!("Invalid age recipient `{}`: {}", r, e))?;
+    Ok(Box::new(recipient))
+}
+
+/// Decrypt a bundle with the identity file, or with the passphrase from
+/// `passphrase` when the bundle was sealed with one
+pub fn open(
+    input: impl Read,
+    identity: &Path,
+    passphrase: impl FnOnce() -> Result<String>,
+) -> Result<Bundle> {
+    let decryptor = age::Decryptor::new(input).context("Not an age-encrypted bundle")?;
+
+    let identities: Vec<Box<dyn age::Identity>> = if decryptor.is_scrypt() {
+        vec![Box::new(age::scrypt::Identity::new(
+            age::secrecy::SecretString::from(passphrase()?),
+        ))]
+    } else {
+        crate::crypto::identity::load(identity)?
+    };
+
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|i| i.as_ref()))
+        .context("Failed to decrypt bundle")?;
+    let mut json = Zeroizing::new(Vec::new());
+    reader.read_to_end(&mut json)?;
+
+    let bundle: Bundle = serde_json::from_slice(&json).context("Bundle contents are corrupted")?;
+    if bundle.version > VERSION {
+        anyhow::bail!(
+            "Bundle version {} is newer than this passman understands ({})",
+            bundle.version,
+            VERSION
+        );
+    }
+    Ok(bundle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::secrecy::ExposeSecret;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn sample() -> Bundle {
+        Bundle {
+            version: VERSION,
+            entries: BTreeMap::from([("web/github".into(), "pw\nusername: me".into())]),
+            recipients: BTreeMap::from([("team".into(), "age1xyz\n".into())]),
+        }
+    }
+
+    #[test]
+    fn roundtrips_with_an_identity() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let identity = age::x25519::Identity::generate();
+        let key_file = tmp.path().join("key.txt");
+        fs::write(&key_file, identity.to_string().expose_secret())?;
+
+        let mut sealed = Vec::new();
+        let seal_with = Seal::Recipients(vec![identity.to_public().to_string()]);
+        seal(&sample(), &seal_with, &mut sealed)?;
+
+        let opened = open(sealed.as_slice(), &key_file, || unreachable!())?;
+        assert_eq!(opened, sample());
+        Ok(())
+    }
+
+    #[test]
+    fn roundtrips_with_a_passphrase() -> Result<()> {
+        let mut sealed = Vec::new();
+        seal(
+            &sample(),
+            &Seal::Passphrase("correct horse".into()),
+            &mut sealed,
+        )?;
+
+        let opened = open(sealed.as_slice(), Path::new("/nonexistent"), || {
+            Ok("correct horse".into())
+        })?;
+        assert_eq!(opened, sample());
+
+        let wrong = open(sealed.as_slice(), Path::new("/nonexistent"), || {
+            Ok("wrong".into())
+        });
+        assert!(wrong.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn seals_to_ssh_recipients() -> Result<()> {
+        let alice = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHsKLqeplhpW+uObz5dvMgjz1OxfM/XXUB+VHtZ6isGN alice@laptop";
+        let mut sealed = Vec::new();
+        let seal_with = Seal::Recipients(vec![
+            alice.into(),
+            age::x25519::Identity::generate().to_public().to_string(),
+        ]);
+        seal(&sample(), &seal_with, &mut sealed)?;
+        assert!(!sealed.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_bad_recipients() {
+        let err = seal(
+            &sample(),
+            &Seal::Recipients(vec!["nope".into()]),
+            Vec::new(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().starts_with("Invalid age recipient `nope`"));
+    }
+}
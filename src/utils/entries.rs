@@ -0,0 +1,59 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::config::strip_entry_extension;
+use crate::utils::index::{self, INDEX_NAME};
+use crate::utils::manifest;
+
+/// An encrypted entry found under the vault
+pub struct EntryFile {
+    /// Path relative to the vault root, without the crypto extension
+    pub name: String,
+
+    /// Full path to the `.age` / `.rage` file
+    pub path: PathBuf,
+}
+
+/// Every `.age` / `.rage` file under `root`, sorted by path.
+/// `.git` and the search index are always skipped; hidden (archived)
+/// entries only with `show_all`. In a hidden-names vault the names come
+/// from its manifest.
+pub fn collect(root: &Path, show_all: bool) -> Result<Vec<EntryFile>> {
+    if let Some(named) = manifest::entries(root)? {
+        return Ok(named
+            .into_iter()
+            .filter(|(name, _)| show_all || !index::is_hidden(name))
+            .map(|(name, path)| EntryFile { name, path })
+            .collect());
+    }
+
+    let walker = WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| {
+            let file_name = e.file_name().to_string_lossy();
+            e.depth() == 0 || (file_name != ".git" && (show_all || !file_name.starts_with('.')))
+        });
+
+    Ok(walker
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let rel = e
+                .path()
+                .strip_prefix(root)
+                .ok()?
+                .to_string_lossy()
+                .to_string();
+            let name = strip_entry_extension(&rel)?.to_string();
+            if name == INDEX_NAME || name == manifest::MANIFEST_NAME {
+                return None;
+            }
+            Some(EntryFile {
+                name,
+                path: e.into_path(),
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,268 @@
+use crate::entry::Entry;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Days, Local, Months, NaiveDate};
+use git2::{DiffOptions, Repository, Sort, Status};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `expires: 2025-06-30` — a fixed date the password must be changed by
+pub const EXPIRES: &str = "expires";
+
+/// `rotate-every: 90d` — how long a password may live after it was last changed
+pub const ROTATE_EVERY: &str = "rotate-every";
+
+/// `changed: 2025-03-10` — when the password itself last changed. Written
+/// on entries with a rotation rule whenever line 1 changes, so re-encrypting
+/// a file doesn't restart their clock.
+pub const CHANGED: &str = "changed";
+
+/// When an entry's password is due and which field said so
+#[derive(Debug, PartialEq)]
+pub struct Due {
+    pub date: NaiveDate,
+    pub rule: String,
+}
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// `date` moved forward by a period such as `14d`, `2w`, `3m` or `1y`
+pub fn add_period(date: NaiveDate, period: &str) -> Result<NaiveDate> {
+    let period = period.trim();
+    let invalid = || {
+        anyhow::anyhow!(
+            "Invalid period `{}` (expected e.g. 14d, 2w, 3m, 1y)",
+            period
+        )
+    };
+    let split = period.len().saturating_sub(1);
+    let (n, unit) = (period.get(..split).unwrap_or(""), period.get(split..));
+    let n: u32 = n.parse().map_err(|_| invalid())?;
+    let moved = match unit {
+        Some("d") => date.checked_add_days(Days::new(n.into())),
+        Some("w") => date.checked_add_days(Days::new(u64::from(n) * 7)),
+        Some("m") => date.checked_add_months(Months::new(n)),
+        Some("y") => date.checked_add_months(Months::new(n.checked_mul(12).ok_or_else(invalid)?)),
+        _ => return Err(invalid()),
+    };
+    moved.ok_or_else(invalid)
+}
+
+/// When `entry` is due. `expires:` wins over `rotate-every:`, which counts
+/// from the `changed:` field, or from when its file last changed for
+/// entries that predate it. Entries with neither rule never expire.
+pub fn due(entry: &Entry, last_changed: NaiveDate) -> Result<Option<Due>> {
+    if let Some(date) = entry.field(EXPIRES) {
+        return Ok(Some(Due {
+            date: parse_date(EXPIRES, date)?,
+            rule: format!("{}: {}", EXPIRES, date),
+        }));
+    }
+    if let Some(period) = entry.field(ROTATE_EVERY) {
+        let since = match entry.field(CHANGED) {
+            Some(date) => parse_date(CHANGED, date)?,
+            None => last_changed,
+        };
+        return Ok(Some(Due {
+            date: add_period(since, period)?,
+            rule: format!("{}: {}", ROTATE_EVERY, period),
+        }));
+    }
+    Ok(None)
+}
+
+fn parse_date(field: &str, date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .with_context(|| format!("Invalid {} date `{}` (expected YYYY-MM-DD)", field, date))
+}
+
+/// Record that the password changed `today`, on entries that rotate
+pub fn record_change(entry: &mut Entry, today: NaiveDate) {
+    if entry.field(ROTATE_EVERY).is_some() {
+        entry.set_field(CHANGED, &today.format("%Y-%m-%d").to_string());
+    }
+}
+
+/// Start the clock again after the password was replaced. A fixed
+/// `expires:` date moves forward by `rotate-every:` when there is one and
+/// is dropped otherwise, since it belonged to the old password.
+pub fn reset(entry: &mut Entry, today: NaiveDate) -> Result<()> {
+    record_change(entry, today);
+    if entry.field(EXPIRES).is_none() {
+        return Ok(());
+    }
+    match entry.field(ROTATE_EVERY).map(str::to_string) {
+        Some(period) => {
+            let next = add_period(today, &period)?;
+            entry.set_field(EXPIRES, &next.format("%Y-%m-%d").to_string());
+        }
+        None => entry
+            .fields
+            .retain(|(k, _)| !k.eq_ignore_ascii_case(EXPIRES)),
+    }
+    Ok(())
+}
+
+/// When each file last changed, for entries without a `changed:` field: the
+/// last commit touching it when the vault
+/// is a git repo and the file has no uncommitted changes (a fresh clone
+/// would otherwise look brand new), else its modification time.
+pub fn last_changed(prefix: &Path, files: &[PathBuf]) -> HashMap<PathBuf, NaiveDate> {
+    let repo = Repository::discover(prefix).ok();
+    let committed = repo.as_ref().map(commit_times).unwrap_or_default();
+
+    let mut dates = HashMap::new();
+    for file in files {
+        let from_git = repo.as_ref().and_then(|repo| {
+            let workdir = repo.workdir()?.canonicalize().ok()?;
+            let absolute = file.canonicalize().ok()?;
+            let relative = absolute.strip_prefix(&workdir).ok()?;
+            let clean = repo.status_file(relative).ok()? == Status::CURRENT;
+            clean.then(|| committed.get(relative).copied()).flatten()
+        });
+        let from_disk = || {
+            let modified = fs::metadata(file).and_then(|m| m.modified()).ok()?;
+            Some(DateTime::<Local>::from(modified).date_naive())
+        };
+        if let Some(date) = from_git.or_else(from_disk) {
+            dates.insert(file.clone(), date);
+        }
+    }
+    dates
+}
+
+/// Newest commit date for every path in history, relative to the workdir
+fn commit_times(repo: &Repository) -> HashMap<PathBuf, NaiveDate> {
+    let mut times = HashMap::new();
+    let Ok(mut walk) = repo.revwalk() else {
+        return times;
+    };
+    if walk.push_head().is_err() || walk.set_sorting(Sort::TIME).is_err() {
+        return times;
+    }
+    for oid in walk.filter_map(Result::ok) {
+        let Ok(commit) = repo.find_commit(oid) else {
+            continue;
+        };
+        let Some(date) = DateTime::from_timestamp(commit.time().seconds(), 0)
+            .map(|t| t.with_timezone(&Local).date_naive())
+        else {
+            continue;
+        };
+        let tree = commit.tree().ok();
+        let parent = commit.parent(0).ok().and_then(|p| p.tree().ok());
+        let Ok(diff) = repo.diff_tree_to_tree(
+            parent.as_ref(),
+            tree.as_ref(),
+            Some(&mut DiffOptions::new()),
+        ) else {
+            continue;
+        };
+        for delta in diff.deltas() {
+            if let Some(path) = delta.new_file().path() {
+                times.entry(path.to_path_buf()).or_insert(date);
+            }
+        }
+    }
+    times
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn periods() -> Result<()> {
+        let start = date("2025-01-31");
+        assert_eq!(add_period(start, "14d")?, date("2025-02-14"));
+        assert_eq!(add_period(start, "2w")?, date("2025-02-14"));
+        assert_eq!(add_period(start, "1m")?, date("2025-02-28"));
+        assert_eq!(add_period(start, "1y")?, date("2026-01-31"));
+        assert!(add_period(start, "90").is_err());
+        assert!(add_period(start, "d").is_err());
+        assert!(add_period(start, "soon").is_err());
+        assert!(add_period(start, "4294967295y").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn expires_wins_over_rotation() -> Result<()> {
+        let changed = date("2025-01-01");
+        let rotating = Entry::parse("pw\nrotate-every: 90d");
+        assert_eq!(
+            due(&rotating, changed)?,
+            Some(Due {
+                date: date("2025-04-01"),
+                rule: "rotate-every: 90d".into()
+            })
+        );
+
+        let both = Entry::parse("pw\nrotate-every: 90d\nexpires: 2025-02-01");
+        assert_eq!(due(&both, changed)?.unwrap().date, date("2025-02-01"));
+        assert_eq!(due(&Entry::parse("pw"), changed)?, None);
+        assert!(due(&Entry::parse("pw\nexpires: tomorrow"), changed).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rotation_counts_from_the_changed_field() -> Result<()> {
+        // the file was re-encrypted long after the password changed
+        let reencrypted = date("2025-06-01");
+        let entry = Entry::parse("pw\nrotate-every: 30d\nchanged: 2025-01-01");
+        assert_eq!(due(&entry, reencrypted)?.unwrap().date, date("2025-01-31"));
+
+        let mut entry = Entry::parse("pw\nrotate-every: 30d");
+        record_change(&mut entry, date("2025-03-10"));
+        assert_eq!(entry.field(CHANGED), Some("2025-03-10"));
+        let mut plain = Entry::parse("pw");
+        record_change(&mut plain, date("2025-03-10"));
+        assert_eq!(plain.field(CHANGED), None, "only rotating entries");
+        Ok(())
+    }
+
+    #[test]
+    fn reset_moves_or_drops_fixed_dates() -> Result<()> {
+        let today = date("2025-03-10");
+
+        let mut rotating = Entry::parse("pw\nexpires: 2025-03-01\nrotate-every: 30d");
+        reset(&mut rotating, today)?;
+        assert_eq!(rotating.field(EXPIRES), Some("2025-04-09"));
+
+        let mut fixed = Entry::parse("pw\nusername: me\nexpires: 2025-03-01");
+        reset(&mut fixed, today)?;
+        assert_eq!(fixed.field(EXPIRES), None);
+        assert_eq!(fixed.field("username"), Some("me"));
+        Ok(())
+    }
+
+    #[test]
+    fn clean_files_take_their_last_commit_date() -> Result<()> {
+        let tmp = tempfile::TempDir::new()?;
+        let repo = Repository::init(tmp.path())?;
+        let file = tmp.path().join("vpn.rage");
+        fs::write(&file, "v1")?;
+
+        let mut index = repo.index()?;
+        index.add_path(Path::new("vpn.rage"))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        // 2021-06-15T12:00:00Z
+        let when = git2::Time::new(1_623_758_400, 0);
+        let sig = git2::Signature::new("t", "t@example.com", &when)?;
+        repo.commit(Some("HEAD"), &sig, &sig, "add", &tree, &[])?;
+
+        let dates = last_changed(tmp.path(), std::slice::from_ref(&file));
+        assert_eq!(dates[&file], date("2021-06-15"));
+
+        fs::write(&file, "v2, not committed yet")?;
+        let dates = last_changed(tmp.path(), std::slice::from_ref(&file));
+        assert_eq!(dates[&file], today());
+        Ok(())
+    }
+}
@@ -3,10 +3,13 @@
 use anyhow::Result;
 use git2::Repository;
 use serde::Serialize;
-use std::{collections::HashMap, env};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+};
 use which::which;
 
-use crate::config::{Config, load_config};
+use crate::config::{Config, Source, SyncPolicy, config_file_path, load_config_with_sources};
 
 /// A serializable snapshot of Passman’s runtime configuration
 #[derive(Serialize)]
@@ -16,21 +19,31 @@ pub struct ConfigDump {
     pub secret: String,
     pub crypto_extension: String,
     pub public_key_filename: String,
+    pub editor: String,
+    pub clipboard_timeout: u64,
+    pub sync: SyncPolicy,
+    /// Selected named vault, if any
+    pub vault: Option<String>,
+    /// The config file that was read (it may not exist yet)
+    pub config_file: String,
+    /// Where each value above came from: default, file, env or detected
+    pub sources: BTreeMap<&'static str, Source>,
     pub env: HashMap<String, String>,
     pub dependencies: HashMap<String, String>,
     /// If the vault is a git repo, the URL of the "origin" remote
     pub git_remote_origin: Option<String>,
 }
 
-pub fn extant_config() -> Result<ConfigDump> {
-    // Load your existing Config struct
-    let cfg: Config = load_config()?;
+pub fn extant_config(vault: Option<&str>) -> Result<ConfigDump> {
+    // Load your existing Config struct, plus where each value came from
+    let (cfg, sources): (Config, _) = load_config_with_sources(vault)?;
 
     // Capture selected environment variables
     let mut env_map = HashMap::new();
     for &key in &[
         "PASSMAN_DIR",
         "XDG_DATA_HOME",
+        "XDG_CONFIG_HOME",
         "PASSMAN_PUBLIC_KEY",
         "EDITOR",
     ] {
@@ -65,6 +78,12 @@ pub fn extant_config() -> Result<ConfigDump> {
         base_dir: cfg.base_dir.display().to_string(),
         prefix: cfg.prefix.display().to_string(),
         secret: cfg.secret.display().to_string(),
+        editor: cfg.editor(),
+        clipboard_timeout: cfg.clipboard_timeout,
+        sync: cfg.sync,
+        vault: cfg.vault.clone(),
+        config_file: config_file_path().display().to_string(),
+        sources,
         crypto_extension: cfg.crypto_extension,
         public_key_filename: cfg.public_key_filename,
         env: env_map,
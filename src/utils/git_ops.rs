@@ -1,5 +1,8 @@
 use anyhow::Result;
 use std::path::Path;
+
+use crate::config::SyncPolicy;
+use crate::utils::{manifest, signing};
 use std::process::{Command, Stdio};
 
 /// Runs a silent Git command. On real execution errors, prints warning but returns Ok(()).
@@ -28,15 +31,36 @@ fn git_silent(repo_path: &Path, label: &str, args: &[&str]) -> Result<()> {
     }
 }
 
-/// Attempts to sync the vault repo. All errors are printed but never cause failure.
-pub fn sync_vault(repo_path: &Path) -> Result<()> {
-    println!("🔄 syncing with GitHub...");
+/// Attempts to sync the vault repo, as far as `policy` allows.
+/// Git errors are printed but never cause failure; a vault that doesn't
+/// verify stops the sync before anything is committed.
+pub fn sync_vault(repo_path: &Path, policy: SyncPolicy) -> Result<()> {
+    sync_vault_with_message(repo_path, policy, "Sync vault")
+}
+
+/// Like `sync_vault`, with a custom commit message for bulk changes.
+pub fn sync_vault_with_message(repo_path: &Path, policy: SyncPolicy, message: &str) -> Result<()> {
+    // a hidden-names vault's manifest has to go out with the entries
+    manifest::flush(repo_path)?;
+    if policy == SyncPolicy::Manual {
+        return Ok(());
+    }
+
+    // vouch for what this device is about to commit
+    signing::sign_vault(repo_path)?;
 
     // stage all changes **including** deletions
     git_silent(repo_path, "add", &["add", "."])?;
+    git_silent(repo_path, "commit", &["commit", "-m", message])?;
+
+    if policy == SyncPolicy::Commit {
+        return Ok(());
+    }
 
-    git_silent(repo_path, "commit", &["commit", "-m", "Sync vault"])?;
+    println!("🔄 syncing with GitHub...");
     git_silent(repo_path, "pull", &["pull", "--rebase"])?;
+    // anyone with push access could have swapped ciphertexts upstream
+    signing::check_pulled(repo_path)?;
     git_silent(repo_path, "push", &["push"])?;
 
     Ok(())
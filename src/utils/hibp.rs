@@ -0,0 +1,208 @@
+use anyhow::{Context, Result};
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+/// The public Pwned Passwords range API
+pub const DEFAULT_API: &str = "https://api.pwnedpasswords.com";
+
+/// Somewhere to look up how often a password hash appears in known breaches
+pub trait Breaches {
+    /// Times `hash` (40 upper-case hex digits) was seen, or `None` if never
+    fn count(&mut self, hash: &str) -> Result<Option<u64>>;
+}
+
+/// Upper-case hex SHA-1, the form Have I Been Pwned uses
+pub fn sha1_hex(password: &str) -> String {
+    Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect()
+}
+
+/// A downloaded `pwned-passwords-sha1-ordered-by-hash` file, one
+/// `HASH:COUNT` per line. Lookups binary-search the file on disk, so the
+/// 30+ GB list is never read into memory.
+pub struct HashList {
+    reader: BufReader<File>,
+    len: u64,
+}
+
+impl HashList {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let len = file.metadata()?.len();
+        let mut list = HashList {
+            reader: BufReader::new(file),
+            len,
+        };
+        if let Some((_, line)) = list.line_from(0)? {
+            let hash = line.split(':').next().unwrap_or("");
+            if hash.len() != 40 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                anyhow::bail!(
+                    "{} doesn't look like a Pwned Passwords SHA-1 list (expected HASH:COUNT lines)",
+                    path.display()
+                );
+            }
+        }
+        Ok(list)
+    }
+
+    /// The first whole line starting at or after `offset`, with the offset
+    /// just past its end
+    fn line_from(&mut self, offset: u64) -> Result<Option<(u64, String)>> {
+        let mut pos = offset;
+        if offset > 0 {
+            // back up one byte so a line starting exactly at `offset` is kept
+            self.reader.seek(SeekFrom::Start(offset - 1))?;
+            let mut skipped = Vec::new();
+            pos = offset - 1 + self.reader.read_until(b'\n', &mut skipped)? as u64;
+        } else {
+            self.reader.seek(SeekFrom::Start(0))?;
+        }
+        let mut line = String::new();
+        let read = self.reader.read_line(&mut line)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        Ok(Some((pos + read as u64, line.trim().to_string())))
+    }
+}
+
+impl Breaches for HashList {
+    fn count(&mut self, hash: &str) -> Result<Option<u64>> {
+        // invariant: if `hash` is in the file, its line starts in lo..hi
+        let (mut lo, mut hi) = (0, self.len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let Some((end, line)) = self.line_from(mid)? else {
+                hi = mid;
+                continue;
+            };
+            let (found, count) = line.split_once(':').unwrap_or((&line, ""));
+            match found.to_ascii_uppercase().as_str().cmp(hash) {
+                Ordering::Equal => return Ok(Some(count.trim().parse().unwrap_or(1))),
+                Ordering::Less => lo = end,
+                Ordering::Greater => hi = mid,
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The k-anonymity range API: only the first five hex digits of each hash
+/// are sent, and the matching suffixes come back padded with decoys.
+pub struct RangeApi {
+    base: String,
+    agent: ureq::Agent,
+}
+
+impl RangeApi {
+    pub fn new(base: &str) -> Self {
+        RangeApi {
+            base: base.trim_end_matches('/').to_string(),
+            agent: ureq::Agent::new_with_defaults(),
+        }
+    }
+}
+
+impl Breaches for RangeApi {
+    fn count(&mut self, hash: &str) -> Result<Option<u64>> {
+        let (prefix, suffix) = hash.split_at(5);
+        let url = format!("{}/range/{}", self.base, prefix);
+        let body = self
+            .agent
+            .get(&url)
+            .header("User-Agent", concat!("passman/", env!("CARGO_PKG_VERSION")))
+            .header("Add-Padding", "true")
+            .call()
+            .and_then(|mut r| r.body_mut().read_to_string())
+            .with_context(|| format!("Failed to query {}", url))?;
+
+        for line in body.lines() {
+            if let Some((found, count)) = line.trim().split_once(':')
+                && found.eq_ignore_ascii_case(suffix)
+            {
+                // padding entries carry a count of zero
+                let count: u64 = count.trim().parse().unwrap_or(0);
+                return Ok((count > 0).then_some(count));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use tempfile::TempDir;
+
+    #[test]
+    fn hashes_like_hibp() {
+        assert_eq!(
+            sha1_hex("password"),
+            "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8"
+        );
+    }
+
+    #[test]
+    fn binary_searches_the_hash_list() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let mut hashes: Vec<String> = (0..500).map(|i| sha1_hex(&format!("pw{}", i))).collect();
+        hashes.sort();
+        let contents: String = hashes
+            .iter()
+            .enumerate()
+            .map(|(i, h)| format!("{}:{}\r\n", h, i + 1))
+            .collect();
+        let path = tmp.path().join("pwned.txt");
+        fs::write(&path, contents)?;
+
+        let mut list = HashList::open(&path)?;
+        for (i, hash) in hashes.iter().enumerate() {
+            assert_eq!(list.count(hash)?, Some(i as u64 + 1), "{}", hash);
+        }
+        assert_eq!(list.count(&sha1_hex("not in the list"))?, None);
+        assert_eq!(list.count(&"0".repeat(40))?, None);
+        assert_eq!(list.count(&"F".repeat(40))?, None);
+
+        fs::write(&path, "hello\n")?;
+        assert!(HashList::open(&path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn queries_the_range_api_by_prefix() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let base = format!("http://{}", listener.local_addr()?);
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let n = stream.read(&mut request).unwrap();
+            let request = String::from_utf8_lossy(&request[..n]).to_string();
+            let body = "1E4C9B93F3F0682250B6CF8331B7EE68FD8:42\r\n0000000000000000000000000000000000A:0\r\n";
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            request
+        });
+
+        let mut api = RangeApi::new(&base);
+        assert_eq!(api.count(&sha1_hex("password"))?, Some(42));
+        let request = server.join().unwrap();
+        assert!(request.starts_with("GET /range/5BAA6 HTTP/1.1"));
+        assert!(!request.contains("1E4C9B93"), "only the prefix is sent");
+        Ok(())
+    }
+}
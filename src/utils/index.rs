@@ -0,0 +1,377 @@
+use crate::config::Config;
+use crate::crypto;
+use crate::entry::Entry;
+use crate::utils::entries;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Index file name inside the vault, before the crypto extension
+pub const INDEX_NAME: &str = ".passman-index";
+
+/// Index layout version, bumped on incompatible changes
+pub const VERSION: u32 = 2;
+
+/// What the index remembers about one entry. Never the password or notes.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Meta {
+    /// Names of the entry's `key: value` fields, lower-cased
+    #[serde(default)]
+    pub fields: Vec<String>,
+
+    #[serde(default)]
+    pub username: Option<String>,
+
+    #[serde(default)]
+    pub url: Option<String>,
+
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Modification time of the entry file, in seconds since the epoch
+    #[serde(default)]
+    pub mtime: u64,
+
+    /// SHA-256 of the encrypted file; a different hash on disk means the
+    /// entry changed. Unlike the mtime, it survives clones and pulls.
+    #[serde(default)]
+    pub hash: String,
+}
+
+impl Meta {
+    /// Metadata for `entry`, stored encrypted at `file`
+    pub fn new(entry: &Entry, file: &Path) -> Self {
+        let first = |keys: &[&str]| keys.iter().find_map(|k| entry.field(k)).map(String::from);
+        Meta {
+            fields: entry.fields.iter().map(|(k, _)| k.to_lowercase()).collect(),
+            username: first(&["username", "user", "login", "email"]),
+            url: first(&["url", "website"]),
+            tags: entry.tags(),
+            mtime: mtime(file),
+            hash: file_hash(file).unwrap_or_default(),
+        }
+    }
+
+    /// Whether `needle` (lower-cased) appears in any of the metadata
+    pub fn matches(&self, needle: &str) -> bool {
+        self.username
+            .iter()
+            .chain(&self.url)
+            .chain(&self.tags)
+            .chain(&self.fields)
+            .any(|v| v.to_lowercase().contains(needle))
+    }
+}
+
+/// Metadata for every entry in a vault (archived ones included), in one
+/// file so searches need a single decryption. It sits in the vault as
+/// `.passman-index.<ext>`, encrypted to the recipients in the public key
+/// file, and is kept out of git: every device maintains its own, so it
+/// never needs merging.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Index {
+    pub version: u32,
+
+    /// Entry name → its metadata
+    pub entries: BTreeMap<String, Meta>,
+}
+
+pub fn index_path(config: &Config) -> Result<PathBuf> {
+    config.entry_path(INDEX_NAME)
+}
+
+/// Modification time of `path` in seconds, 0 if unknown
+fn mtime(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs())
+}
+
+fn file_hash(path: &Path) -> Result<String> {
+    Ok(format!("{:x}", Sha256::digest(fs::read(path)?)))
+}
+
+impl Index {
+    /// The saved index, or `None` if this vault has never been indexed
+    pub fn load(config: &Config) -> Result<Option<Index>> {
+        let path = index_path(config)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json = crypto::decrypt(&config.secret, &path)
+            .with_context(|| format!("Failed to decrypt index {}", path.display()))?;
+        let index: Index = serde_json::from_str(json.expose())
+            .with_context(|| format!("Index {} is corrupted", path.display()))?;
+        if index.version != VERSION {
+            // an older layout is simply rebuilt
+            return Ok(Some(Index::default()));
+        }
+        Ok(Some(index))
+    }
+
+    pub fn save(&self, config: &Config) -> Result<()> {
+        ignore_in_git(&config.prefix)?;
+        let path = index_path(config)?;
+        let json = serde_json::to_string(&Index {
+            version: VERSION,
+            entries: self.entries.clone(),
+        })?;
+        crypto::encrypt(&config.read_recipients()?, &path, &json.into())
+            .with_context(|| format!("Failed to write index {}", path.display()))
+    }
+
+    /// Catch up with the vault on disk: entries that are new or whose file
+    /// changed are decrypted again and removed ones are dropped. Returns
+    /// whether anything changed.
+    pub fn refresh(&mut self, config: &Config) -> Result<bool> {
+        let files = entries::collect(&config.prefix, true)?;
+        let before = self.entries.len();
+        self.entries
+            .retain(|name, _| files.iter().any(|f| &f.name == name));
+        let mut changed = self.entries.len() != before;
+
+        for file in files {
+            let hash = file_hash(&file.path).unwrap_or_default();
+            if self.entries.get(&file.name).is_some_and(|m| m.hash == hash) {
+                continue;
+            }
+            match crypto::decrypt(&config.secret, &file.path) {
+                Ok(plaintext) => {
+                    let meta = Meta::new(&Entry::parse(plaintext.expose()), &file.path);
+                    self.entries.insert(file.name, meta);
+                    changed = true;
+                }
+                Err(e) => eprintln!("⚠️  Skipping {}: {:#}", file.name, e),
+            }
+        }
+        Ok(changed)
+    }
+}
+
+/// The vault's `.gitignore` must list the index, or every device would
+/// commit its own copy
+fn ignore_in_git(prefix: &Path) -> Result<()> {
+    let path = prefix.join(".gitignore");
+    let pattern = format!("/{}.*", INDEX_NAME);
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+    if existing.lines().any(|l| l.trim() == pattern) {
+        return Ok(());
+    }
+    let mut contents = existing;
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&pattern);
+    contents.push('\n');
+    fs::write(&path, contents).with_context(|| format!("Failed to update {}", path.display()))
+}
+
+/// Metadata for every entry: from the index when there is one (refreshing
+/// and re-saving it if the vault changed), otherwise by decrypting them all
+pub fn metadata(config: &Config) -> Result<BTreeMap<String, Meta>> {
+    match Index::load(config)? {
+        Some(mut index) => {
+            if index.refresh(config)? {
+                index.save(config)?;
+            }
+            Ok(index.entries)
+        }
+        None => {
+            let mut index = Index::default();
+            index.refresh(config)?;
+            Ok(index.entries)
+        }
+    }
+}
+
+/// Rebuild the index from scratch and save it
+pub fn rebuild(config: &Config) -> Result<Index> {
+    let mut index = Index::default();
+    index.refresh(config)?;
+    index.save(config)?;
+    Ok(index)
+}
+
+/// Apply `change` to the saved index, if there is one. Failures only warn:
+/// the vault itself has already changed, and the next query or
+/// `passman reindex` catches up anyway.
+fn maintain(config: &Config, change: impl FnOnce(&mut Index) -> Result<()>) {
+    let result = Index::load(config).and_then(|index| match index {
+        Some(mut index) => {
+            change(&mut index)?;
+            index.save(config)
+        }
+        None => Ok(()),
+    });
+    if let Err(e) = result {
+        eprintln!(
+            "⚠️  Search index not updated ({:#}); run `passman reindex`",
+            e
+        );
+    }
+}
+
+/// Record an entry that was just written with `plaintext`
+pub fn record(config: &Config, name: &str, plaintext: &str) {
+    maintain(config, |index| {
+        let meta = Meta::new(&Entry::parse(plaintext), &config.entry_path(name)?);
+        index.entries.insert(name.to_string(), meta);
+        Ok(())
+    });
+}
+
+/// Drop an entry, or every entry below a folder
+pub fn forget(config: &Config, name: &str) {
+    let folder = format!("{}/", name.trim_end_matches('/'));
+    maintain(config, |index| {
+        index
+            .entries
+            .retain(|n, _| n != name && !n.starts_with(&folder));
+        Ok(())
+    });
+}
+
+/// Follow an entry or folder that moved from `from` to `to`
+pub fn rename(config: &Config, from: &str, to: &str) {
+    let folder = format!("{}/", from.trim_end_matches('/'));
+    maintain(config, |index| {
+        let moved: Vec<String> = index
+            .entries
+            .keys()
+            .filter(|n| *n == from || n.starts_with(&folder))
+            .cloned()
+            .collect();
+        for old in moved {
+            let new = format!("{}{}", to, &old[from.len()..]);
+            if let Some(mut meta) = index.entries.remove(&old) {
+                let file = config.entry_path(&new)?;
+                meta.hash = file_hash(&file).unwrap_or_default();
+                meta.mtime = mtime(&file);
+                index.entries.insert(new, meta);
+            }
+        }
+        Ok(())
+    });
+}
+
+/// Whether `name` is archived: it, or a folder above it, starts with a dot
+pub fn is_hidden(name: &str) -> bool {
+    name.split('/').any(|part| part.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{
+        CryptoBackend, SecretString, set_decrypt_factory, set_encrypt_factory, testing,
+    };
+    use std::cell::Cell;
+    use tempfile::TempDir;
+
+    thread_local! {
+        static DECRYPTS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Stores plaintext as-is and counts decryptions
+    struct CountingBackend;
+    impl CryptoBackend for CountingBackend {
+        fn encrypt(&self, _r: &[String], o: &Path, p: &SecretString) -> Result<()> {
+            fs::write(o, p.expose())?;
+            Ok(())
+        }
+        fn decrypt(&self, _i: &Path, e: &Path) -> Result<SecretString> {
+            DECRYPTS.with(|c| c.set(c.get() + 1));
+            Ok(fs::read_to_string(e)?.into())
+        }
+    }
+
+    fn make_config(tmp: &TempDir) -> Config {
+        let cfg = testing::plain_vault(tmp.path());
+        set_encrypt_factory(|_: &Path| Box::new(CountingBackend));
+        set_decrypt_factory(|_: &Path| Box::new(CountingBackend));
+        cfg
+    }
+
+    fn decrypts() -> usize {
+        DECRYPTS.with(|c| c.replace(0))
+    }
+
+    #[test]
+    fn index_only_rereads_changed_entries() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        fs::write(cfg.entry_path("a")?, "pw\ntags: prod\nusername: alice")?;
+        fs::write(cfg.entry_path("b")?, "pw\ntags: dev")?;
+
+        rebuild(&cfg)?;
+        assert_eq!(index_path(&cfg)?, cfg.prefix.join(".passman-index.rage"));
+        assert_eq!(
+            fs::read_to_string(cfg.prefix.join(".gitignore"))?,
+            "/.passman-index.*\n"
+        );
+        decrypts();
+
+        let meta = metadata(&cfg)?;
+        assert_eq!(meta["a"].tags, vec!["prod"]);
+        assert_eq!(meta["a"].username.as_deref(), Some("alice"));
+        assert_eq!(meta["a"].fields, vec!["tags", "username"]);
+        assert_eq!(decrypts(), 1, "only the index itself is decrypted");
+
+        fs::remove_file(cfg.entry_path("b")?)?;
+        fs::write(cfg.entry_path("a")?, "pw\ntags: prod, web")?;
+        let meta = metadata(&cfg)?;
+        assert_eq!(meta.keys().collect::<Vec<_>>(), vec!["a"]);
+        assert_eq!(meta["a"].tags, vec!["prod", "web"]);
+        assert_eq!(decrypts(), 2, "the index and the changed entry");
+        Ok(())
+    }
+
+    #[test]
+    fn without_an_index_everything_is_decrypted() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        fs::write(cfg.entry_path("a")?, "pw\ntags: prod")?;
+        decrypts();
+
+        assert_eq!(metadata(&cfg)?["a"].tags, vec!["prod"]);
+        assert_eq!(decrypts(), 1);
+        assert!(!index_path(&cfg)?.exists(), "the index is opt-in");
+
+        record(&cfg, "a", "pw");
+        assert!(!index_path(&cfg)?.exists(), "nor created by maintenance");
+        Ok(())
+    }
+
+    #[test]
+    fn maintenance_keeps_the_index_current() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = make_config(&tmp);
+        fs::create_dir_all(cfg.prefix.join("team"))?;
+        fs::write(cfg.entry_path("team/db")?, "pw\nurl: db.test")?;
+        rebuild(&cfg)?;
+
+        fs::write(cfg.entry_path("web")?, "pw\nurl: web.test")?;
+        record(&cfg, "web", "pw\nurl: web.test");
+        fs::create_dir_all(cfg.prefix.join("ops"))?;
+        fs::rename(cfg.entry_path("team/db")?, cfg.prefix.join("ops/db.rage"))?;
+        rename(&cfg, "team", "ops");
+        decrypts();
+
+        let meta = metadata(&cfg)?;
+        assert_eq!(meta.keys().collect::<Vec<_>>(), vec!["ops/db", "web"]);
+        assert_eq!(meta["ops/db"].url.as_deref(), Some("db.test"));
+        assert_eq!(decrypts(), 1, "maintained entries need no refresh");
+
+        fs::remove_file(cfg.entry_path("web")?)?;
+        forget(&cfg, "web");
+        let index = Index::load(&cfg)?.unwrap();
+        assert_eq!(index.entries.keys().collect::<Vec<_>>(), vec!["ops/db"]);
+        Ok(())
+    }
+}
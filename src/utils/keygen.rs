@@ -7,6 +7,9 @@ use std::process::{Command, Stdio};
 use anyhow::{Context, Result};
 use which::which;
 
+use crate::crypto::ssh;
+use crate::utils::secure_fs;
+
 pub fn generate_keypair(secret_path: &Path, public_path: &Path) -> Result<()> {
     let (bin, pub_flag, install_hint) = match secret_path.extension().and_then(OsStr::to_str) {
         Some("age") => (
@@ -24,7 +27,7 @@ pub fn generate_keypair(secret_path: &Path, public_path: &Path) -> Result<()> {
     which(bin).context(install_hint)?;
 
     if let Some(parent) = secret_path.parent() {
-        fs::create_dir_all(parent).context("Creating parent directory for identity file")?;
+        secure_fs::private_dir(parent).context("Creating parent directory for identity file")?;
     }
 
     let status = Command::new(bin)
@@ -36,6 +39,7 @@ pub fn generate_keypair(secret_path: &Path, public_path: &Path) -> Result<()> {
     if !status.success() {
         anyhow::bail!("{} failed to generate identity", bin);
     }
+    secure_fs::private_file(secret_path)?;
 
     let output = Command::new(bin)
         .arg(pub_flag)
@@ -67,3 +71,28 @@ pub fn generate_keypair(secret_path: &Path, public_path: &Path) -> Result<()> {
 
     Ok(())
 }
+
+/// Use an existing SSH key as the identity: `public_path` gets its public
+/// key (with the `.pub` file's comment, so others can tell whose it is)
+/// and the private key stays where it is
+pub fn use_ssh_key(ssh_key: &Path, public_path: &Path) -> Result<()> {
+    let recipient = ssh::public_key(ssh_key)?;
+    let pub_file = ssh_key.with_extension("pub");
+    let line = fs::read_to_string(&pub_file)
+        .ok()
+        .map(|raw| raw.trim().to_string())
+        .filter(|line| ssh::recipient(line).as_deref() == Some(recipient.as_str()))
+        .unwrap_or(recipient);
+
+    fs::write(public_path, format!("{}\n", line))
+        .context("Failed to write public key to recipient file")?;
+
+    println!("✅ Using your SSH key as the identity:");
+    println!("  🔐 Private:    {}", ssh_key.display());
+    println!("  🟢 Public:     {}", public_path.display());
+    println!(
+        "\nCopy the public key to other systems to allow encryption to this identity:\n{}",
+        line
+    );
+    Ok(())
+}
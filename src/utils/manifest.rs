@@ -0,0 +1,321 @@
+use crate::config::{self, Config};
+use crate::crypto;
+use anyhow::{Context, Result};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Manifest file name inside the vault, before the crypto extension
+pub const MANIFEST_NAME: &str = ".passman-manifest";
+
+/// Manifest layout version, bumped on incompatible changes
+pub const VERSION: u32 = 1;
+
+/// Names that are never looked up in the manifest: the manifest itself and
+/// the search index keep their own names
+const RESERVED_PREFIX: &str = ".passman-";
+
+/// Entry name → random file ID, for vaults in hidden-names mode. The
+/// manifest is the only place the names exist; it is committed with the
+/// vault and encrypted to the vault's root recipients.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Manifest {
+    pub version: u32,
+    pub entries: BTreeMap<String, String>,
+}
+
+struct Loaded {
+    /// Current mapping, including IDs handed out for entries that may not
+    /// have been written (yet)
+    names: BTreeMap<String, String>,
+    /// The mapping as last saved, to tell whether it needs writing
+    saved: BTreeMap<String, String>,
+}
+
+thread_local! {
+    /// Manifests are decrypted at most once per process, keyed by vault prefix
+    static VAULTS: RefCell<HashMap<PathBuf, Loaded>> = RefCell::new(HashMap::new());
+}
+
+/// Whether the vault at `prefix` stores entries under random IDs
+pub fn is_enabled(prefix: &Path) -> bool {
+    manifest_file(prefix).is_some()
+}
+
+/// The vault's manifest file, if it is in hidden-names mode
+pub fn manifest_file(prefix: &Path) -> Option<PathBuf> {
+    crate::config::ENTRY_EXTENSIONS
+        .iter()
+        .map(|ext| prefix.join(format!("{}.{}", MANIFEST_NAME, ext)))
+        .find(|p| p.is_file())
+}
+
+/// Decrypt the vault's manifest up front, if it is in hidden-names mode, so
+/// a manifest that can't be read stops the command before anything looks
+/// up a name
+pub fn open(config: &Config) -> Result<()> {
+    config::register(config);
+    let Some(file) = manifest_file(&config.prefix) else {
+        return Ok(());
+    };
+    if VAULTS.with(|v| v.borrow().contains_key(&config.prefix)) {
+        return Ok(());
+    }
+    let names = load(config, &file)?;
+    VAULTS.with(|v| {
+        v.borrow_mut().insert(
+            config.prefix.clone(),
+            Loaded {
+                saved: names.clone(),
+                names,
+            },
+        )
+    });
+    Ok(())
+}
+
+/// Run `f` on the vault's manifest, decrypting it on first use. `None` when
+/// the vault isn't in hidden-names mode. A manifest that exists but can't
+/// be read is an error, never a fallback to plain names: those would write
+/// and list entries under the very names the vault hides.
+fn with_loaded<R>(prefix: &Path, f: impl FnOnce(&mut Loaded, &Config) -> R) -> Result<Option<R>> {
+    if manifest_file(prefix).is_none() {
+        return Ok(None);
+    }
+    let config = config::registered(prefix)
+        .with_context(|| format!("No config registered for {}", prefix.display()))?;
+    open(&config)?;
+    Ok(VAULTS.with(|v| {
+        let mut vaults = v.borrow_mut();
+        vaults.get_mut(prefix).map(|loaded| f(loaded, &config))
+    }))
+}
+
+fn load(config: &Config, file: &Path) -> Result<BTreeMap<String, String>> {
+    let json = crypto::decrypt(&config.secret, file)
+        .with_context(|| format!("Failed to decrypt manifest {}", file.display()))?;
+    let manifest: Manifest = serde_json::from_str(json.expose())
+        .with_context(|| format!("Manifest {} is corrupted", file.display()))?;
+    if manifest.version > VERSION {
+        anyhow::bail!(
+            "Manifest version {} is newer than this passman understands ({})",
+            manifest.version,
+            VERSION
+        );
+    }
+    Ok(manifest.entries)
+}
+
+/// Encrypt and write `names` as the vault's manifest
+pub fn save(config: &Config, names: &BTreeMap<String, String>) -> Result<()> {
+    let file = manifest_file(&config.prefix).unwrap_or_else(|| {
+        let ext = config.crypto_extension.trim_start_matches('.');
+        config.prefix.join(format!("{}.{}", MANIFEST_NAME, ext))
+    });
+    let recipients = config.recipients_for(&file)?;
+    let json = serde_json::to_string(&Manifest {
+        version: VERSION,
+        entries: names.clone(),
+    })?;
+    crypto::encrypt(&recipients, &file, &json.into())
+        .with_context(|| format!("Failed to write manifest {}", file.display()))
+}
+
+/// A fresh random file ID
+pub fn new_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The file ID for `name` in a hidden-names vault, handing out a new one
+/// for names not seen before. `None` for ordinary vaults.
+pub fn id_for(config: &Config, name: &str) -> Result<Option<String>> {
+    if name.starts_with(RESERVED_PREFIX) {
+        return Ok(None);
+    }
+    config::register(config);
+    with_loaded(&config.prefix, |loaded, _| {
+        loaded
+            .names
+            .entry(name.to_string())
+            .or_insert_with(new_id)
+            .clone()
+    })
+}
+
+/// Every entry name with its file, sorted by name, if `prefix` is a
+/// registered hidden-names vault
+pub fn entries(prefix: &Path) -> Result<Option<Vec<(String, PathBuf)>>> {
+    with_loaded(prefix, |loaded, config| {
+        loaded
+            .names
+            .iter()
+            .filter_map(|(n, id)| Some((n.clone(), file_for(config, id)?)))
+            .collect()
+    })
+}
+
+fn file_for(config: &Config, id: &str) -> Option<PathBuf> {
+    crate::config::ENTRY_EXTENSIONS
+        .iter()
+        .map(|ext| config.prefix.join(format!("{}.{}", id, ext)))
+        .find(|p| p.is_file())
+}
+
+fn exists(config: &Config, id: &str) -> bool {
+    file_for(config, id).is_some()
+}
+
+/// Rename an entry, or every entry below a folder, without touching any
+/// file. Returns how many entries moved.
+pub fn rename(config: &Config, from: &str, to: &str) -> Result<usize> {
+    config::register(config);
+    let folder = format!("{}/", from);
+    with_loaded(&config.prefix, |loaded, config| {
+        let live: Vec<String> = loaded
+            .names
+            .iter()
+            .filter(|(_, id)| exists(config, id))
+            .map(|(n, _)| n.clone())
+            .collect();
+        let moving: Vec<&String> = live
+            .iter()
+            .filter(|n| *n == from || n.starts_with(&folder))
+            .collect();
+        if moving.is_empty() {
+            anyhow::bail!("No such entry or folder: {}", from);
+        }
+        let target_folder = format!("{}/", to);
+        if live
+            .iter()
+            .any(|n| n == to || n.starts_with(&target_folder))
+        {
+            anyhow::bail!("{} already exists", to);
+        }
+        for old in &moving {
+            if let Some(id) = loaded.names.remove(*old) {
+                loaded
+                    .names
+                    .insert(format!("{}{}", to, &old[from.len()..]), id);
+            }
+        }
+        Ok(moving.len())
+    })?
+    .unwrap_or_else(|| anyhow::bail!("{} does not keep a manifest", config.prefix.display()))
+}
+
+/// Delete the vault's manifest, turning hidden-names mode off. The caller
+/// must already have given the entry files their names back.
+pub fn remove(prefix: &Path) -> Result<()> {
+    if let Some(file) = manifest_file(prefix) {
+        std::fs::remove_file(&file)
+            .with_context(|| format!("Failed to remove {}", file.display()))?;
+    }
+    VAULTS.with(|v| v.borrow_mut().remove(prefix));
+    Ok(())
+}
+
+/// Write the manifest if it changed. IDs handed out for names that were
+/// never written, and names whose file was deleted, are dropped first.
+pub fn flush(prefix: &Path) -> Result<()> {
+    // a manifest that was never read has nothing to write
+    if !VAULTS.with(|v| v.borrow().contains_key(prefix)) {
+        return Ok(());
+    }
+    with_loaded(prefix, |loaded, config| {
+        loaded.names.retain(|_, id| exists(config, id));
+        if loaded.names == loaded.saved {
+            return Ok(());
+        }
+        save(config, &loaded.names)?;
+        loaded.saved = loaded.names.clone();
+        Ok(())
+    })?
+    .unwrap_or(Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::testing;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn ordinary_vaults_have_no_ids() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::plain_vault(tmp.path());
+        assert_eq!(id_for(&cfg, "bank/chase")?, None);
+        assert_eq!(
+            cfg.entry_path("bank/chase")?,
+            cfg.prefix.join("bank/chase.rage")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn names_resolve_through_the_manifest() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::plain_vault(tmp.path());
+        save(&cfg, &BTreeMap::new())?;
+
+        let path = cfg.entry_path("bank/chase")?;
+        assert_eq!(path.parent(), Some(cfg.prefix.as_path()));
+        assert!(!path.to_string_lossy().contains("chase"));
+        assert_eq!(cfg.entry_path("bank/chase")?, path, "IDs are stable");
+        fs::write(&path, "pw")?;
+        cfg.entry_path("never/written")?;
+
+        flush(&cfg.prefix)?;
+        let saved = fs::read_to_string(cfg.prefix.join(".passman-manifest.rage"))?;
+        let manifest: Manifest = serde_json::from_str(&saved)?;
+        assert_eq!(
+            manifest.entries.keys().collect::<Vec<_>>(),
+            vec!["bank/chase"],
+            "unused IDs are not saved"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unreadable_manifests_are_an_error() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::plain_vault(tmp.path());
+        fs::write(cfg.prefix.join(".passman-manifest.rage"), "not json")?;
+        let err = open(&cfg).unwrap_err();
+        assert!(format!("{:#}", err).contains("corrupted"), "{:#}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn renames_entries_and_folders() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = testing::plain_vault(tmp.path());
+        save(&cfg, &BTreeMap::new())?;
+        for name in ["team/a", "team/b", "mine"] {
+            fs::write(cfg.entry_path(name)?, name)?;
+        }
+
+        assert_eq!(rename(&cfg, "team", "ops")?, 2);
+        assert_eq!(fs::read_to_string(cfg.entry_path("ops/b")?)?, "team/b");
+        assert_eq!(
+            rename(&cfg, "mine", "ops/a").unwrap_err().to_string(),
+            "ops/a already exists"
+        );
+        assert_eq!(
+            rename(&cfg, "team", "x").unwrap_err().to_string(),
+            "No such entry or folder: team"
+        );
+        let names: Vec<String> = entries(&cfg.prefix)?
+            .unwrap()
+            .into_iter()
+            .map(|(n, _)| n)
+            .collect();
+        assert_eq!(names, vec!["mine", "ops/a", "ops/b"]);
+        Ok(())
+    }
+}
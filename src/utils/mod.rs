@@ -1,4 +1,14 @@
+pub mod age_header;
+pub mod agent;
+pub mod bundle;
+pub mod entries;
+pub mod expiry;
 pub mod gather_config;
 pub mod git_ops;
+pub mod hibp;
+pub mod index;
 pub mod keygen;
+pub mod manifest;
 pub mod qr;
+pub mod secure_fs;
+pub mod signing;
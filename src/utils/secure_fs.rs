@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// Permission bits that let anyone but the owner in
+const OTHERS: u32 = 0o077;
+
+/// Create `path` (and missing parents) readable by the owner only, and
+/// tighten it if it already exists
+pub fn private_dir(path: &Path) -> Result<()> {
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(path)
+        .with_context(|| format!("Failed to create directory {}", path.display()))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o700))
+        .with_context(|| format!("Failed to restrict {}", path.display()))
+}
+
+/// Make `path` readable and writable by the owner only
+pub fn private_file(path: &Path) -> Result<()> {
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict {}", path.display()))
+}
+
+/// Refuse to go on when anyone else can read the identity; warn when its
+/// group can. A missing identity is left for the command to report.
+pub fn check_identity(path: &Path) -> Result<()> {
+    let Ok(meta) = fs::metadata(path) else {
+        return Ok(());
+    };
+    let mode = meta.permissions().mode() & 0o777;
+    if mode & 0o007 != 0 {
+        anyhow::bail!(
+            "Your identity {} is readable by other users (mode {:04o}); \
+             run `chmod 600 {}` and consider it compromised",
+            path.display(),
+            mode,
+            path.display()
+        );
+    }
+    if mode & OTHERS != 0 {
+        eprintln!(
+            "⚠️  Your identity {} is readable by its group (mode {:04o}); run `chmod 600 {}`",
+            path.display(),
+            mode,
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Private directory for decrypted plaintext: under `$XDG_RUNTIME_DIR`
+/// (per-user tmpfs), else `/dev/shm`, so it never reaches a disk or the
+/// shared `/tmp`
+pub fn scratch_dir() -> Result<PathBuf> {
+    match env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from) {
+        Some(runtime) if runtime.is_dir() => scratch_dir_in(&runtime, "passman"),
+        _ => {
+            let name = format!("passman-{}", own_uid());
+            let shm = Path::new("/dev/shm");
+            if shm.is_dir() {
+                scratch_dir_in(shm, &name)
+            } else {
+                scratch_dir_in(&env::temp_dir(), &name)
+            }
+        }
+    }
+}
+
+/// `parent/name` as a 0700 directory owned by us. Anything else already
+/// there (someone else's directory, a symlink) is refused.
+fn scratch_dir_in(parent: &Path, name: &str) -> Result<PathBuf> {
+    let dir = parent.join(name);
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to create {}", dir.display()));
+        }
+    }
+    let meta = fs::symlink_metadata(&dir)?;
+    if !meta.is_dir() || meta.uid() != own_uid() {
+        anyhow::bail!(
+            "{} is not a directory owned by you; refusing to put plaintext there",
+            dir.display()
+        );
+    }
+    if meta.permissions().mode() & OTHERS != 0 {
+        private_dir(&dir)?;
+    }
+    Ok(dir)
+}
+
+/// A new 0600 temp file in `scratch_dir`, removed when dropped
+pub fn scratch_file() -> Result<NamedTempFile> {
+    let dir = scratch_dir()?;
+    tempfile::Builder::new()
+        .prefix("passman-")
+        .tempfile_in(&dir)
+        .with_context(|| format!("Failed to create temporary file in {}", dir.display()))
+}
+
+fn own_uid() -> u32 {
+    unsafe { libc::getuid() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn private_dirs_are_0700() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let dir = tmp.path().join("a/b");
+        private_dir(&dir)?;
+        assert_eq!(mode(&dir), 0o700);
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755))?;
+        private_dir(&dir)?;
+        assert_eq!(mode(&dir), 0o700, "existing directories are tightened");
+        Ok(())
+    }
+
+    #[test]
+    fn readable_identities_are_refused() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let id = tmp.path().join("private.rage");
+        check_identity(&id)?;
+
+        fs::write(&id, "AGE-SECRET-KEY-1")?;
+        fs::set_permissions(&id, fs::Permissions::from_mode(0o644))?;
+        assert!(
+            check_identity(&id)
+                .unwrap_err()
+                .to_string()
+                .contains("readable by other users")
+        );
+
+        fs::set_permissions(&id, fs::Permissions::from_mode(0o640))?;
+        check_identity(&id)?;
+        private_file(&id)?;
+        assert_eq!(mode(&id), 0o600);
+        Ok(())
+    }
+
+    #[test]
+    fn scratch_dir_is_private_and_ours() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let dir = scratch_dir_in(tmp.path(), "passman")?;
+        assert_eq!(mode(&dir), 0o700);
+
+        let elsewhere = TempDir::new()?;
+        std::os::unix::fs::symlink(elsewhere.path(), tmp.path().join("link"))?;
+        assert!(scratch_dir_in(tmp.path(), "link").is_err());
+        Ok(())
+    }
+}
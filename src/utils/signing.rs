@@ -0,0 +1,788 @@
+use crate::config::{self, Config};
+use crate::utils::index::INDEX_NAME;
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use git2::{Repository, StatusOptions};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Folder in the vault holding one signed manifest per device. One file
+/// each means two devices never edit the same file, so rebases stay clean.
+pub const SIGNATURES_DIR: &str = ".passman-signatures";
+
+/// This device's Ed25519 signing key, in `base_dir`
+pub const SIGNING_KEY_FILENAME: &str = "signing.key";
+
+/// Other devices whose signatures this device accepts, in `base_dir`. Kept
+/// outside the vault so a push can't add to it. This device's own key is
+/// always accepted and never listed.
+pub const TRUSTED_FILENAME: &str = "trusted-signers";
+
+/// Signed manifest layout version, bumped on incompatible changes. Version
+/// 1 manifests carry no sequence numbers and still verify, as sequence 0.
+pub const VERSION: u32 = 2;
+
+/// What one device vouches for: the SHA-256 of every file in the vault as
+/// it last wrote it, and the files it deleted, each with a sequence number
+/// so the newest signature for a file wins over older ones
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SignedManifest {
+    pub version: u32,
+    /// Signer's public key, base64
+    pub signer: String,
+    /// Human-readable device name
+    pub device: String,
+    /// Vault-relative path → SHA-256 (hex) of the file
+    pub files: BTreeMap<String, String>,
+    /// Vault-relative paths this device deleted
+    #[serde(default)]
+    pub removed: BTreeSet<String>,
+    /// Vault-relative path → one more than the highest sequence any trusted
+    /// device had given the file when this device signed its hash or removal
+    #[serde(default)]
+    pub sequence: BTreeMap<String, u64>,
+    /// Ed25519 signature over the other fields, base64
+    pub signature: String,
+}
+
+impl SignedManifest {
+    fn payload(&self) -> Vec<u8> {
+        let (signer, device, files, removed) =
+            (&self.signer, &self.device, &self.files, &self.removed);
+        match self.version {
+            1 => serde_json::to_vec(&(1, signer, device, files, removed)),
+            v => serde_json::to_vec(&(v, signer, device, files, removed, &self.sequence)),
+        }
+        .unwrap_or_default()
+    }
+
+    /// Sequence of the signature for `rel`, 0 for version 1 manifests
+    fn sequence_of(&self, rel: &str) -> u64 {
+        self.sequence.get(rel).copied().unwrap_or(0)
+    }
+
+    fn verify(&self) -> Result<()> {
+        let key = decode_key(&self.signer)?;
+        let raw = STANDARD
+            .decode(&self.signature)
+            .context("Signature is not base64")?;
+        let signature = Signature::from_slice(&raw).context("Malformed signature")?;
+        key.verify(&self.payload(), &signature)
+            .context("Signature does not match")
+    }
+}
+
+/// A device allowed to vouch for vault contents
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedSigner {
+    pub key: String,
+    pub device: String,
+}
+
+/// Outcome of checking the vault against its signed manifests
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Devices whose manifests were accepted
+    pub trusted: Vec<String>,
+    /// Validly signed manifests from devices this device doesn't trust
+    pub untrusted: Vec<TrustedSigner>,
+    /// Manifest files whose signature doesn't verify
+    pub forged: Vec<String>,
+    /// Files no trusted device vouches for in their current state
+    pub unsigned: Vec<String>,
+    /// Files put back to a state a trusted device has since replaced
+    pub rolled_back: Vec<String>,
+    /// Files a trusted device vouches for that are gone, with no trusted
+    /// device saying it deleted them
+    pub missing: Vec<String>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.untrusted.is_empty()
+            && self.forged.is_empty()
+            && self.unsigned.is_empty()
+            && self.rolled_back.is_empty()
+            && self.missing.is_empty()
+    }
+
+    /// Print the problems (loudly) or a one-line all-clear
+    pub fn print(&self) {
+        if self.is_clean() {
+            println!(
+                "✅ Every file is signed by a trusted device ({})",
+                self.trusted.join(", ")
+            );
+            return;
+        }
+        eprintln!("🚨🚨 VAULT CONTENTS COULD NOT BE VERIFIED 🚨🚨");
+        for file in &self.forged {
+            eprintln!("   ✗ {} has an invalid signature", file);
+        }
+        for signer in &self.untrusted {
+            eprintln!(
+                "   ✗ signed by untrusted device `{}` (key {})",
+                signer.device, signer.key
+            );
+        }
+        for file in &self.unsigned {
+            eprintln!("   ✗ {} changed without a trusted signature", file);
+        }
+        for file in &self.rolled_back {
+            eprintln!("   ✗ {} was put back to an older signed version", file);
+        }
+        for file in &self.missing {
+            eprintln!("   ✗ {} was deleted without a trusted signature", file);
+        }
+        eprintln!("   Do not use these entries until you know who changed them.");
+        if !self.untrusted.is_empty() {
+            eprintln!(
+                "   If a device above is yours, trust it with `passman verify --trust <key>`."
+            );
+        }
+    }
+}
+
+fn decode_key(b64: &str) -> Result<VerifyingKey> {
+    let raw = STANDARD.decode(b64).context("Key is not base64")?;
+    let bytes: [u8; 32] = raw
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Key must be 32 bytes"))?;
+    VerifyingKey::from_bytes(&bytes).context("Invalid Ed25519 key")
+}
+
+/// This device's signing key, created on first use
+pub fn device_key(config: &Config) -> Result<SigningKey> {
+    if let Some(key) = existing_key(config)? {
+        return Ok(key);
+    }
+
+    let path = config.base_dir.join(SIGNING_KEY_FILENAME);
+    let key = SigningKey::generate(&mut OsRng);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    writeln!(file, "{}", STANDARD.encode(key.to_bytes()))?;
+    Ok(key)
+}
+
+/// This device's signing key, if it has made one
+fn existing_key(config: &Config) -> Result<Option<SigningKey>> {
+    let path = config.base_dir.join(SIGNING_KEY_FILENAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let raw =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let bytes: [u8; 32] = STANDARD
+        .decode(raw.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .with_context(|| format!("{} is not a signing key", path.display()))?;
+    Ok(Some(SigningKey::from_bytes(&bytes)))
+}
+
+/// Base64 public half of `key`
+pub fn public_key(key: &SigningKey) -> String {
+    STANDARD.encode(key.verifying_key().as_bytes())
+}
+
+/// The name this device signs under
+pub fn device_name() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unnamed-device".into())
+}
+
+/// Devices this device accepts signatures from
+pub fn trusted(config: &Config) -> Result<Vec<TrustedSigner>> {
+    let path = config.base_dir.join(TRUSTED_FILENAME);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let raw =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(raw
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| {
+            let (key, device) = l.split_once(' ').unwrap_or((l, ""));
+            TrustedSigner {
+                key: key.to_string(),
+                device: device.trim().to_string(),
+            }
+        })
+        .collect())
+}
+
+/// Add `signer` to this device's trusted signers
+pub fn trust(config: &Config, signer: &TrustedSigner) -> Result<()> {
+    decode_key(&signer.key)?;
+    if trusted(config)?.iter().any(|t| t.key == signer.key) {
+        return Ok(());
+    }
+    let path = config.base_dir.join(TRUSTED_FILENAME);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{} {}", signer.key, signer.device)?;
+    Ok(())
+}
+
+/// Stop accepting signatures from `device`; returns how many keys it had
+pub fn untrust(config: &Config, device: &str) -> Result<usize> {
+    let path = config.base_dir.join(TRUSTED_FILENAME);
+    let signers = trusted(config)?;
+    let (dropped, kept): (Vec<_>, Vec<_>) = signers.into_iter().partition(|t| t.device == device);
+    if dropped.is_empty() {
+        return Ok(0);
+    }
+    let raw: String = kept
+        .iter()
+        .map(|t| format!("{} {}\n", t.key, t.device))
+        .collect();
+    fs::write(&path, raw).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(dropped.len())
+}
+
+/// SHA-256 of every file the signatures cover: everything in the vault
+/// except `.git`, the signatures themselves and the local search index
+pub fn hashes(prefix: &Path) -> Result<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+    let walker = WalkDir::new(prefix)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git" && e.file_name() != SIGNATURES_DIR);
+    for entry in walker {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel = entry
+            .path()
+            .strip_prefix(prefix)
+            .unwrap_or(entry.path())
+            .to_string_lossy()
+            .to_string();
+        if rel.starts_with(INDEX_NAME) {
+            continue;
+        }
+        hashes.insert(rel, hash_file(entry.path())?);
+    }
+    Ok(hashes)
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(Sha256::digest(&bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Vault-relative paths this device changed since the last commit, i.e.
+/// what it is about to commit. `None` when there is no commit yet, so the
+/// whole vault is this device's.
+fn own_changes(prefix: &Path) -> Option<BTreeSet<String>> {
+    let repo = Repository::discover(prefix).ok()?;
+    repo.head().ok()?;
+    let workdir = repo.workdir()?.canonicalize().ok()?;
+    let vault = prefix.canonicalize().ok()?;
+    let vault = vault.strip_prefix(&workdir).ok()?;
+
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    let statuses = repo.statuses(Some(&mut options)).ok()?;
+    Some(
+        statuses
+            .iter()
+            .filter_map(|s| {
+                let path = PathBuf::from(s.path()?);
+                let rel = path.strip_prefix(vault).ok()?.to_string_lossy().to_string();
+                (!rel.starts_with(SIGNATURES_DIR) && !rel.starts_with(INDEX_NAME)).then_some(rel)
+            })
+            .collect(),
+    )
+}
+
+/// Where `key`'s manifest lives in the vault
+fn manifest_path(prefix: &Path, key: &SigningKey) -> PathBuf {
+    let id: String = key.verifying_key().as_bytes()[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    prefix.join(SIGNATURES_DIR).join(format!("{}.json", id))
+}
+
+/// Sign the vault's current contents as this device, vouching for every
+/// file as it is now. Only for when someone has reviewed them.
+pub fn sign(config: &Config) -> Result<()> {
+    let key = device_key(config)?;
+    let files = hashes(&config.prefix)?;
+    let mut removed = BTreeSet::new();
+    if let Some(previous) = own_manifest(config, &key) {
+        removed = previous.removed;
+        removed.extend(previous.files.into_keys());
+    }
+    removed.retain(|rel| !files.contains_key(rel));
+    write_manifest(config, &key, files, removed)
+}
+
+/// Vouch for this device's own changes only: its previous manifest is
+/// carried forward and just the files it is about to commit are hashed
+/// again, so files pulled from elsewhere are never signed by accident.
+pub fn sign_changes(config: &Config) -> Result<()> {
+    let Some(changed) = own_changes(&config.prefix) else {
+        return sign(config);
+    };
+    let key = device_key(config)?;
+    let (mut files, mut removed) = own_manifest(config, &key)
+        .map(|m| (m.files, m.removed))
+        .unwrap_or_default();
+    for rel in changed {
+        let path = config.prefix.join(&rel);
+        if path.is_file() {
+            files.insert(rel.clone(), hash_file(&path)?);
+            removed.remove(&rel);
+        } else {
+            files.remove(&rel);
+            removed.insert(rel);
+        }
+    }
+    write_manifest(config, &key, files, removed)
+}
+
+/// This device's last manifest, if it is there and really signed by `key`
+fn own_manifest(config: &Config, key: &SigningKey) -> Option<SignedManifest> {
+    let raw = fs::read_to_string(manifest_path(&config.prefix, key)).ok()?;
+    let manifest: SignedManifest = serde_json::from_str(&raw).ok()?;
+    (manifest.signer == public_key(key) && manifest.verify().is_ok()).then_some(manifest)
+}
+
+fn write_manifest(
+    config: &Config,
+    key: &SigningKey,
+    files: BTreeMap<String, String>,
+    removed: BTreeSet<String>,
+) -> Result<()> {
+    let sequence = sequence_for(config, key, &files, &removed)?;
+    let mut manifest = SignedManifest {
+        version: VERSION,
+        signer: public_key(key),
+        device: device_name(),
+        files,
+        removed,
+        sequence,
+        signature: String::new(),
+    };
+    manifest.signature = STANDARD.encode(key.sign(&manifest.payload()).to_bytes());
+
+    let path = manifest_path(&config.prefix, key);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(&manifest)? + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Sequence numbers for a manifest about to be written: entries unchanged
+/// since this device's last manifest keep theirs, the rest go one past the
+/// highest any trusted device has signed for that file
+fn sequence_for(
+    config: &Config,
+    key: &SigningKey,
+    files: &BTreeMap<String, String>,
+    removed: &BTreeSet<String>,
+) -> Result<BTreeMap<String, u64>> {
+    let previous = own_manifest(config, key);
+    let mut highest: BTreeMap<String, u64> = BTreeMap::new();
+    let trusted_keys = trusted_keys(config)?;
+    for (_, manifest) in manifests(&config.prefix) {
+        let Ok(manifest) = manifest.and_then(|m| m.verify().map(|_| m)) else {
+            continue;
+        };
+        if !trusted_keys.iter().any(|t| t.key == manifest.signer) {
+            continue;
+        }
+        for rel in manifest.files.keys().chain(&manifest.removed) {
+            let seen = highest.entry(rel.clone()).or_default();
+            *seen = (*seen).max(manifest.sequence_of(rel));
+        }
+    }
+
+    let mut sequence = BTreeMap::new();
+    for (rel, hash) in files {
+        let kept = previous
+            .as_ref()
+            .filter(|p| p.files.get(rel) == Some(hash))
+            .map(|p| p.sequence_of(rel));
+        let next = highest.get(rel).copied().unwrap_or(0).saturating_add(1);
+        sequence.insert(rel.clone(), kept.unwrap_or(next));
+    }
+    for rel in removed {
+        let kept = previous
+            .as_ref()
+            .filter(|p| p.removed.contains(rel))
+            .map(|p| p.sequence_of(rel));
+        let next = highest.get(rel).copied().unwrap_or(0).saturating_add(1);
+        sequence.insert(rel.clone(), kept.unwrap_or(next));
+    }
+    Ok(sequence)
+}
+
+/// This device plus those in the trusted signers file
+fn trusted_keys(config: &Config) -> Result<Vec<TrustedSigner>> {
+    let mut keys = trusted(config)?;
+    if let Some(own) = existing_key(config)? {
+        keys.push(TrustedSigner {
+            key: public_key(&own),
+            device: device_name(),
+        });
+    }
+    Ok(keys)
+}
+
+/// Check every file against the manifests of trusted devices: this one and
+/// those in the trusted signers file. Nobody is trusted implicitly, not
+/// even on a fresh clone; other devices need `passman verify --trust`.
+/// Only the highest-sequence signatures for a file count, so putting back
+/// an older signed ciphertext doesn't verify.
+pub fn verify(config: &Config) -> Result<Report> {
+    let mut report = Report::default();
+    let trusted_keys = trusted_keys(config)?;
+
+    // path → (sequence, states signed at it: a hash, or None for removed)
+    let mut newest: BTreeMap<String, (u64, BTreeSet<Option<String>>)> = BTreeMap::new();
+    let mut vouched: BTreeSet<(String, String)> = BTreeSet::new();
+    for (file, manifest) in manifests(&config.prefix) {
+        let manifest = match manifest.and_then(|m| m.verify().map(|_| m)) {
+            Ok(m) => m,
+            Err(_) => {
+                report.forged.push(file);
+                continue;
+            }
+        };
+        let signer = TrustedSigner {
+            key: manifest.signer.clone(),
+            device: manifest.device.clone(),
+        };
+        if !trusted_keys.iter().any(|t| t.key == signer.key) {
+            report.untrusted.push(signer);
+            continue;
+        }
+        report.trusted.push(manifest.device.clone());
+        let states = manifest
+            .files
+            .iter()
+            .map(|(rel, hash)| (rel, Some(hash.clone())))
+            .chain(manifest.removed.iter().map(|rel| (rel, None)));
+        for (rel, state) in states {
+            let sequence = manifest.sequence_of(rel);
+            let slot = newest.entry(rel.clone()).or_default();
+            if sequence > slot.0 || slot.1.is_empty() {
+                *slot = (sequence, BTreeSet::new());
+            }
+            if sequence == slot.0 {
+                slot.1.insert(state.clone());
+            }
+            if let Some(hash) = state {
+                vouched.insert((rel.clone(), hash));
+            }
+        }
+    }
+
+    let present = hashes(&config.prefix)?;
+    for (file, hash) in &present {
+        let current = Some(hash.clone());
+        if newest
+            .get(file)
+            .is_some_and(|(_, states)| states.contains(&current))
+        {
+            continue;
+        }
+        if vouched.contains(&(file.clone(), hash.clone())) {
+            report.rolled_back.push(file.clone());
+        } else {
+            report.unsigned.push(file.clone());
+        }
+    }
+    report.missing = newest
+        .into_iter()
+        .filter(|(file, (_, states))| !present.contains_key(file) && !states.contains(&None))
+        .map(|(file, _)| file)
+        .collect();
+    Ok(report)
+}
+
+/// Every manifest file in the vault with its parsed contents
+fn manifests(prefix: &Path) -> Vec<(String, Result<SignedManifest>)> {
+    let dir = prefix.join(SIGNATURES_DIR);
+    let Ok(read) = fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut found: Vec<_> = read
+        .filter_map(Result::ok)
+        .filter(|e| e.path().extension().is_some_and(|x| x == "json"))
+        .map(|e| {
+            let name = format!("{}/{}", SIGNATURES_DIR, e.file_name().to_string_lossy());
+            let parsed = fs::read_to_string(e.path())
+                .map_err(anyhow::Error::from)
+                .and_then(|raw| Ok(serde_json::from_str(&raw)?));
+            (name, parsed)
+        })
+        .collect();
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+    found
+}
+
+/// Sign this device's changes to the registered vault at `prefix` before
+/// they are committed. A vault with files no trusted device vouches for
+/// stops the sync, so tampered files never travel on with our commit.
+pub fn sign_vault(prefix: &Path) -> Result<()> {
+    let Some(config) = config::registered(prefix) else {
+        return Ok(());
+    };
+    sign_changes(&config).context("Could not sign the vault")?;
+    let report = verify(&config)?;
+    if !report.is_clean() {
+        report.print();
+        anyhow::bail!("Not syncing a vault that doesn't verify; review it with `passman verify`");
+    }
+    Ok(())
+}
+
+/// Verify the registered vault at `prefix` after a pull. Anything no
+/// trusted device signed stops the sync before it pushes.
+pub fn check_pulled(prefix: &Path) -> Result<()> {
+    let Some(config) = config::registered(prefix) else {
+        return Ok(());
+    };
+    let report = verify(&config).context("Could not verify the pulled vault")?;
+    if !report.is_clean() {
+        report.print();
+        anyhow::bail!("The pulled vault doesn't verify; review it with `passman verify`");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn signed_vault_verifies_until_tampered() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = test_config(tmp.path());
+        fs::write(cfg.prefix.join("a.rage"), "ciphertext")?;
+        fs::write(cfg.prefix.join(".recipients"), "age1me\n")?;
+        sign(&cfg)?;
+        assert!(verify(&cfg)?.is_clean());
+
+        fs::write(cfg.prefix.join("a.rage"), "attacker's ciphertext")?;
+        fs::write(cfg.prefix.join("b.rage"), "planted")?;
+        let report = verify(&cfg)?;
+        assert_eq!(report.unsigned, vec!["a.rage", "b.rage"]);
+
+        sign(&cfg)?;
+        assert!(verify(&cfg)?.is_clean(), "our own changes are vouched for");
+        Ok(())
+    }
+
+    fn commit_all(dir: &Path) -> Result<()> {
+        let repo = Repository::open(dir)?;
+        let mut index = repo.index()?;
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None)?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let sig = git2::Signature::now("t", "t@example.com")?;
+        let parents = match repo.head() {
+            Ok(head) => vec![head.peel_to_commit()?],
+            Err(_) => Vec::new(),
+        };
+        let parents: Vec<_> = parents.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, "sync", &tree, &parents)?;
+        Ok(())
+    }
+
+    #[test]
+    fn syncs_sign_only_this_devices_changes() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = test_config(tmp.path());
+        Repository::init(&cfg.prefix)?;
+        crate::config::register(&cfg);
+        fs::write(cfg.prefix.join("a.rage"), "ciphertext")?;
+        sign_vault(&cfg.prefix)?;
+        commit_all(&cfg.prefix)?;
+
+        // a pull brings in a swapped file, then we add an entry
+        fs::write(cfg.prefix.join("a.rage"), "attacker's ciphertext")?;
+        commit_all(&cfg.prefix)?;
+        fs::write(cfg.prefix.join("b.rage"), "mine")?;
+
+        let err = sign_vault(&cfg.prefix).unwrap_err();
+        assert!(err.to_string().contains("Not syncing"), "{}", err);
+        let report = verify(&cfg)?;
+        assert_eq!(report.unsigned, vec!["a.rage"], "b.rage is ours");
+        Ok(())
+    }
+
+    #[test]
+    fn other_devices_need_trust() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let laptop = test_config(&tmp.path().join("laptop"));
+        let intruder = test_config(&tmp.path().join("intruder"));
+        // both work on the same vault
+        let intruder = Config {
+            prefix: laptop.prefix.clone(),
+            ..intruder
+        };
+        fs::write(laptop.prefix.join("a.rage"), "ciphertext")?;
+        sign(&laptop)?;
+
+        fs::write(laptop.prefix.join("a.rage"), "swapped")?;
+        sign(&intruder)?;
+        let report = verify(&laptop)?;
+        assert_eq!(report.untrusted.len(), 1);
+        assert_eq!(report.unsigned, vec!["a.rage"]);
+
+        trust(&laptop, &report.untrusted[0])?;
+        assert!(verify(&laptop)?.is_clean());
+        Ok(())
+    }
+
+    #[test]
+    fn nobody_is_trusted_on_first_use() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let laptop = test_config(&tmp.path().join("laptop"));
+        Repository::init(&laptop.prefix)?;
+        fs::write(laptop.prefix.join("a.rage"), "ciphertext")?;
+        sign(&laptop)?;
+        commit_all(&laptop.prefix)?;
+
+        // a fresh clone that signs before it ever verifies
+        let clone = Config {
+            prefix: laptop.prefix.clone(),
+            ..test_config(&tmp.path().join("clone"))
+        };
+        fs::write(clone.prefix.join("b.rage"), "mine")?;
+        sign_changes(&clone)?;
+        assert!(trusted(&clone)?.is_empty());
+        let report = verify(&clone)?;
+        assert_eq!(report.untrusted.len(), 1);
+        assert_eq!(report.unsigned, vec!["a.rage"]);
+
+        trust(&clone, &report.untrusted[0])?;
+        assert!(verify(&clone)?.is_clean());
+        Ok(())
+    }
+
+    #[test]
+    fn older_signed_versions_dont_verify() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let laptop = test_config(&tmp.path().join("laptop"));
+        let phone = Config {
+            prefix: laptop.prefix.clone(),
+            ..test_config(&tmp.path().join("phone"))
+        };
+        let as_signer = |cfg: &Config, device: &str| -> Result<TrustedSigner> {
+            Ok(TrustedSigner {
+                key: public_key(&device_key(cfg)?),
+                device: device.into(),
+            })
+        };
+        trust(&laptop, &as_signer(&phone, "phone")?)?;
+        trust(&phone, &as_signer(&laptop, "laptop")?)?;
+
+        fs::write(laptop.prefix.join("a.rage"), "old ciphertext")?;
+        fs::write(laptop.prefix.join("b.rage"), "ciphertext")?;
+        sign(&laptop)?;
+        fs::remove_file(laptop.prefix.join("b.rage"))?;
+        sign(&laptop)?;
+        fs::write(laptop.prefix.join("a.rage"), "new ciphertext")?;
+        sign(&phone)?;
+        assert!(verify(&laptop)?.is_clean());
+
+        // put back what the laptop signed before the deletion and the phone's edit
+        fs::write(laptop.prefix.join("a.rage"), "old ciphertext")?;
+        fs::write(laptop.prefix.join("b.rage"), "ciphertext")?;
+        let report = verify(&laptop)?;
+        assert_eq!(report.rolled_back, vec!["a.rage"]);
+        assert_eq!(
+            report.unsigned,
+            vec!["b.rage"],
+            "its hash went with the deletion"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pulls_that_dont_verify_stop_the_sync() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = test_config(tmp.path());
+        crate::config::register(&cfg);
+        fs::write(cfg.prefix.join("a.rage"), "ciphertext")?;
+        sign(&cfg)?;
+        check_pulled(&cfg.prefix)?;
+
+        fs::write(cfg.prefix.join("a.rage"), "attacker's ciphertext")?;
+        let err = check_pulled(&cfg.prefix).unwrap_err();
+        assert!(err.to_string().contains("doesn't verify"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn deleted_files_need_a_signature_too() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = test_config(tmp.path());
+        fs::write(cfg.prefix.join("a.rage"), "ciphertext")?;
+        fs::write(cfg.prefix.join("b.rage"), "ciphertext")?;
+        sign(&cfg)?;
+
+        fs::remove_file(cfg.prefix.join("b.rage"))?;
+        assert_eq!(verify(&cfg)?.missing, vec!["b.rage"]);
+
+        sign(&cfg)?;
+        assert!(verify(&cfg)?.is_clean(), "our own deletion is vouched for");
+        Ok(())
+    }
+
+    #[test]
+    fn forged_manifests_are_rejected() -> Result<()> {
+        let tmp = TempDir::new()?;
+        let cfg = test_config(tmp.path());
+        fs::write(cfg.prefix.join("a.rage"), "ciphertext")?;
+        sign(&cfg)?;
+
+        let path = manifest_path(&cfg.prefix, &device_key(&cfg)?);
+        let mut manifest: SignedManifest = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        manifest.files.insert("b.rage".into(), "00".into());
+        fs::write(&path, serde_json::to_string(&manifest)?)?;
+
+        let report = verify(&cfg)?;
+        assert_eq!(report.forged.len(), 1);
+        assert_eq!(report.unsigned, vec!["a.rage"]);
+        Ok(())
+    }
+}